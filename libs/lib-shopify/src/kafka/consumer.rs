@@ -0,0 +1,84 @@
+use std::marker::PhantomData;
+
+use rdkafka::{
+    ClientConfig, Message,
+    consumer::{Consumer, StreamConsumer},
+    message::Headers,
+};
+use tracing::warn;
+
+use super::events::{DecodeEvent, Encoding};
+
+/// A Kafka consumer that decodes event envelopes into `T`
+///
+/// The wire encoding comes from each message's `content-type` header.
+///
+/// Offsets are committed automatically, so a crash may skip messages that were
+/// received but not yet handled; consumers should be idempotent and have a
+/// periodic catch-up path.
+pub struct EventConsumer<T> {
+    consumer: StreamConsumer,
+    marker: PhantomData<fn() -> T>,
+}
+
+impl<T: DecodeEvent> EventConsumer<T> {
+    /// Join `group_id` and subscribe to `topics`
+    pub fn new(brokers: &str, group_id: &str, topics: &[&str]) -> anyhow::Result<Self> {
+        let consumer: StreamConsumer = ClientConfig::new()
+            .set("bootstrap.servers", brokers)
+            .set("group.id", group_id)
+            .set("enable.auto.commit", "true")
+            .set("auto.offset.reset", "earliest")
+            .create()
+            .map_err(|e| anyhow::anyhow!("Failed to create Kafka consumer: {}", e))?;
+
+        consumer
+            .subscribe(topics)
+            .map_err(|e| anyhow::anyhow!("Failed to subscribe to {:?}: {}", topics, e))?;
+
+        Ok(Self {
+            consumer,
+            marker: PhantomData,
+        })
+    }
+
+    /// Wait for the next message that decodes as `T`
+    ///
+    /// Messages without a payload or with a payload that is not a `T` are
+    /// logged and skipped.
+    pub async fn recv(&self) -> anyhow::Result<T> {
+        loop {
+            let message = self
+                .consumer
+                .recv()
+                .await
+                .map_err(|e| anyhow::anyhow!("Kafka error: {}", e))?;
+
+            let Some(payload) = message.payload() else {
+                warn!(
+                    "Skipping message without payload on {} at offset {}",
+                    message.topic(),
+                    message.offset()
+                );
+                continue;
+            };
+
+            let content_type = message.headers().and_then(|headers| {
+                headers
+                    .iter()
+                    .find(|header| header.key == "content-type")
+                    .and_then(|header| header.value)
+            });
+
+            match T::decode_event(payload, Encoding::from_content_type(content_type)) {
+                Ok(event) => return Ok(event),
+                Err(e) => warn!(
+                    "Skipping undecodable message on {} at offset {}: {}",
+                    message.topic(),
+                    message.offset(),
+                    e
+                ),
+            }
+        }
+    }
+}
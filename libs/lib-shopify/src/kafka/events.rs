@@ -0,0 +1,284 @@
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+use uuid::Uuid;
+
+/// Topic the shopify-consumer publishes synced order, product and refund changes to
+pub const SHOPIFY_CHANGES_TOPIC: &str = "shopify.changes";
+
+/// Topic ad spend imports are announced on
+pub const AD_SPEND_TOPIC: &str = "ads.spend";
+
+/// Topic the cost engine announces recomputed profit snapshots on
+pub const PROFIT_TOPIC: &str = "profit.snapshots";
+
+/// A payload that can travel in an [`EventEnvelope`]
+///
+/// Bump `SCHEMA_VERSION` on changes that older consumers cannot read (removed
+/// or retyped fields); adding optional fields keeps the version.
+pub trait Event: Serialize + DeserializeOwned {
+    /// Name stored in the envelope's `event_type`
+    const EVENT_TYPE: &'static str;
+    /// Latest schema version of the payload
+    const SCHEMA_VERSION: u16;
+    /// Topic the event is published to
+    const TOPIC: &'static str;
+}
+
+/// How an envelope is encoded on the wire, sent in the `content-type` header
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Encoding {
+    #[default]
+    Json,
+    /// MessagePack with field names, for high-volume topics
+    MessagePack,
+}
+
+impl Encoding {
+    pub fn content_type(self) -> &'static str {
+        match self {
+            Self::Json => "application/json",
+            Self::MessagePack => "application/msgpack",
+        }
+    }
+
+    /// Encoding for a `content-type` header; JSON when it is missing or unknown
+    pub fn from_content_type(content_type: Option<&[u8]>) -> Self {
+        match content_type {
+            Some(b"application/msgpack") => Self::MessagePack,
+            _ => Self::Json,
+        }
+    }
+}
+
+/// Metadata shared by every event, wrapped around its payload
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EventEnvelope<E> {
+    pub event_id: Uuid,
+    pub event_type: String,
+    pub schema_version: u16,
+    pub merchant_id: Uuid,
+    pub occurred_at: DateTime<Utc>,
+    pub payload: E,
+}
+
+/// Just the envelope fields needed to pick the payload type
+#[derive(Deserialize)]
+struct EnvelopeHeader {
+    event_type: String,
+    schema_version: u16,
+}
+
+impl<E: Event> EventEnvelope<E> {
+    /// Wrap `payload` in a new envelope with a fresh event id, stamped now
+    pub fn new(merchant_id: Uuid, payload: E) -> Self {
+        Self {
+            event_id: Uuid::new_v4(),
+            event_type: E::EVENT_TYPE.to_string(),
+            schema_version: E::SCHEMA_VERSION,
+            merchant_id,
+            occurred_at: Utc::now(),
+            payload,
+        }
+    }
+
+    /// Message key: events of one merchant land on the same partition, in order
+    pub fn key(&self) -> String {
+        self.merchant_id.to_string()
+    }
+
+    pub fn encode(&self, encoding: Encoding) -> anyhow::Result<Vec<u8>> {
+        let bytes = match encoding {
+            Encoding::Json => serde_json::to_vec(self)?,
+            Encoding::MessagePack => rmp_serde::to_vec_named(self)?,
+        };
+        Ok(bytes)
+    }
+
+    /// Decode an envelope of this event type, rejecting other types and newer
+    /// schema versions than this build knows
+    pub fn decode(bytes: &[u8], encoding: Encoding) -> anyhow::Result<Self> {
+        let header: EnvelopeHeader = decode_as(bytes, encoding)?;
+        anyhow::ensure!(
+            header.event_type == E::EVENT_TYPE,
+            "expected a {} event, got {}",
+            E::EVENT_TYPE,
+            header.event_type
+        );
+        anyhow::ensure!(
+            header.schema_version <= E::SCHEMA_VERSION,
+            "{} schema version {} is newer than the supported {}",
+            E::EVENT_TYPE,
+            header.schema_version,
+            E::SCHEMA_VERSION
+        );
+
+        decode_as(bytes, encoding)
+    }
+}
+
+fn decode_as<T: DeserializeOwned>(bytes: &[u8], encoding: Encoding) -> anyhow::Result<T> {
+    let value = match encoding {
+        Encoding::Json => serde_json::from_slice(bytes)?,
+        Encoding::MessagePack => rmp_serde::from_slice(bytes)?,
+    };
+    Ok(value)
+}
+
+/// Types an [`EventConsumer`](super::EventConsumer) can decode messages into
+pub trait DecodeEvent: Sized {
+    fn decode_event(bytes: &[u8], encoding: Encoding) -> anyhow::Result<Self>;
+}
+
+impl<E: Event> DecodeEvent for EventEnvelope<E> {
+    fn decode_event(bytes: &[u8], encoding: Encoding) -> anyhow::Result<Self> {
+        Self::decode(bytes, encoding)
+    }
+}
+
+// Events
+
+/// An order was created or updated
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OrderUpserted {
+    pub shopify_order_id: i64,
+    pub processed_at: Option<DateTime<Utc>>,
+}
+
+impl Event for OrderUpserted {
+    const EVENT_TYPE: &'static str = "order_upserted";
+    const SCHEMA_VERSION: u16 = 1;
+    const TOPIC: &'static str = SHOPIFY_CHANGES_TOPIC;
+}
+
+/// A product or its variants were created or updated
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ProductUpserted {
+    pub shopify_product_id: i64,
+}
+
+impl Event for ProductUpserted {
+    const EVENT_TYPE: &'static str = "product_upserted";
+    const SCHEMA_VERSION: u16 = 1;
+    const TOPIC: &'static str = SHOPIFY_CHANGES_TOPIC;
+}
+
+/// A refund was recorded against an order
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RefundCreated {
+    pub shopify_order_id: i64,
+    pub shopify_refund_id: i64,
+    pub processed_at: Option<DateTime<Utc>>,
+}
+
+impl Event for RefundCreated {
+    const EVENT_TYPE: &'static str = "refund_created";
+    const SCHEMA_VERSION: u16 = 1;
+    const TOPIC: &'static str = SHOPIFY_CHANGES_TOPIC;
+}
+
+/// Daily ad spend was imported for a range of days
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AdSpendImported {
+    pub start_date: NaiveDate,
+    pub end_date: NaiveDate,
+    pub rows_imported: u64,
+    pub campaigns: u64,
+}
+
+impl Event for AdSpendImported {
+    const EVENT_TYPE: &'static str = "ad_spend_imported";
+    const SCHEMA_VERSION: u16 = 1;
+    const TOPIC: &'static str = AD_SPEND_TOPIC;
+}
+
+/// Profit snapshots were recomputed for a range of local days
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ProfitRecomputed {
+    pub start_date: NaiveDate,
+    pub end_date: NaiveDate,
+    pub snapshots: u64,
+}
+
+impl Event for ProfitRecomputed {
+    const EVENT_TYPE: &'static str = "profit_recomputed";
+    const SCHEMA_VERSION: u16 = 1;
+    const TOPIC: &'static str = PROFIT_TOPIC;
+}
+
+/// Any event published on [`SHOPIFY_CHANGES_TOPIC`]
+#[derive(Debug, Clone, PartialEq)]
+pub enum ShopifyChangeEvent {
+    OrderUpserted(EventEnvelope<OrderUpserted>),
+    ProductUpserted(EventEnvelope<ProductUpserted>),
+    RefundCreated(EventEnvelope<RefundCreated>),
+}
+
+impl ShopifyChangeEvent {
+    pub fn merchant_id(&self) -> Uuid {
+        match self {
+            Self::OrderUpserted(envelope) => envelope.merchant_id,
+            Self::ProductUpserted(envelope) => envelope.merchant_id,
+            Self::RefundCreated(envelope) => envelope.merchant_id,
+        }
+    }
+}
+
+impl DecodeEvent for ShopifyChangeEvent {
+    fn decode_event(bytes: &[u8], encoding: Encoding) -> anyhow::Result<Self> {
+        let header: EnvelopeHeader = decode_as(bytes, encoding)?;
+        match header.event_type.as_str() {
+            OrderUpserted::EVENT_TYPE => {
+                Ok(Self::OrderUpserted(EventEnvelope::decode(bytes, encoding)?))
+            }
+            ProductUpserted::EVENT_TYPE => Ok(Self::ProductUpserted(EventEnvelope::decode(
+                bytes, encoding,
+            )?)),
+            RefundCreated::EVENT_TYPE => {
+                Ok(Self::RefundCreated(EventEnvelope::decode(bytes, encoding)?))
+            }
+            other => anyhow::bail!("unknown event type on {}: {}", SHOPIFY_CHANGES_TOPIC, other),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_envelopes_round_trip_in_both_encodings() {
+        let merchant_id = Uuid::new_v4();
+        let envelope = EventEnvelope::new(
+            merchant_id,
+            RefundCreated {
+                shopify_order_id: 1001,
+                shopify_refund_id: 2002,
+                processed_at: Some(Utc::now()),
+            },
+        );
+
+        for encoding in [Encoding::Json, Encoding::MessagePack] {
+            let bytes = envelope.encode(encoding).unwrap();
+            assert_eq!(
+                EventEnvelope::<RefundCreated>::decode(&bytes, encoding).unwrap(),
+                envelope
+            );
+            assert_eq!(
+                ShopifyChangeEvent::decode_event(&bytes, encoding).unwrap(),
+                ShopifyChangeEvent::RefundCreated(envelope.clone())
+            );
+            // Envelopes of another event type are refused
+            assert!(EventEnvelope::<OrderUpserted>::decode(&bytes, encoding).is_err());
+        }
+
+        let json = serde_json::to_value(&envelope).unwrap();
+        assert_eq!(json["event_type"], "refund_created");
+        assert_eq!(json["schema_version"], 1);
+        assert_eq!(json["merchant_id"], merchant_id.to_string());
+
+        let mut newer = envelope.clone();
+        newer.schema_version = RefundCreated::SCHEMA_VERSION + 1;
+        let bytes = newer.encode(Encoding::Json).unwrap();
+        assert!(EventEnvelope::<RefundCreated>::decode(&bytes, Encoding::Json).is_err());
+    }
+}
@@ -1,2 +1,11 @@
 pub mod consumer;
+pub mod events;
 pub mod producer;
+
+pub use consumer::EventConsumer;
+pub use events::{
+    AD_SPEND_TOPIC, AdSpendImported, DecodeEvent, Encoding, Event, EventEnvelope, OrderUpserted,
+    PROFIT_TOPIC, ProductUpserted, ProfitRecomputed, RefundCreated, SHOPIFY_CHANGES_TOPIC,
+    ShopifyChangeEvent,
+};
+pub use producer::{create_producer, publish};
@@ -2,12 +2,14 @@ use std::sync::Arc;
 use std::time::Duration;
 
 use rdkafka::{
+    ClientConfig,
     message::{Header, OwnedHeaders},
     producer::{FutureProducer, FutureRecord},
-    ClientConfig,
 };
 use tracing::info;
 
+use super::events::{Encoding, Event, EventEnvelope};
+
 /// Create a Kafka producer singleton that can be shared across threads
 pub fn create_producer(brokers: &str) -> anyhow::Result<Arc<FutureProducer>> {
     let producer: FutureProducer = ClientConfig::new()
@@ -19,40 +21,40 @@ pub fn create_producer(brokers: &str) -> anyhow::Result<Arc<FutureProducer>> {
     Ok(Arc::new(producer))
 }
 
-pub async fn produce(brokers: &str, topic_name: &str) {
-    let producer: &FutureProducer = &ClientConfig::new()
-        .set("bootstrap.servers", brokers)
-        .set("message.timeout.ms", "5000")
-        .create()
-        .expect("Producer creation error");
-
-    // This loop is non blocking: all messages will be sent one after the other, without waiting
-    // for the results.
-    let futures = (0..5)
-        .map(|i| async move {
-            // The send operation on the topic returns a future, which will be
-            // completed once the result or failure from Kafka is received.
-            let delivery_status = producer
-                .send(
-                    FutureRecord::to(topic_name)
-                        .payload(&format!("Message {}", i))
-                        .key(&format!("Key {}", i))
-                        .headers(OwnedHeaders::new().insert(Header {
-                            key: "header_key",
-                            value: Some("header_value"),
-                        })),
-                    Duration::from_secs(0),
-                )
-                .await;
-
-            // This will be executed when the result is received.
-            info!("Delivery status for message {} received", i);
-            delivery_status
+/// Publish an event to its topic, keyed by merchant, and wait for the delivery
+/// report
+pub async fn publish<E: Event>(
+    producer: &FutureProducer,
+    envelope: &EventEnvelope<E>,
+    encoding: Encoding,
+) -> anyhow::Result<()> {
+    let payload = envelope.encode(encoding)?;
+    let headers = OwnedHeaders::new()
+        .insert(Header {
+            key: "content-type",
+            value: Some(encoding.content_type()),
         })
-        .collect::<Vec<_>>();
+        .insert(Header {
+            key: "event-type",
+            value: Some(E::EVENT_TYPE),
+        });
+
+    producer
+        .send(
+            FutureRecord::to(E::TOPIC)
+                .payload(&payload)
+                .key(&envelope.key())
+                .headers(headers),
+            Duration::from_secs(0),
+        )
+        .await
+        .map_err(|(e, _)| anyhow::anyhow!("Failed to deliver {} event: {}", E::EVENT_TYPE, e))?;
 
-    // This loop will wait until all delivery statuses have been received.
-    for future in futures {
-        info!("Future completed. Result: {:?}", future.await);
-    }
+    info!(
+        "Published {} event {} to {}",
+        E::EVENT_TYPE,
+        envelope.event_id,
+        E::TOPIC
+    );
+    Ok(())
 }
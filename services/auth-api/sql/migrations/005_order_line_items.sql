@@ -0,0 +1,20 @@
+-- 005_order_line_items.sql
+-- order_line_items: one row per Shopify line item; the basis for COGS and per-product metrics
+CREATE TABLE order_line_items (
+	id                      BIGSERIAL PRIMARY KEY,
+	merchant_id             UUID NOT NULL REFERENCES merchants(id) ON DELETE CASCADE,
+	order_id                BIGINT NOT NULL REFERENCES orders(id) ON DELETE CASCADE,
+	shopify_line_item_id    BIGINT NOT NULL,
+	shopify_product_id      BIGINT,
+	shopify_variant_id      BIGINT,
+	sku                     TEXT,
+	title                   TEXT,
+	quantity                INTEGER NOT NULL,
+	price                   NUMERIC(14,4) NOT NULL,
+	total_discount          NUMERIC(14,4) NOT NULL DEFAULT 0, -- sum of discount allocations
+	created_at              TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+	updated_at              TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+CREATE UNIQUE INDEX ux_order_line_items_shopify ON order_line_items(merchant_id, shopify_line_item_id);
+CREATE INDEX idx_order_line_items_order ON order_line_items(order_id);
+CREATE INDEX idx_order_line_items_variant ON order_line_items(merchant_id, shopify_variant_id);
@@ -0,0 +1,4 @@
+-- 007_variant_pricing.sql
+-- Selling price and inventory item link for variants, synced from Shopify
+ALTER TABLE variants ADD COLUMN price NUMERIC(14,4);
+ALTER TABLE variants ADD COLUMN shopify_inventory_item_id BIGINT;
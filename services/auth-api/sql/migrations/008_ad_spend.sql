@@ -0,0 +1,45 @@
+-- 008_ad_spend.sql
+-- ad_accounts: one row per advertising account on an ad platform
+CREATE TABLE ad_accounts (
+	id                      UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+	merchant_id             UUID NOT NULL REFERENCES merchants(id) ON DELETE CASCADE,
+	platform                TEXT NOT NULL, -- meta|google|tiktok|other
+	external_account_id     TEXT NOT NULL,
+	name                    TEXT,
+	currency                TEXT,
+	created_at              TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+	updated_at              TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+CREATE UNIQUE INDEX ux_ad_accounts_external ON ad_accounts(merchant_id, platform, external_account_id);
+
+-- ad_campaigns: campaigns within an ad account
+CREATE TABLE ad_campaigns (
+	id                      UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+	merchant_id             UUID NOT NULL REFERENCES merchants(id) ON DELETE CASCADE,
+	ad_account_id           UUID NOT NULL REFERENCES ad_accounts(id) ON DELETE CASCADE,
+	external_campaign_id    TEXT NOT NULL,
+	name                    TEXT,
+	status                  TEXT,
+	created_at              TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+	updated_at              TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+CREATE UNIQUE INDEX ux_ad_campaigns_external ON ad_campaigns(ad_account_id, external_campaign_id);
+CREATE INDEX idx_ad_campaigns_merchant ON ad_campaigns(merchant_id);
+
+-- ad_spend_daily: normalized spend per campaign per day (platform reporting date)
+CREATE TABLE ad_spend_daily (
+	id                      BIGSERIAL PRIMARY KEY,
+	merchant_id             UUID NOT NULL REFERENCES merchants(id) ON DELETE CASCADE,
+	ad_campaign_id          UUID NOT NULL REFERENCES ad_campaigns(id) ON DELETE CASCADE,
+	spend_date              DATE NOT NULL,
+	spend                   NUMERIC(14,4) NOT NULL,
+	currency                TEXT NOT NULL,
+	impressions             BIGINT,
+	clicks                  BIGINT,
+	conversions             NUMERIC(14,4),
+	conversion_value        NUMERIC(14,4), -- platform-reported, not used for profit
+	created_at              TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+	updated_at              TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+CREATE UNIQUE INDEX ux_ad_spend_daily_key ON ad_spend_daily(ad_campaign_id, spend_date);
+CREATE INDEX idx_ad_spend_daily_merchant_date ON ad_spend_daily(merchant_id, spend_date);
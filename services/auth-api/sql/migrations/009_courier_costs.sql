@@ -0,0 +1,110 @@
+-- 009_courier_costs.sql
+-- Destination country is needed to pick the courier zone for an order
+ALTER TABLE orders ADD COLUMN shipping_country_code TEXT;
+
+-- courier_zones: maps destination countries to a merchant's courier zones
+CREATE TABLE courier_zones (
+	id                      UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+	merchant_id             UUID NOT NULL REFERENCES merchants(id) ON DELETE CASCADE,
+	zone                    TEXT NOT NULL,
+	country_code            TEXT NOT NULL, -- ISO 3166-1 alpha-2
+	created_at              TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+CREATE UNIQUE INDEX ux_courier_zones_country ON courier_zones(merchant_id, country_code);
+
+-- courier_rate_cards: what a carrier charges per zone and weight band
+-- Orders in countries without a zone mapping use the 'default' zone.
+CREATE TABLE courier_rate_cards (
+	id                      UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+	merchant_id             UUID NOT NULL REFERENCES merchants(id) ON DELETE CASCADE,
+	carrier                 TEXT NOT NULL,
+	zone                    TEXT NOT NULL DEFAULT 'default',
+	min_weight_grams        NUMERIC(14,4) NOT NULL DEFAULT 0, -- inclusive
+	max_weight_grams        NUMERIC(14,4),                    -- exclusive, NULL = no upper bound
+	price                   NUMERIC(14,4) NOT NULL,
+	currency                TEXT NOT NULL,
+	created_at              TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+	updated_at              TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+CREATE INDEX idx_courier_rate_cards_lookup ON courier_rate_cards(merchant_id, zone, min_weight_grams);
+
+-- courier_invoices: invoices received from carriers
+CREATE TABLE courier_invoices (
+	id                      UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+	merchant_id             UUID NOT NULL REFERENCES merchants(id) ON DELETE CASCADE,
+	carrier                 TEXT NOT NULL,
+	invoice_number          TEXT NOT NULL,
+	invoice_date            DATE,
+	currency                TEXT NOT NULL,
+	created_at              TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+	updated_at              TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+CREATE UNIQUE INDEX ux_courier_invoices_number ON courier_invoices(merchant_id, carrier, invoice_number);
+
+-- courier_invoice_lines: per-shipment charges; order_id is NULL when the reference matched no order
+CREATE TABLE courier_invoice_lines (
+	id                      BIGSERIAL PRIMARY KEY,
+	invoice_id              UUID NOT NULL REFERENCES courier_invoices(id) ON DELETE CASCADE,
+	merchant_id             UUID NOT NULL REFERENCES merchants(id) ON DELETE CASCADE,
+	order_id                BIGINT REFERENCES orders(id) ON DELETE SET NULL,
+	order_reference         TEXT NOT NULL, -- order name (e.g. #1001) or Shopify order ID
+	tracking_number         TEXT,
+	amount                  NUMERIC(14,4) NOT NULL,
+	created_at              TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+CREATE INDEX idx_courier_invoice_lines_order ON courier_invoice_lines(order_id);
+
+-- order_courier_costs: what shipping each order cost us.
+-- Invoiced amounts win; otherwise the cheapest rate card matching the order's
+-- zone and shipment weight (line item quantity × variant weight) is used.
+CREATE VIEW order_courier_costs AS
+WITH order_weights AS (
+	SELECT
+		o.id AS order_id,
+		o.merchant_id,
+		o.processed_at,
+		COALESCE(z.zone, 'default') AS zone,
+		COALESCE(SUM(
+			li.quantity * COALESCE(v.weight, 0) *
+			CASE LOWER(v.weight_unit)
+				WHEN 'kg' THEN 1000
+				WHEN 'lb' THEN 453.59237
+				WHEN 'oz' THEN 28.349523125
+				ELSE 1
+			END
+		), 0) AS shipment_weight_grams
+	FROM orders o
+	LEFT JOIN courier_zones z
+		ON z.merchant_id = o.merchant_id AND z.country_code = o.shipping_country_code
+	LEFT JOIN order_line_items li ON li.order_id = o.id
+	LEFT JOIN variants v
+		ON v.merchant_id = li.merchant_id AND v.shopify_variant_id = li.shopify_variant_id
+	GROUP BY o.id, o.merchant_id, o.processed_at, z.zone
+),
+invoiced AS (
+	SELECT order_id, SUM(amount) AS amount
+	FROM courier_invoice_lines
+	WHERE order_id IS NOT NULL
+	GROUP BY order_id
+)
+SELECT
+	ow.order_id,
+	ow.merchant_id,
+	ow.processed_at,
+	ow.zone,
+	ow.shipment_weight_grams,
+	rate.price AS estimated_cost,
+	inv.amount AS invoiced_cost,
+	COALESCE(inv.amount, rate.price, 0) AS courier_cost
+FROM order_weights ow
+LEFT JOIN invoiced inv ON inv.order_id = ow.order_id
+LEFT JOIN LATERAL (
+	SELECT rc.price
+	FROM courier_rate_cards rc
+	WHERE rc.merchant_id = ow.merchant_id
+		AND rc.zone = ow.zone
+		AND ow.shipment_weight_grams >= rc.min_weight_grams
+		AND (rc.max_weight_grams IS NULL OR ow.shipment_weight_grams < rc.max_weight_grams)
+	ORDER BY rc.price
+	LIMIT 1
+) rate ON TRUE;
@@ -0,0 +1,21 @@
+-- 010_manual_costs.sql
+-- manual_costs: overheads entered by the merchant (app subscriptions, payment fees, rent, ...)
+--   one_off            - amount incurred once, on starts_on
+--   monthly            - amount per calendar month, spread evenly over its days
+--   per_order          - amount charged for every order processed while active
+--   percent_of_revenue - amount is a percentage (e.g. 2.9) of revenue while active
+CREATE TABLE manual_costs (
+	id                      UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+	merchant_id             UUID NOT NULL REFERENCES merchants(id) ON DELETE CASCADE,
+	name                    TEXT NOT NULL,
+	category                TEXT,
+	cost_type               TEXT NOT NULL CHECK (cost_type IN ('one_off', 'monthly', 'per_order', 'percent_of_revenue')),
+	amount                  NUMERIC(14,4) NOT NULL,
+	currency                TEXT NOT NULL,
+	starts_on               DATE NOT NULL,
+	ends_on                 DATE, -- inclusive, NULL = still active
+	created_at              TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+	updated_at              TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+	CHECK (ends_on IS NULL OR ends_on >= starts_on)
+);
+CREATE INDEX idx_manual_costs_merchant ON manual_costs(merchant_id, cost_type);
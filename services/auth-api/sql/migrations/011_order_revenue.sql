@@ -0,0 +1,25 @@
+-- 011_order_revenue.sql
+-- One settings row per merchant, so settings can be upserted
+CREATE UNIQUE INDEX ux_app_settings_merchant ON app_settings(merchant_id);
+
+-- order_revenue: revenue recognised per order under the merchant's app_settings.
+-- Merchants without settings get the app_settings defaults (subtotal, no taxes, no shipping).
+--   subtotal basis: subtotal_price, plus taxes/shipping when included
+--   total basis:    total_price, minus taxes/shipping unless included
+CREATE VIEW order_revenue AS
+SELECT
+	o.id AS order_id,
+	o.merchant_id,
+	o.processed_at,
+	CASE COALESCE(s.revenue_basis, 'subtotal')
+		WHEN 'total' THEN
+			COALESCE(o.total_price, 0)
+			- CASE WHEN COALESCE(s.include_taxes, FALSE) THEN 0 ELSE COALESCE(o.total_tax, 0) END
+			- CASE WHEN COALESCE(s.include_shipping, FALSE) THEN 0 ELSE COALESCE(o.total_shipping_price_set_amount, 0) END
+		ELSE
+			COALESCE(o.subtotal_price, 0)
+			+ CASE WHEN COALESCE(s.include_taxes, FALSE) THEN COALESCE(o.total_tax, 0) ELSE 0 END
+			+ CASE WHEN COALESCE(s.include_shipping, FALSE) THEN COALESCE(o.total_shipping_price_set_amount, 0) ELSE 0 END
+	END AS revenue
+FROM orders o
+LEFT JOIN app_settings s ON s.merchant_id = o.merchant_id;
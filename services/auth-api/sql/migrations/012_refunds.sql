@@ -0,0 +1,54 @@
+-- 012_refunds.sql
+-- refunds: money returned to customers, recognised when the refund was processed
+CREATE TABLE refunds (
+	id                      BIGSERIAL PRIMARY KEY,
+	merchant_id             UUID NOT NULL REFERENCES merchants(id) ON DELETE CASCADE,
+	order_id                BIGINT NOT NULL REFERENCES orders(id) ON DELETE CASCADE,
+	shopify_refund_id       BIGINT NOT NULL,
+	processed_at            TIMESTAMPTZ NOT NULL,
+	note                    TEXT,
+	currency                TEXT,
+	amount                  NUMERIC(14,4) NOT NULL DEFAULT 0, -- total refunded to the customer
+	shipping_amount         NUMERIC(14,4) NOT NULL DEFAULT 0, -- part of amount refunding shipping
+	tax_amount              NUMERIC(14,4) NOT NULL DEFAULT 0, -- part of amount refunding taxes
+	created_at              TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+	updated_at              TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+CREATE UNIQUE INDEX ux_refunds_shopify ON refunds(merchant_id, shopify_refund_id);
+CREATE INDEX idx_refunds_merchant_processed ON refunds(merchant_id, processed_at);
+CREATE INDEX idx_refunds_order ON refunds(order_id);
+
+-- refund_line_items: which order line items a refund covers
+-- restock_type follows Shopify: no_restock|cancel|return|legacy_restock
+CREATE TABLE refund_line_items (
+	id                          BIGSERIAL PRIMARY KEY,
+	refund_id                   BIGINT NOT NULL REFERENCES refunds(id) ON DELETE CASCADE,
+	merchant_id                 UUID NOT NULL REFERENCES merchants(id) ON DELETE CASCADE,
+	shopify_refund_line_item_id BIGINT NOT NULL,
+	shopify_line_item_id        BIGINT NOT NULL,
+	quantity                    INT NOT NULL,
+	subtotal                    NUMERIC(14,4) NOT NULL DEFAULT 0,
+	total_tax                   NUMERIC(14,4) NOT NULL DEFAULT 0,
+	restock_type                TEXT,
+	restocked                   BOOLEAN NOT NULL DEFAULT FALSE,
+	created_at                  TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+CREATE UNIQUE INDEX ux_refund_line_items_shopify ON refund_line_items(merchant_id, shopify_refund_line_item_id);
+CREATE INDEX idx_refund_line_items_refund ON refund_line_items(refund_id);
+
+-- refund_revenue: revenue reversed by each refund under the merchant's app_settings.
+-- Taxes and shipping are only reversed when they were counted as revenue (see order_revenue).
+CREATE VIEW refund_revenue AS
+SELECT
+	r.id AS refund_id,
+	r.merchant_id,
+	r.order_id,
+	r.processed_at,
+	GREATEST(
+		r.amount
+		- CASE WHEN COALESCE(s.include_taxes, FALSE) THEN 0 ELSE r.tax_amount END
+		- CASE WHEN COALESCE(s.include_shipping, FALSE) THEN 0 ELSE r.shipping_amount END,
+		0
+	) AS revenue
+FROM refunds r
+LEFT JOIN app_settings s ON s.merchant_id = r.merchant_id;
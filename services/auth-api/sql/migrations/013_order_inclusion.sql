@@ -0,0 +1,111 @@
+-- 013_order_inclusion.sql
+-- Shopify flags orders placed through test gateways
+ALTER TABLE orders ADD COLUMN test BOOLEAN NOT NULL DEFAULT FALSE;
+
+-- Order-inclusion policy: which orders count towards financial aggregates
+ALTER TABLE app_settings
+	ADD COLUMN included_financial_statuses TEXT[] NOT NULL
+		DEFAULT ARRAY['authorized', 'paid', 'partially_paid', 'partially_refunded', 'refunded'],
+	ADD COLUMN include_cancelled_orders BOOLEAN NOT NULL DEFAULT FALSE,
+	ADD COLUMN include_test_orders BOOLEAN NOT NULL DEFAULT FALSE;
+
+-- included_orders: orders that pass the merchant's inclusion policy.
+-- Merchants without settings get the app_settings defaults.
+-- Every financial aggregate reads orders through this view.
+CREATE VIEW included_orders AS
+SELECT o.*
+FROM orders o
+LEFT JOIN app_settings s ON s.merchant_id = o.merchant_id
+WHERE o.financial_status = ANY(COALESCE(
+		s.included_financial_statuses,
+		ARRAY['authorized', 'paid', 'partially_paid', 'partially_refunded', 'refunded']
+	))
+	AND (o.cancelled_at IS NULL OR COALESCE(s.include_cancelled_orders, FALSE))
+	AND (NOT o.test OR COALESCE(s.include_test_orders, FALSE));
+
+-- Re-point the aggregate views at included_orders
+CREATE OR REPLACE VIEW order_revenue AS
+SELECT
+	o.id AS order_id,
+	o.merchant_id,
+	o.processed_at,
+	CASE COALESCE(s.revenue_basis, 'subtotal')
+		WHEN 'total' THEN
+			COALESCE(o.total_price, 0)
+			- CASE WHEN COALESCE(s.include_taxes, FALSE) THEN 0 ELSE COALESCE(o.total_tax, 0) END
+			- CASE WHEN COALESCE(s.include_shipping, FALSE) THEN 0 ELSE COALESCE(o.total_shipping_price_set_amount, 0) END
+		ELSE
+			COALESCE(o.subtotal_price, 0)
+			+ CASE WHEN COALESCE(s.include_taxes, FALSE) THEN COALESCE(o.total_tax, 0) ELSE 0 END
+			+ CASE WHEN COALESCE(s.include_shipping, FALSE) THEN COALESCE(o.total_shipping_price_set_amount, 0) ELSE 0 END
+	END AS revenue
+FROM included_orders o
+LEFT JOIN app_settings s ON s.merchant_id = o.merchant_id;
+
+CREATE OR REPLACE VIEW refund_revenue AS
+SELECT
+	r.id AS refund_id,
+	r.merchant_id,
+	r.order_id,
+	r.processed_at,
+	GREATEST(
+		r.amount
+		- CASE WHEN COALESCE(s.include_taxes, FALSE) THEN 0 ELSE r.tax_amount END
+		- CASE WHEN COALESCE(s.include_shipping, FALSE) THEN 0 ELSE r.shipping_amount END,
+		0
+	) AS revenue
+FROM refunds r
+JOIN included_orders o ON o.id = r.order_id
+LEFT JOIN app_settings s ON s.merchant_id = r.merchant_id;
+
+CREATE OR REPLACE VIEW order_courier_costs AS
+WITH order_weights AS (
+	SELECT
+		o.id AS order_id,
+		o.merchant_id,
+		o.processed_at,
+		COALESCE(z.zone, 'default') AS zone,
+		COALESCE(SUM(
+			li.quantity * COALESCE(v.weight, 0) *
+			CASE LOWER(v.weight_unit)
+				WHEN 'kg' THEN 1000
+				WHEN 'lb' THEN 453.59237
+				WHEN 'oz' THEN 28.349523125
+				ELSE 1
+			END
+		), 0) AS shipment_weight_grams
+	FROM included_orders o
+	LEFT JOIN courier_zones z
+		ON z.merchant_id = o.merchant_id AND z.country_code = o.shipping_country_code
+	LEFT JOIN order_line_items li ON li.order_id = o.id
+	LEFT JOIN variants v
+		ON v.merchant_id = li.merchant_id AND v.shopify_variant_id = li.shopify_variant_id
+	GROUP BY o.id, o.merchant_id, o.processed_at, z.zone
+),
+invoiced AS (
+	SELECT order_id, SUM(amount) AS amount
+	FROM courier_invoice_lines
+	WHERE order_id IS NOT NULL
+	GROUP BY order_id
+)
+SELECT
+	ow.order_id,
+	ow.merchant_id,
+	ow.processed_at,
+	ow.zone,
+	ow.shipment_weight_grams,
+	rate.price AS estimated_cost,
+	inv.amount AS invoiced_cost,
+	COALESCE(inv.amount, rate.price, 0) AS courier_cost
+FROM order_weights ow
+LEFT JOIN invoiced inv ON inv.order_id = ow.order_id
+LEFT JOIN LATERAL (
+	SELECT rc.price
+	FROM courier_rate_cards rc
+	WHERE rc.merchant_id = ow.merchant_id
+		AND rc.zone = ow.zone
+		AND ow.shipment_weight_grams >= rc.min_weight_grams
+		AND (rc.max_weight_grams IS NULL OR ow.shipment_weight_grams < rc.max_weight_grams)
+	ORDER BY rc.price
+	LIMIT 1
+) rate ON TRUE;
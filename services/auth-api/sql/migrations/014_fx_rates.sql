@@ -0,0 +1,161 @@
+-- 014_fx_rates.sql
+-- fx_rates: daily exchange rates; 1 unit of base_currency = rate units of quote_currency
+CREATE TABLE fx_rates (
+	id                      BIGSERIAL PRIMARY KEY,
+	base_currency           TEXT NOT NULL,
+	quote_currency          TEXT NOT NULL,
+	rate_date               DATE NOT NULL,
+	rate                    NUMERIC(20,10) NOT NULL CHECK (rate > 0),
+	source                  TEXT NOT NULL DEFAULT 'manual', -- manual|file|api
+	created_at              TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+	updated_at              TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+CREATE UNIQUE INDEX ux_fx_rates_pair_date ON fx_rates(base_currency, quote_currency, rate_date);
+
+-- fx_rate: units of to_currency per unit of from_currency on a day, using the latest
+-- rate on or before that day (the inverse pair is used when only it is stored).
+-- NULL when no rate is known.
+CREATE FUNCTION fx_rate(p_from_currency TEXT, p_to_currency TEXT, p_on_date DATE) RETURNS NUMERIC
+LANGUAGE sql STABLE AS $$
+	SELECT CASE
+		WHEN p_from_currency IS NULL OR p_to_currency IS NULL OR p_from_currency = p_to_currency THEN 1
+		ELSE COALESCE(
+			(SELECT r.rate FROM fx_rates r
+			 WHERE r.base_currency = p_from_currency AND r.quote_currency = p_to_currency
+				AND r.rate_date <= p_on_date
+			 ORDER BY r.rate_date DESC LIMIT 1),
+			(SELECT 1 / r.rate FROM fx_rates r
+			 WHERE r.base_currency = p_to_currency AND r.quote_currency = p_from_currency
+				AND r.rate_date <= p_on_date
+			 ORDER BY r.rate_date DESC LIMIT 1)
+		)
+	END
+$$;
+
+-- merchant_currency: the currency a merchant reports in and how foreign amounts are handled
+CREATE VIEW merchant_currency AS
+SELECT
+	m.id AS merchant_id,
+	COALESCE(s.default_currency, m.shop_currency) AS reporting_currency,
+	COALESCE(s.multi_currency_mode, 'warn') AS multi_currency_mode
+FROM merchants m
+LEFT JOIN app_settings s ON s.merchant_id = m.id;
+
+-- to_reporting_currency: an amount in the merchant's reporting currency.
+-- Only converts when multi_currency_mode = convert and a rate is known;
+-- otherwise the amount is returned unchanged (and /calculate warns about it).
+CREATE FUNCTION to_reporting_currency(
+	p_merchant_id UUID, p_amount NUMERIC, p_currency TEXT, p_on_date DATE
+) RETURNS NUMERIC
+LANGUAGE sql STABLE AS $$
+	SELECT p_amount * COALESCE(
+		(SELECT fx_rate(p_currency, mc.reporting_currency, p_on_date)
+		 FROM merchant_currency mc
+		 WHERE mc.merchant_id = p_merchant_id AND mc.multi_currency_mode = 'convert'),
+		1
+	)
+$$;
+
+-- Convert the aggregate views into the reporting currency at the time of each order/refund
+CREATE OR REPLACE VIEW order_revenue AS
+SELECT
+	o.id AS order_id,
+	o.merchant_id,
+	o.processed_at,
+	to_reporting_currency(
+		o.merchant_id,
+		CASE COALESCE(s.revenue_basis, 'subtotal')
+			WHEN 'total' THEN
+				COALESCE(o.total_price, 0)
+				- CASE WHEN COALESCE(s.include_taxes, FALSE) THEN 0 ELSE COALESCE(o.total_tax, 0) END
+				- CASE WHEN COALESCE(s.include_shipping, FALSE) THEN 0 ELSE COALESCE(o.total_shipping_price_set_amount, 0) END
+			ELSE
+				COALESCE(o.subtotal_price, 0)
+				+ CASE WHEN COALESCE(s.include_taxes, FALSE) THEN COALESCE(o.total_tax, 0) ELSE 0 END
+				+ CASE WHEN COALESCE(s.include_shipping, FALSE) THEN COALESCE(o.total_shipping_price_set_amount, 0) ELSE 0 END
+		END,
+		o.currency,
+		(o.processed_at AT TIME ZONE 'UTC')::date
+	) AS revenue
+FROM included_orders o
+LEFT JOIN app_settings s ON s.merchant_id = o.merchant_id;
+
+CREATE OR REPLACE VIEW refund_revenue AS
+SELECT
+	r.id AS refund_id,
+	r.merchant_id,
+	r.order_id,
+	r.processed_at,
+	to_reporting_currency(
+		r.merchant_id,
+		GREATEST(
+			r.amount
+			- CASE WHEN COALESCE(s.include_taxes, FALSE) THEN 0 ELSE r.tax_amount END
+			- CASE WHEN COALESCE(s.include_shipping, FALSE) THEN 0 ELSE r.shipping_amount END,
+			0
+		),
+		COALESCE(r.currency, o.currency),
+		(r.processed_at AT TIME ZONE 'UTC')::date
+	) AS revenue
+FROM refunds r
+JOIN included_orders o ON o.id = r.order_id
+LEFT JOIN app_settings s ON s.merchant_id = r.merchant_id;
+
+-- estimated_cost and invoiced_cost stay in the rate card / invoice currency;
+-- courier_cost is in the reporting currency.
+CREATE OR REPLACE VIEW order_courier_costs AS
+WITH order_weights AS (
+	SELECT
+		o.id AS order_id,
+		o.merchant_id,
+		o.processed_at,
+		COALESCE(z.zone, 'default') AS zone,
+		COALESCE(SUM(
+			li.quantity * COALESCE(v.weight, 0) *
+			CASE LOWER(v.weight_unit)
+				WHEN 'kg' THEN 1000
+				WHEN 'lb' THEN 453.59237
+				WHEN 'oz' THEN 28.349523125
+				ELSE 1
+			END
+		), 0) AS shipment_weight_grams
+	FROM included_orders o
+	LEFT JOIN courier_zones z
+		ON z.merchant_id = o.merchant_id AND z.country_code = o.shipping_country_code
+	LEFT JOIN order_line_items li ON li.order_id = o.id
+	LEFT JOIN variants v
+		ON v.merchant_id = li.merchant_id AND v.shopify_variant_id = li.shopify_variant_id
+	GROUP BY o.id, o.merchant_id, o.processed_at, z.zone
+),
+invoiced AS (
+	SELECT l.order_id, SUM(l.amount) AS amount, MAX(i.currency) AS currency
+	FROM courier_invoice_lines l
+	JOIN courier_invoices i ON i.id = l.invoice_id
+	WHERE l.order_id IS NOT NULL
+	GROUP BY l.order_id
+)
+SELECT
+	ow.order_id,
+	ow.merchant_id,
+	ow.processed_at,
+	ow.zone,
+	ow.shipment_weight_grams,
+	rate.price AS estimated_cost,
+	inv.amount AS invoiced_cost,
+	COALESCE(
+		to_reporting_currency(ow.merchant_id, inv.amount, inv.currency, (ow.processed_at AT TIME ZONE 'UTC')::date),
+		to_reporting_currency(ow.merchant_id, rate.price, rate.currency, (ow.processed_at AT TIME ZONE 'UTC')::date),
+		0
+	) AS courier_cost
+FROM order_weights ow
+LEFT JOIN invoiced inv ON inv.order_id = ow.order_id
+LEFT JOIN LATERAL (
+	SELECT rc.price, rc.currency
+	FROM courier_rate_cards rc
+	WHERE rc.merchant_id = ow.merchant_id
+		AND rc.zone = ow.zone
+		AND ow.shipment_weight_grams >= rc.min_weight_grams
+		AND (rc.max_weight_grams IS NULL OR ow.shipment_weight_grams < rc.max_weight_grams)
+	ORDER BY rc.price
+	LIMIT 1
+) rate ON TRUE;
@@ -0,0 +1,64 @@
+-- 015_profit_timeseries.sql
+-- merchant_timezone: the IANA zone a merchant's reporting days follow (UTC when unset or unknown)
+CREATE VIEW merchant_timezone AS
+SELECT
+	m.id AS merchant_id,
+	CASE
+		WHEN EXISTS (SELECT 1 FROM pg_timezone_names tz WHERE tz.name = m.timezone) THEN m.timezone
+		ELSE 'UTC'
+	END AS timezone
+FROM merchants m;
+
+-- line_item_costs: COGS per sold line item, at the latest cost on or before the sale,
+-- in the reporting currency. Line items without a known cost are left out.
+CREATE VIEW line_item_costs AS
+SELECT
+	li.id AS order_line_item_id,
+	li.order_id,
+	o.merchant_id,
+	o.processed_at,
+	li.shopify_line_item_id,
+	li.shopify_variant_id,
+	li.quantity,
+	to_reporting_currency(
+		o.merchant_id, unit_cost.cost, unit_cost.currency, (o.processed_at AT TIME ZONE 'UTC')::date
+	) AS unit_cost
+FROM included_orders o
+JOIN order_line_items li ON li.order_id = o.id
+JOIN inventory_items ii
+	ON ii.merchant_id = li.merchant_id AND ii.shopify_variant_id = li.shopify_variant_id
+CROSS JOIN LATERAL (
+	SELECT h.cost, h.currency
+	FROM inventory_cost_history h
+	WHERE h.merchant_id = ii.merchant_id
+		AND h.shopify_inventory_item_id = ii.shopify_inventory_item_id
+		AND h.effective_at <= o.processed_at
+	ORDER BY h.effective_at DESC
+	LIMIT 1
+) unit_cost;
+
+-- order_product_costs: COGS per order, dated when the order was processed
+CREATE VIEW order_product_costs AS
+SELECT
+	lic.order_id,
+	lic.merchant_id,
+	lic.processed_at,
+	SUM(lic.quantity * lic.unit_cost) AS product_cost
+FROM line_item_costs lic
+GROUP BY lic.order_id, lic.merchant_id, lic.processed_at;
+
+-- refund_product_costs: COGS given back by restocked refund lines, valued at the
+-- cost used when the units were sold and dated when the refund was processed
+CREATE VIEW refund_product_costs AS
+SELECT
+	r.id AS refund_id,
+	r.merchant_id,
+	r.order_id,
+	r.processed_at,
+	SUM(rli.quantity * lic.unit_cost) AS product_cost
+FROM refunds r
+JOIN refund_line_items rli ON rli.refund_id = r.id
+JOIN line_item_costs lic
+	ON lic.merchant_id = rli.merchant_id AND lic.shopify_line_item_id = rli.shopify_line_item_id
+WHERE rli.restocked
+GROUP BY r.id, r.merchant_id, r.order_id, r.processed_at;
@@ -0,0 +1,69 @@
+-- 016_ad_attribution.sql
+-- Where an order came from; utm_* are parsed from the landing site query string
+ALTER TABLE orders
+	ADD COLUMN landing_site TEXT,
+	ADD COLUMN referring_site TEXT,
+	ADD COLUMN utm_source TEXT,
+	ADD COLUMN utm_medium TEXT,
+	ADD COLUMN utm_campaign TEXT;
+
+-- How a merchant's ad spend is distributed over orders
+ALTER TABLE app_settings ADD COLUMN attribution_model TEXT NOT NULL DEFAULT 'revenue'; -- even|revenue|utm
+
+-- order_ad_allocations: a campaign's spend on a day distributed over the orders processed
+-- that day (UTC). allocated_cost is in the reporting currency.
+-- Rows for a day are replaced each time attribution runs over it.
+CREATE TABLE order_ad_allocations (
+	id                      BIGSERIAL PRIMARY KEY,
+	merchant_id             UUID NOT NULL REFERENCES merchants(id) ON DELETE CASCADE,
+	order_id                BIGINT NOT NULL REFERENCES orders(id) ON DELETE CASCADE,
+	ad_campaign_id          UUID NOT NULL REFERENCES ad_campaigns(id) ON DELETE CASCADE,
+	spend_date              DATE NOT NULL,
+	model                   TEXT NOT NULL, -- even|revenue|utm
+	share                   NUMERIC(20,10) NOT NULL, -- fraction of the campaign's spend that day
+	allocated_cost          NUMERIC(14,4) NOT NULL,
+	created_at              TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+CREATE UNIQUE INDEX ux_order_ad_allocations_key ON order_ad_allocations(ad_campaign_id, spend_date, order_id);
+CREATE INDEX idx_order_ad_allocations_merchant_date ON order_ad_allocations(merchant_id, spend_date);
+CREATE INDEX idx_order_ad_allocations_order ON order_ad_allocations(order_id);
+
+-- order_ad_costs: ad cost carried by each order. Days that attribution has run over use
+-- the stored allocations; other days fall back to a revenue-weighted share of the
+-- day's spend.
+CREATE VIEW order_ad_costs AS
+WITH orders_by_day AS (
+	SELECT
+		r.order_id,
+		r.merchant_id,
+		r.processed_at,
+		(r.processed_at AT TIME ZONE 'UTC')::date AS processed_on,
+		r.revenue,
+		SUM(r.revenue) OVER (PARTITION BY r.merchant_id, (r.processed_at AT TIME ZONE 'UTC')::date)
+			AS day_revenue
+	FROM order_revenue r
+),
+day_spend AS (
+	SELECT
+		merchant_id,
+		spend_date,
+		SUM(to_reporting_currency(merchant_id, spend, currency, spend_date)) AS spend
+	FROM ad_spend_daily
+	GROUP BY merchant_id, spend_date
+)
+SELECT
+	o.order_id,
+	o.merchant_id,
+	o.processed_at,
+	CASE
+		WHEN EXISTS (
+			SELECT 1 FROM order_ad_allocations a
+			WHERE a.merchant_id = o.merchant_id AND a.spend_date = o.processed_on
+		) THEN COALESCE(
+			(SELECT SUM(a.allocated_cost) FROM order_ad_allocations a WHERE a.order_id = o.order_id),
+			0
+		)
+		ELSE COALESCE(ROUND(ds.spend * o.revenue / NULLIF(o.day_revenue, 0), 4), 0)
+	END AS ad_cost
+FROM orders_by_day o
+LEFT JOIN day_spend ds ON ds.merchant_id = o.merchant_id AND ds.spend_date = o.processed_on;
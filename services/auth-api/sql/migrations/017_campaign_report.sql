@@ -0,0 +1,54 @@
+-- 017_campaign_report.sql
+-- order_ad_shares: the fraction of a campaign's spend on a day carried by each order
+-- processed that day (UTC). Days that attribution has run over use the stored
+-- allocations; other days fall back to a revenue-weighted share.
+CREATE VIEW order_ad_shares AS
+WITH orders_by_day AS (
+	SELECT
+		r.order_id,
+		r.merchant_id,
+		(r.processed_at AT TIME ZONE 'UTC')::date AS processed_on,
+		r.revenue,
+		SUM(r.revenue) OVER (PARTITION BY r.merchant_id, (r.processed_at AT TIME ZONE 'UTC')::date)
+			AS day_revenue
+	FROM order_revenue r
+),
+attributed_days AS (
+	SELECT DISTINCT merchant_id, spend_date
+	FROM order_ad_allocations
+)
+SELECT
+	a.merchant_id,
+	a.ad_campaign_id,
+	a.spend_date,
+	a.order_id,
+	a.share,
+	a.allocated_cost
+FROM order_ad_allocations a
+UNION ALL
+SELECT
+	s.merchant_id,
+	s.ad_campaign_id,
+	s.spend_date,
+	o.order_id,
+	o.revenue / o.day_revenue AS share,
+	ROUND(to_reporting_currency(s.merchant_id, s.spend, s.currency, s.spend_date) * o.revenue / o.day_revenue, 4)
+		AS allocated_cost
+FROM ad_spend_daily s
+JOIN orders_by_day o ON o.merchant_id = s.merchant_id AND o.processed_on = s.spend_date
+WHERE o.day_revenue <> 0
+	AND NOT EXISTS (
+		SELECT 1 FROM attributed_days d
+		WHERE d.merchant_id = s.merchant_id AND d.spend_date = s.spend_date
+	);
+
+-- Re-point order_ad_costs at order_ad_shares
+CREATE OR REPLACE VIEW order_ad_costs AS
+SELECT
+	r.order_id,
+	r.merchant_id,
+	r.processed_at,
+	COALESCE(SUM(s.allocated_cost), 0) AS ad_cost
+FROM order_revenue r
+LEFT JOIN order_ad_shares s ON s.order_id = r.order_id
+GROUP BY r.order_id, r.merchant_id, r.processed_at;
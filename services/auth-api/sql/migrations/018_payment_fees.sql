@@ -0,0 +1,66 @@
+-- 018_payment_fees.sql
+-- order_transactions: payment transactions of an order as reported by Shopify
+-- kind follows Shopify: authorization|capture|sale|void|refund
+CREATE TABLE order_transactions (
+	id                      BIGSERIAL PRIMARY KEY,
+	merchant_id             UUID NOT NULL REFERENCES merchants(id) ON DELETE CASCADE,
+	order_id                BIGINT NOT NULL REFERENCES orders(id) ON DELETE CASCADE,
+	shopify_transaction_id  BIGINT NOT NULL,
+	kind                    TEXT NOT NULL,
+	status                  TEXT,                 -- pending|failure|success|error
+	gateway                 TEXT,                 -- e.g. shopify_payments, paypal, stripe
+	amount                  NUMERIC(14,4) NOT NULL DEFAULT 0,
+	currency                TEXT,
+	processed_at            TIMESTAMPTZ,
+	created_at              TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+	updated_at              TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+CREATE UNIQUE INDEX ux_order_transactions_shopify ON order_transactions(merchant_id, shopify_transaction_id);
+CREATE INDEX idx_order_transactions_order ON order_transactions(order_id);
+
+-- payment_fee_schedules: what a payment gateway charges the merchant per transaction
+CREATE TABLE payment_fee_schedules (
+	id                      UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+	merchant_id             UUID NOT NULL REFERENCES merchants(id) ON DELETE CASCADE,
+	gateway                 TEXT NOT NULL,        -- lowercase, matched against transaction gateways
+	percentage              NUMERIC(7,4) NOT NULL DEFAULT 0,  -- percent of the transaction amount
+	fixed_fee               NUMERIC(14,4) NOT NULL DEFAULT 0, -- per transaction, in currency
+	currency                TEXT NOT NULL,
+	created_at              TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+	updated_at              TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+CREATE UNIQUE INDEX ux_payment_fee_schedules_gateway ON payment_fee_schedules(merchant_id, gateway);
+
+-- order_payment_fees: fees on the successful sale and capture transactions of each
+-- included order, in the reporting currency. Transactions whose gateway has no
+-- schedule cost nothing. Fees are not given back on refunds.
+CREATE VIEW order_payment_fees AS
+WITH transaction_fees AS (
+	SELECT
+		t.order_id,
+		to_reporting_currency(
+			t.merchant_id,
+			t.amount * s.percentage / 100,
+			COALESCE(t.currency, o.currency),
+			(COALESCE(t.processed_at, o.processed_at) AT TIME ZONE 'UTC')::date
+		)
+		+ to_reporting_currency(
+			t.merchant_id,
+			s.fixed_fee,
+			s.currency,
+			(COALESCE(t.processed_at, o.processed_at) AT TIME ZONE 'UTC')::date
+		) AS fee
+	FROM order_transactions t
+	JOIN orders o ON o.id = t.order_id
+	JOIN payment_fee_schedules s
+		ON s.merchant_id = t.merchant_id AND s.gateway = LOWER(t.gateway)
+	WHERE t.kind IN ('sale', 'capture') AND t.status = 'success'
+)
+SELECT
+	o.id AS order_id,
+	o.merchant_id,
+	o.processed_at,
+	ROUND(COALESCE(SUM(f.fee), 0), 4) AS payment_fees
+FROM included_orders o
+LEFT JOIN transaction_fees f ON f.order_id = o.id
+GROUP BY o.id, o.merchant_id, o.processed_at;
@@ -0,0 +1,180 @@
+-- 019_profit_snapshots.sql
+-- merchant_daily_profit: profit components per day between two dates. Days are local to
+-- the merchant's timezone: orders and refunds count on their local processing day, while
+-- ad spend and manual costs, which are already per calendar day, count on that day.
+-- Shared by /profit/timeseries and the cost engine's snapshots so both agree.
+CREATE FUNCTION merchant_daily_profit(p_merchant_id UUID, p_start DATE, p_end DATE)
+RETURNS TABLE (
+	day DATE,
+	shopify_revenue NUMERIC,
+	refunds NUMERIC,
+	shopify_product_cost NUMERIC,
+	returned_product_cost NUMERIC,
+	ad_cost NUMERIC,
+	courier_cost NUMERIC,
+	payment_fees NUMERIC,
+	manual_cost NUMERIC,
+	profit NUMERIC
+)
+LANGUAGE sql STABLE AS $$
+	WITH local_window AS (
+		SELECT
+			timezone,
+			p_start::timestamp AT TIME ZONE timezone AS window_start,
+			(p_end + 1)::timestamp AT TIME ZONE timezone AS window_end
+		FROM merchant_timezone
+		WHERE merchant_id = p_merchant_id
+	),
+	days AS (
+		SELECT d::date AS day
+		FROM generate_series(p_start::timestamp, p_end::timestamp, INTERVAL '1 day') AS d
+	),
+	revenue AS (
+		SELECT (r.processed_at AT TIME ZONE w.timezone)::date AS day, SUM(r.revenue) AS amount
+		FROM order_revenue r, local_window w
+		WHERE r.merchant_id = p_merchant_id
+			AND r.processed_at >= w.window_start AND r.processed_at < w.window_end
+		GROUP BY 1
+	),
+	refunds AS (
+		SELECT (r.processed_at AT TIME ZONE w.timezone)::date AS day, SUM(r.revenue) AS amount
+		FROM refund_revenue r, local_window w
+		WHERE r.merchant_id = p_merchant_id
+			AND r.processed_at >= w.window_start AND r.processed_at < w.window_end
+		GROUP BY 1
+	),
+	product_cost AS (
+		SELECT (c.processed_at AT TIME ZONE w.timezone)::date AS day, SUM(c.product_cost) AS amount
+		FROM order_product_costs c, local_window w
+		WHERE c.merchant_id = p_merchant_id
+			AND c.processed_at >= w.window_start AND c.processed_at < w.window_end
+		GROUP BY 1
+	),
+	returned_product_cost AS (
+		SELECT (c.processed_at AT TIME ZONE w.timezone)::date AS day, SUM(c.product_cost) AS amount
+		FROM refund_product_costs c, local_window w
+		WHERE c.merchant_id = p_merchant_id
+			AND c.processed_at >= w.window_start AND c.processed_at < w.window_end
+		GROUP BY 1
+	),
+	ad_cost AS (
+		SELECT spend_date AS day, SUM(to_reporting_currency(merchant_id, spend, currency, spend_date)) AS amount
+		FROM ad_spend_daily
+		WHERE merchant_id = p_merchant_id AND spend_date >= p_start AND spend_date <= p_end
+		GROUP BY 1
+	),
+	courier_cost AS (
+		SELECT (c.processed_at AT TIME ZONE w.timezone)::date AS day, SUM(c.courier_cost) AS amount
+		FROM order_courier_costs c, local_window w
+		WHERE c.merchant_id = p_merchant_id
+			AND c.processed_at >= w.window_start AND c.processed_at < w.window_end
+		GROUP BY 1
+	),
+	payment_fees AS (
+		SELECT (f.processed_at AT TIME ZONE w.timezone)::date AS day, SUM(f.payment_fees) AS amount
+		FROM order_payment_fees f, local_window w
+		WHERE f.merchant_id = p_merchant_id
+			AND f.processed_at >= w.window_start AND f.processed_at < w.window_end
+		GROUP BY 1
+	),
+	-- Same rules as get_total_manual_cost, with days local to the merchant
+	manual_cost AS (
+		SELECT day, SUM(amount) AS amount
+		FROM (
+			SELECT
+				mc.starts_on AS day,
+				to_reporting_currency(mc.merchant_id, mc.amount, mc.currency, mc.starts_on) AS amount
+			FROM manual_costs mc
+			WHERE mc.merchant_id = p_merchant_id
+				AND mc.cost_type = 'one_off'
+				AND mc.starts_on >= p_start AND mc.starts_on <= p_end
+			UNION ALL
+			SELECT
+				d::date,
+				to_reporting_currency(mc.merchant_id, mc.amount, mc.currency, d::date)
+					/ EXTRACT(DAY FROM date_trunc('month', d) + INTERVAL '1 month' - INTERVAL '1 day')
+			FROM manual_costs mc
+			CROSS JOIN LATERAL generate_series(
+				GREATEST(mc.starts_on, p_start)::timestamp,
+				LEAST(COALESCE(mc.ends_on, p_end), p_end)::timestamp,
+				INTERVAL '1 day'
+			) AS d
+			WHERE mc.merchant_id = p_merchant_id AND mc.cost_type = 'monthly'
+			UNION ALL
+			SELECT
+				(o.processed_at AT TIME ZONE w.timezone)::date,
+				CASE mc.cost_type
+					WHEN 'per_order' THEN to_reporting_currency(
+						mc.merchant_id, mc.amount, mc.currency, (o.processed_at AT TIME ZONE 'UTC')::date
+					)
+					ELSE mc.amount / 100 * o.revenue
+				END
+			FROM manual_costs mc
+			CROSS JOIN local_window w
+			JOIN order_revenue o
+				ON o.merchant_id = mc.merchant_id
+				AND (o.processed_at AT TIME ZONE w.timezone)::date >= mc.starts_on
+				AND (mc.ends_on IS NULL OR (o.processed_at AT TIME ZONE w.timezone)::date <= mc.ends_on)
+			WHERE mc.merchant_id = p_merchant_id
+				AND mc.cost_type IN ('per_order', 'percent_of_revenue')
+				AND o.processed_at >= w.window_start AND o.processed_at < w.window_end
+		) m
+		GROUP BY day
+	),
+	totals AS (
+		SELECT
+			d.day,
+			COALESCE(rev.amount, 0) AS shopify_revenue,
+			COALESCE(ref.amount, 0) AS refunds,
+			COALESCE(pc.amount, 0) AS shopify_product_cost,
+			COALESCE(rpc.amount, 0) AS returned_product_cost,
+			COALESCE(ad.amount, 0) AS ad_cost,
+			COALESCE(cc.amount, 0) AS courier_cost,
+			COALESCE(pf.amount, 0) AS payment_fees,
+			COALESCE(man.amount, 0) AS manual_cost
+		FROM days d
+		LEFT JOIN revenue rev ON rev.day = d.day
+		LEFT JOIN refunds ref ON ref.day = d.day
+		LEFT JOIN product_cost pc ON pc.day = d.day
+		LEFT JOIN returned_product_cost rpc ON rpc.day = d.day
+		LEFT JOIN ad_cost ad ON ad.day = d.day
+		LEFT JOIN courier_cost cc ON cc.day = d.day
+		LEFT JOIN payment_fees pf ON pf.day = d.day
+		LEFT JOIN manual_cost man ON man.day = d.day
+	)
+	SELECT
+		day,
+		shopify_revenue,
+		refunds,
+		shopify_product_cost,
+		returned_product_cost,
+		ad_cost,
+		courier_cost,
+		payment_fees,
+		manual_cost,
+		shopify_revenue - refunds - shopify_product_cost + returned_product_cost
+			- ad_cost - courier_cost - payment_fees - manual_cost AS profit
+	FROM totals
+	ORDER BY day
+$$;
+
+-- profit_snapshots: precomputed merchant_daily_profit rows written by the cost engine,
+-- so dashboards do not aggregate on every request. snapshot_date is local to the
+-- merchant's timezone and amounts are in its reporting currency.
+CREATE TABLE profit_snapshots (
+	id                      BIGSERIAL PRIMARY KEY,
+	merchant_id             UUID NOT NULL REFERENCES merchants(id) ON DELETE CASCADE,
+	snapshot_date           DATE NOT NULL,
+	reporting_currency      TEXT,
+	shopify_revenue         NUMERIC(14,4) NOT NULL DEFAULT 0,
+	refunds                 NUMERIC(14,4) NOT NULL DEFAULT 0,
+	shopify_product_cost    NUMERIC(14,4) NOT NULL DEFAULT 0,
+	returned_product_cost   NUMERIC(14,4) NOT NULL DEFAULT 0,
+	ad_cost                 NUMERIC(14,4) NOT NULL DEFAULT 0,
+	courier_cost            NUMERIC(14,4) NOT NULL DEFAULT 0,
+	payment_fees            NUMERIC(14,4) NOT NULL DEFAULT 0,
+	manual_cost             NUMERIC(14,4) NOT NULL DEFAULT 0,
+	profit                  NUMERIC(14,4) NOT NULL DEFAULT 0,
+	computed_at             TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+CREATE UNIQUE INDEX ux_profit_snapshots_day ON profit_snapshots(merchant_id, snapshot_date);
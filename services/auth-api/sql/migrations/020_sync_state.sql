@@ -0,0 +1,17 @@
+-- 020_sync_state.sql
+-- sync_state: where the shopify-consumer's incremental sync of a resource stands.
+-- last_updated_at: records updated at or after this time still need syncing; NULL until the
+-- first sync completes, which then looks back app_settings.sync_lookback_days.
+-- cursor/run_started_at: page_info to resume an interrupted sync from, and when that sync
+-- started (the next checkpoint once it completes).
+CREATE TABLE sync_state (
+	id                      BIGSERIAL PRIMARY KEY,
+	merchant_id             UUID NOT NULL REFERENCES merchants(id) ON DELETE CASCADE,
+	resource                TEXT NOT NULL,        -- products|orders
+	last_updated_at         TIMESTAMPTZ,
+	cursor                  TEXT,
+	run_started_at          TIMESTAMPTZ,
+	created_at              TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+	updated_at              TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+CREATE UNIQUE INDEX ux_sync_state_resource ON sync_state(merchant_id, resource);
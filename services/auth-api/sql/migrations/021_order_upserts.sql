@@ -0,0 +1,52 @@
+-- 021_order_upserts.sql
+-- Orders are upserted by their Shopify ID. Racing syncs could insert an order twice before
+-- this index existed; the most recently updated row is kept. Rows of the other copies are
+-- moved over to it first, so deleting the copies does not cascade to them.
+CREATE TEMPORARY TABLE order_duplicates AS
+SELECT o.id AS duplicate_id, kept.id AS kept_id
+FROM orders o
+JOIN LATERAL (
+	SELECT k.id
+	FROM orders k
+	WHERE k.merchant_id = o.merchant_id
+		AND k.shopify_order_id = o.shopify_order_id
+	ORDER BY k.updated_at DESC, k.id DESC
+	LIMIT 1
+) kept ON kept.id <> o.id;
+
+UPDATE order_line_items t SET order_id = d.kept_id
+FROM order_duplicates d WHERE t.order_id = d.duplicate_id;
+
+UPDATE refunds t SET order_id = d.kept_id
+FROM order_duplicates d WHERE t.order_id = d.duplicate_id;
+
+UPDATE order_transactions t SET order_id = d.kept_id
+FROM order_duplicates d WHERE t.order_id = d.duplicate_id;
+
+UPDATE courier_invoice_lines t SET order_id = d.kept_id
+FROM order_duplicates d WHERE t.order_id = d.duplicate_id;
+
+-- An allocation is kept unless the kept order already has one for that campaign and day;
+-- such extra copies double-counted the order and go with the duplicate
+UPDATE order_ad_allocations t SET order_id = moved.kept_id
+FROM (
+	SELECT DISTINCT ON (a.ad_campaign_id, a.spend_date, d.kept_id) a.id, d.kept_id
+	FROM order_ad_allocations a
+	JOIN order_duplicates d ON d.duplicate_id = a.order_id
+	WHERE NOT EXISTS (
+		SELECT 1 FROM order_ad_allocations k
+		WHERE k.ad_campaign_id = a.ad_campaign_id
+			AND k.spend_date = a.spend_date
+			AND k.order_id = d.kept_id
+	)
+	ORDER BY a.ad_campaign_id, a.spend_date, d.kept_id, a.id
+) moved
+WHERE t.id = moved.id;
+
+DELETE FROM orders o
+USING order_duplicates d
+WHERE o.id = d.duplicate_id;
+
+DROP TABLE order_duplicates;
+
+CREATE UNIQUE INDEX ux_orders_shopify ON orders(merchant_id, shopify_order_id);
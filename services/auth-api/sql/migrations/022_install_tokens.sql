@@ -0,0 +1,17 @@
+-- 022_install_tokens.sql
+-- The Admin API access token of an install, encrypted by the auth API
+-- (base64 of a 12-byte AES-256-GCM nonce followed by the ciphertext).
+ALTER TABLE shopify_installs ADD COLUMN access_token_encrypted TEXT;
+
+-- One active install per merchant: keep the newest, retire the others
+UPDATE shopify_installs s
+SET status = 'uninstalled', uninstalled_at = COALESCE(s.uninstalled_at, NOW()), updated_at = NOW()
+WHERE s.status = 'active'
+	AND EXISTS (
+		SELECT 1 FROM shopify_installs newer
+		WHERE newer.merchant_id = s.merchant_id
+			AND newer.status = 'active'
+			AND (newer.installed_at, newer.id) > (s.installed_at, s.id)
+	);
+
+CREATE UNIQUE INDEX ux_shopify_installs_active ON shopify_installs(merchant_id) WHERE status = 'active';
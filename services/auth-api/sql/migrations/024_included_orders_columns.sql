@@ -0,0 +1,36 @@
+-- 024_included_orders_columns.sql
+-- included_orders was created with o.*, which Postgres expands once: order columns
+-- added later (landing_site, utm_*) were missing from it. Columns are listed
+-- explicitly now; columns added to orders must be appended here as well.
+CREATE OR REPLACE VIEW included_orders AS
+SELECT
+	o.id,
+	o.merchant_id,
+	o.shopify_order_id,
+	o.name,
+	o.processed_at,
+	o.currency,
+	o.subtotal_price,
+	o.total_price,
+	o.total_discounts,
+	o.total_shipping_price_set_amount,
+	o.total_tax,
+	o.financial_status,
+	o.cancelled_at,
+	o.created_at,
+	o.updated_at,
+	o.shipping_country_code,
+	o.test,
+	o.landing_site,
+	o.referring_site,
+	o.utm_source,
+	o.utm_medium,
+	o.utm_campaign
+FROM orders o
+LEFT JOIN app_settings s ON s.merchant_id = o.merchant_id
+WHERE o.financial_status = ANY(COALESCE(
+		s.included_financial_statuses,
+		ARRAY['authorized', 'paid', 'partially_paid', 'partially_refunded', 'refunded']
+	))
+	AND (o.cancelled_at IS NULL OR COALESCE(s.include_cancelled_orders, FALSE))
+	AND (NOT o.test OR COALESCE(s.include_test_orders, FALSE));
@@ -0,0 +1,97 @@
+-- 025_cost_currencies.sql
+-- The currency each COGS and courier amount was taken in, so /calculate can warn
+-- about exactly the amounts of a window that were not converted.
+CREATE OR REPLACE VIEW line_item_costs AS
+SELECT
+	li.id AS order_line_item_id,
+	li.order_id,
+	o.merchant_id,
+	o.processed_at,
+	li.shopify_line_item_id,
+	li.shopify_variant_id,
+	li.quantity,
+	to_reporting_currency(
+		o.merchant_id, unit_cost.cost, unit_cost.currency, (o.processed_at AT TIME ZONE 'UTC')::date
+	) AS unit_cost,
+	unit_cost.currency AS cost_currency
+FROM included_orders o
+JOIN order_line_items li ON li.order_id = o.id
+JOIN inventory_items ii
+	ON ii.merchant_id = li.merchant_id AND ii.shopify_variant_id = li.shopify_variant_id
+CROSS JOIN LATERAL (
+	SELECT h.cost, h.currency
+	FROM inventory_cost_history h
+	WHERE h.merchant_id = ii.merchant_id
+		AND h.shopify_inventory_item_id = ii.shopify_inventory_item_id
+		AND h.effective_at <= o.processed_at
+	ORDER BY h.effective_at DESC
+	LIMIT 1
+) unit_cost;
+
+CREATE OR REPLACE VIEW order_courier_costs AS
+WITH order_weights AS (
+	SELECT
+		o.id AS order_id,
+		o.merchant_id,
+		o.processed_at,
+		COALESCE(z.zone, 'default') AS zone,
+		COALESCE(SUM(
+			li.quantity * COALESCE(v.weight, 0) *
+			CASE LOWER(v.weight_unit)
+				WHEN 'kg' THEN 1000
+				WHEN 'lb' THEN 453.59237
+				WHEN 'oz' THEN 28.349523125
+				ELSE 1
+			END
+		), 0) AS shipment_weight_grams
+	FROM included_orders o
+	LEFT JOIN courier_zones z
+		ON z.merchant_id = o.merchant_id AND z.country_code = o.shipping_country_code
+	LEFT JOIN order_line_items li ON li.order_id = o.id
+	LEFT JOIN variants v
+		ON v.merchant_id = li.merchant_id AND v.shopify_variant_id = li.shopify_variant_id
+	GROUP BY o.id, o.merchant_id, o.processed_at, z.zone
+),
+invoiced AS (
+	SELECT COALESCE(l.order_id, ref.id) AS order_id, SUM(l.amount) AS amount, MAX(i.currency) AS currency
+	FROM courier_invoice_lines l
+	JOIN courier_invoices i ON i.id = l.invoice_id
+	LEFT JOIN LATERAL (
+		SELECT o.id
+		FROM orders o
+		WHERE l.order_id IS NULL
+			AND o.merchant_id = l.merchant_id
+			AND (o.name = l.order_reference OR o.shopify_order_id::text = l.order_reference)
+		LIMIT 1
+	) ref ON TRUE
+	WHERE COALESCE(l.order_id, ref.id) IS NOT NULL
+	GROUP BY COALESCE(l.order_id, ref.id)
+)
+SELECT
+	ow.order_id,
+	ow.merchant_id,
+	ow.processed_at,
+	ow.zone,
+	ow.shipment_weight_grams,
+	rate.price AS estimated_cost,
+	inv.amount AS invoiced_cost,
+	COALESCE(
+		to_reporting_currency(ow.merchant_id, inv.amount, inv.currency, (ow.processed_at AT TIME ZONE 'UTC')::date),
+		to_reporting_currency(ow.merchant_id, rate.price, rate.currency, (ow.processed_at AT TIME ZONE 'UTC')::date),
+		0
+	) AS courier_cost,
+	CASE WHEN inv.amount IS NOT NULL THEN inv.currency ELSE rate.currency END AS cost_currency
+FROM order_weights ow
+LEFT JOIN app_settings s ON s.merchant_id = ow.merchant_id
+LEFT JOIN invoiced inv ON inv.order_id = ow.order_id
+LEFT JOIN LATERAL (
+	SELECT rc.price, rc.currency
+	FROM courier_rate_cards rc
+	WHERE rc.merchant_id = ow.merchant_id
+		AND (s.courier_carrier IS NULL OR rc.carrier = s.courier_carrier)
+		AND rc.zone = ow.zone
+		AND ow.shipment_weight_grams >= rc.min_weight_grams
+		AND (rc.max_weight_grams IS NULL OR ow.shipment_weight_grams < rc.max_weight_grams)
+	ORDER BY rc.price
+	LIMIT 1
+) rate ON TRUE;
@@ -0,0 +1,44 @@
+-- 026_ad_shares_timezone.sql
+-- Spend days are matched to the orders processed that day in the merchant's
+-- timezone, like the profit timeseries and snapshots. order_ad_allocations
+-- rows are written the same way by attribution runs.
+CREATE OR REPLACE VIEW order_ad_shares AS
+WITH orders_by_day AS (
+	SELECT
+		r.order_id,
+		r.merchant_id,
+		(r.processed_at AT TIME ZONE tz.timezone)::date AS processed_on,
+		r.revenue,
+		SUM(r.revenue) OVER (PARTITION BY r.merchant_id, (r.processed_at AT TIME ZONE tz.timezone)::date)
+			AS day_revenue
+	FROM order_revenue r
+	JOIN merchant_timezone tz ON tz.merchant_id = r.merchant_id
+),
+attributed_days AS (
+	SELECT DISTINCT merchant_id, spend_date
+	FROM order_ad_allocations
+)
+SELECT
+	a.merchant_id,
+	a.ad_campaign_id,
+	a.spend_date,
+	a.order_id,
+	a.share,
+	a.allocated_cost
+FROM order_ad_allocations a
+UNION ALL
+SELECT
+	s.merchant_id,
+	s.ad_campaign_id,
+	s.spend_date,
+	o.order_id,
+	o.revenue / o.day_revenue AS share,
+	ROUND(to_reporting_currency(s.merchant_id, s.spend, s.currency, s.spend_date) * o.revenue / o.day_revenue, 4)
+		AS allocated_cost
+FROM ad_spend_daily s
+JOIN orders_by_day o ON o.merchant_id = s.merchant_id AND o.processed_on = s.spend_date
+WHERE o.day_revenue <> 0
+	AND NOT EXISTS (
+		SELECT 1 FROM attributed_days d
+		WHERE d.merchant_id = s.merchant_id AND d.spend_date = s.spend_date
+	);
@@ -0,0 +1,201 @@
+-- 027_included_order_ids.sql
+-- included_orders had to list every order column by hand (024) to keep up with orders.
+-- It now only holds the ids of the orders that pass the merchant's inclusion policy, and
+-- the views join orders by id for the columns they read. The view is built under a new
+-- name, the views that read it are re-pointed, and it then takes over the old name.
+--
+-- Orders without a financial_status are included: Shopify always reports one, so these
+-- come from other sources (e.g. created through the API) and silently dropping them would
+-- understate revenue. Cancelled and test orders are still excluded unless the merchant
+-- includes them.
+CREATE VIEW included_order_ids AS
+SELECT o.id
+FROM orders o
+LEFT JOIN app_settings s ON s.merchant_id = o.merchant_id
+WHERE (
+		o.financial_status IS NULL
+		OR o.financial_status = ANY(COALESCE(
+			s.included_financial_statuses,
+			ARRAY['authorized', 'paid', 'partially_paid', 'partially_refunded', 'refunded']
+		))
+	)
+	AND (o.cancelled_at IS NULL OR COALESCE(s.include_cancelled_orders, FALSE))
+	AND (NOT o.test OR COALESCE(s.include_test_orders, FALSE));
+
+CREATE OR REPLACE VIEW order_revenue AS
+SELECT
+	o.id AS order_id,
+	o.merchant_id,
+	o.processed_at,
+	to_reporting_currency(
+		o.merchant_id,
+		CASE COALESCE(s.revenue_basis, 'subtotal')
+			WHEN 'total' THEN
+				COALESCE(o.total_price, 0)
+				- CASE WHEN COALESCE(s.include_taxes, FALSE) THEN 0 ELSE COALESCE(o.total_tax, 0) END
+				- CASE WHEN COALESCE(s.include_shipping, FALSE) THEN 0 ELSE COALESCE(o.total_shipping_price_set_amount, 0) END
+			ELSE
+				COALESCE(o.subtotal_price, 0)
+				+ CASE WHEN COALESCE(s.include_taxes, FALSE) THEN COALESCE(o.total_tax, 0) ELSE 0 END
+				+ CASE WHEN COALESCE(s.include_shipping, FALSE) THEN COALESCE(o.total_shipping_price_set_amount, 0) ELSE 0 END
+		END,
+		o.currency,
+		(o.processed_at AT TIME ZONE 'UTC')::date
+	) AS revenue
+FROM included_order_ids io
+JOIN orders o ON o.id = io.id
+LEFT JOIN app_settings s ON s.merchant_id = o.merchant_id;
+
+CREATE OR REPLACE VIEW refund_revenue AS
+SELECT
+	r.id AS refund_id,
+	r.merchant_id,
+	r.order_id,
+	r.processed_at,
+	to_reporting_currency(
+		r.merchant_id,
+		GREATEST(
+			r.amount
+			- CASE WHEN COALESCE(s.include_taxes, FALSE) THEN 0 ELSE r.tax_amount END
+			- CASE WHEN COALESCE(s.include_shipping, FALSE) THEN 0 ELSE r.shipping_amount END,
+			0
+		),
+		COALESCE(r.currency, o.currency),
+		(r.processed_at AT TIME ZONE 'UTC')::date
+	) AS revenue
+FROM refunds r
+JOIN orders o ON o.id = r.order_id
+JOIN included_order_ids io ON io.id = o.id
+LEFT JOIN app_settings s ON s.merchant_id = r.merchant_id;
+
+CREATE OR REPLACE VIEW line_item_costs AS
+SELECT
+	li.id AS order_line_item_id,
+	li.order_id,
+	o.merchant_id,
+	o.processed_at,
+	li.shopify_line_item_id,
+	li.shopify_variant_id,
+	li.quantity,
+	to_reporting_currency(
+		o.merchant_id, unit_cost.cost, unit_cost.currency, (o.processed_at AT TIME ZONE 'UTC')::date
+	) AS unit_cost,
+	unit_cost.currency AS cost_currency
+FROM included_order_ids io
+JOIN orders o ON o.id = io.id
+JOIN order_line_items li ON li.order_id = o.id
+JOIN inventory_items ii
+	ON ii.merchant_id = li.merchant_id AND ii.shopify_variant_id = li.shopify_variant_id
+CROSS JOIN LATERAL (
+	SELECT h.cost, h.currency
+	FROM inventory_cost_history h
+	WHERE h.merchant_id = ii.merchant_id
+		AND h.shopify_inventory_item_id = ii.shopify_inventory_item_id
+		AND h.effective_at <= o.processed_at
+	ORDER BY h.effective_at DESC
+	LIMIT 1
+) unit_cost;
+
+CREATE OR REPLACE VIEW order_courier_costs AS
+WITH order_weights AS (
+	SELECT
+		o.id AS order_id,
+		o.merchant_id,
+		o.processed_at,
+		COALESCE(z.zone, 'default') AS zone,
+		COALESCE(SUM(
+			li.quantity * COALESCE(v.weight, 0) *
+			CASE LOWER(v.weight_unit)
+				WHEN 'kg' THEN 1000
+				WHEN 'lb' THEN 453.59237
+				WHEN 'oz' THEN 28.349523125
+				ELSE 1
+			END
+		), 0) AS shipment_weight_grams
+	FROM included_order_ids io
+	JOIN orders o ON o.id = io.id
+	LEFT JOIN courier_zones z
+		ON z.merchant_id = o.merchant_id AND z.country_code = o.shipping_country_code
+	LEFT JOIN order_line_items li ON li.order_id = o.id
+	LEFT JOIN variants v
+		ON v.merchant_id = li.merchant_id AND v.shopify_variant_id = li.shopify_variant_id
+	GROUP BY o.id, o.merchant_id, o.processed_at, z.zone
+),
+invoiced AS (
+	SELECT COALESCE(l.order_id, ref.id) AS order_id, SUM(l.amount) AS amount, MAX(i.currency) AS currency
+	FROM courier_invoice_lines l
+	JOIN courier_invoices i ON i.id = l.invoice_id
+	LEFT JOIN LATERAL (
+		SELECT o.id
+		FROM orders o
+		WHERE l.order_id IS NULL
+			AND o.merchant_id = l.merchant_id
+			AND (o.name = l.order_reference OR o.shopify_order_id::text = l.order_reference)
+		LIMIT 1
+	) ref ON TRUE
+	WHERE COALESCE(l.order_id, ref.id) IS NOT NULL
+	GROUP BY COALESCE(l.order_id, ref.id)
+)
+SELECT
+	ow.order_id,
+	ow.merchant_id,
+	ow.processed_at,
+	ow.zone,
+	ow.shipment_weight_grams,
+	rate.price AS estimated_cost,
+	inv.amount AS invoiced_cost,
+	COALESCE(
+		to_reporting_currency(ow.merchant_id, inv.amount, inv.currency, (ow.processed_at AT TIME ZONE 'UTC')::date),
+		to_reporting_currency(ow.merchant_id, rate.price, rate.currency, (ow.processed_at AT TIME ZONE 'UTC')::date),
+		0
+	) AS courier_cost,
+	CASE WHEN inv.amount IS NOT NULL THEN inv.currency ELSE rate.currency END AS cost_currency
+FROM order_weights ow
+LEFT JOIN app_settings s ON s.merchant_id = ow.merchant_id
+LEFT JOIN invoiced inv ON inv.order_id = ow.order_id
+LEFT JOIN LATERAL (
+	SELECT rc.price, rc.currency
+	FROM courier_rate_cards rc
+	WHERE rc.merchant_id = ow.merchant_id
+		AND (s.courier_carrier IS NULL OR rc.carrier = s.courier_carrier)
+		AND rc.zone = ow.zone
+		AND ow.shipment_weight_grams >= rc.min_weight_grams
+		AND (rc.max_weight_grams IS NULL OR ow.shipment_weight_grams < rc.max_weight_grams)
+	ORDER BY rc.price
+	LIMIT 1
+) rate ON TRUE;
+
+CREATE OR REPLACE VIEW order_payment_fees AS
+WITH transaction_fees AS (
+	SELECT
+		t.order_id,
+		to_reporting_currency(
+			t.merchant_id,
+			t.amount * s.percentage / 100,
+			COALESCE(t.currency, o.currency),
+			(COALESCE(t.processed_at, o.processed_at) AT TIME ZONE 'UTC')::date
+		)
+		+ to_reporting_currency(
+			t.merchant_id,
+			s.fixed_fee,
+			s.currency,
+			(COALESCE(t.processed_at, o.processed_at) AT TIME ZONE 'UTC')::date
+		) AS fee
+	FROM order_transactions t
+	JOIN orders o ON o.id = t.order_id
+	JOIN payment_fee_schedules s
+		ON s.merchant_id = t.merchant_id AND s.gateway = LOWER(t.gateway)
+	WHERE t.kind IN ('sale', 'capture') AND t.status = 'success'
+)
+SELECT
+	o.id AS order_id,
+	o.merchant_id,
+	o.processed_at,
+	ROUND(COALESCE(SUM(f.fee), 0), 4) AS payment_fees
+FROM included_order_ids io
+JOIN orders o ON o.id = io.id
+LEFT JOIN transaction_fees f ON f.order_id = o.id
+GROUP BY o.id, o.merchant_id, o.processed_at;
+
+DROP VIEW included_orders;
+ALTER VIEW included_order_ids RENAME TO included_orders;
@@ -44,6 +44,26 @@ pub struct CliArgs {
     /// Server base URL (for email links and SMTP configuration)
     #[arg(long, env = "DARKEX_URL")]
     pub darkex_url: Option<String>,
+
+    /// Directory of exported ad spend reports (<platform>/<account_id>.csv|json)
+    #[arg(long, env = "AD_SPEND_DIR")]
+    pub ad_spend_dir: Option<String>,
+
+    /// File of daily FX rates (CSV or JSON: date, base_currency, quote_currency, rate)
+    #[arg(long, env = "FX_RATES_FILE")]
+    pub fx_rates_file: Option<String>,
+
+    /// Base URL of a Frankfurter-compatible FX rates API
+    #[arg(long, env = "FX_RATES_API_URL")]
+    pub fx_rates_api_url: Option<String>,
+
+    /// Base64 of the 32-byte key that Shopify access tokens are encrypted with
+    #[arg(long, env = "SHOPIFY_TOKEN_KEY")]
+    pub shopify_token_key: Option<String>,
+
+    /// Kafka brokers (comma-separated); enables publishing ad spend import events
+    #[arg(long, env = "KAFKA_BROKERS")]
+    pub kafka_brokers: Option<String>,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -59,6 +79,11 @@ pub struct Args {
     pub enable_email: bool,
     pub jwt_expiration_hours: u64,
     pub darkex_url: String,
+    pub ad_spend_dir: Option<String>,
+    pub fx_rates_file: Option<String>,
+    pub fx_rates_api_url: Option<String>,
+    pub shopify_token_key: Option<String>,
+    pub kafka_brokers: Option<String>,
 }
 
 impl Default for Args {
@@ -76,6 +101,11 @@ impl Default for Args {
             enable_email: true,
             jwt_expiration_hours: 24,
             darkex_url: "http://localhost:8080".to_string(),
+            ad_spend_dir: None,
+            fx_rates_file: None,
+            fx_rates_api_url: None,
+            shopify_token_key: None,
+            kafka_brokers: None,
         }
     }
 }
@@ -98,6 +128,11 @@ impl From<CliArgs> for Args {
                 .jwt_expiration_hours
                 .unwrap_or(default.jwt_expiration_hours),
             darkex_url: cli_args.darkex_url.unwrap_or(default.darkex_url),
+            ad_spend_dir: cli_args.ad_spend_dir.or(default.ad_spend_dir),
+            fx_rates_file: cli_args.fx_rates_file.or(default.fx_rates_file),
+            fx_rates_api_url: cli_args.fx_rates_api_url.or(default.fx_rates_api_url),
+            shopify_token_key: cli_args.shopify_token_key.or(default.shopify_token_key),
+            kafka_brokers: cli_args.kafka_brokers.or(default.kafka_brokers),
         }
     }
 }
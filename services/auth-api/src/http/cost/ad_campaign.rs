@@ -0,0 +1,680 @@
+use crate::http::{types::*, ApiContext, AppError, AppResult};
+use axum::{
+    extract::{Path, Query},
+    http::StatusCode,
+    routing::{delete, get, post},
+    Extension, Json, Router,
+};
+use chrono::{DateTime, NaiveDate, Utc};
+use lib_shopify::kafka::AdSpendImported;
+use rust_decimal::Decimal;
+use std::collections::HashSet;
+use uuid::Uuid;
+
+/// Ad platforms we accept spend for
+const AD_PLATFORMS: &[&str] = &["meta", "google", "tiktok", "other"];
+
+fn validate_platform(platform: &str) -> Result<(), AppError> {
+    if AD_PLATFORMS.contains(&platform) {
+        Ok(())
+    } else {
+        Err(AppError::Validation(format!(
+            "Platform must be one of: {}",
+            AD_PLATFORMS.join(", ")
+        )))
+    }
+}
+
+/// Sum ad spend for a merchant over a window (can be used by other modules)
+///
+/// Spend is reported per calendar day, so a day counts when its date (in UTC)
+/// falls within the window. Spend is converted into the reporting currency at
+/// that day's rate.
+pub async fn get_total_ad_cost(
+    db: &sqlx::PgPool,
+    merchant_id: Uuid,
+    start_date: Option<DateTime<Utc>>,
+    end_date: Option<DateTime<Utc>>,
+) -> Result<Decimal, sqlx::Error> {
+    sqlx::query_scalar(
+        r#"
+        SELECT COALESCE(SUM(to_reporting_currency(merchant_id, spend, currency, spend_date)), 0)
+        FROM ad_spend_daily
+        WHERE merchant_id = $1
+            AND ($2::timestamptz IS NULL OR spend_date >= ($2::timestamptz AT TIME ZONE 'UTC')::date)
+            AND ($3::timestamptz IS NULL OR spend_date <= ($3::timestamptz AT TIME ZONE 'UTC')::date)
+        "#,
+    )
+    .bind(merchant_id)
+    .bind(start_date)
+    .bind(end_date)
+    .fetch_one(db)
+    .await
+}
+
+/// Import daily spend rows, creating accounts and campaigns as needed
+/// (can be used by HTTP handlers, connectors and tests).
+///
+/// Rows are keyed by campaign and day, so re-importing a day replaces its spend.
+pub async fn import_ad_spend(
+    db: &sqlx::PgPool,
+    payload: ImportAdSpendRequest,
+) -> Result<ImportAdSpendResponse, AppError> {
+    for row in &payload.rows {
+        validate_platform(&row.platform)?;
+        if row.spend.is_sign_negative() {
+            return Err(AppError::Validation(format!(
+                "Spend for campaign {} on {} must not be negative",
+                row.external_campaign_id, row.spend_date
+            )));
+        }
+    }
+
+    let mut tx = db.begin().await?;
+    let mut campaigns = HashSet::new();
+
+    for row in &payload.rows {
+        let ad_account_id = sqlx::query_scalar::<_, Uuid>(
+            r#"
+            INSERT INTO ad_accounts (merchant_id, platform, external_account_id, name, currency)
+            VALUES ($1, $2, $3, $4, $5)
+            ON CONFLICT (merchant_id, platform, external_account_id) DO UPDATE SET
+                name = COALESCE(EXCLUDED.name, ad_accounts.name),
+                currency = COALESCE(ad_accounts.currency, EXCLUDED.currency),
+                updated_at = NOW()
+            RETURNING id
+            "#,
+        )
+        .bind(payload.merchant_id)
+        .bind(&row.platform)
+        .bind(&row.external_account_id)
+        .bind(&row.account_name)
+        .bind(&row.currency)
+        .fetch_one(&mut *tx)
+        .await?;
+
+        let ad_campaign_id = sqlx::query_scalar::<_, Uuid>(
+            r#"
+            INSERT INTO ad_campaigns (merchant_id, ad_account_id, external_campaign_id, name)
+            VALUES ($1, $2, $3, $4)
+            ON CONFLICT (ad_account_id, external_campaign_id) DO UPDATE SET
+                name = COALESCE(EXCLUDED.name, ad_campaigns.name),
+                updated_at = NOW()
+            RETURNING id
+            "#,
+        )
+        .bind(payload.merchant_id)
+        .bind(ad_account_id)
+        .bind(&row.external_campaign_id)
+        .bind(&row.campaign_name)
+        .fetch_one(&mut *tx)
+        .await?;
+
+        sqlx::query(
+            r#"
+            INSERT INTO ad_spend_daily (
+                merchant_id, ad_campaign_id, spend_date, spend, currency,
+                impressions, clicks, conversions, conversion_value
+            )
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+            ON CONFLICT (ad_campaign_id, spend_date) DO UPDATE SET
+                spend = EXCLUDED.spend,
+                currency = EXCLUDED.currency,
+                impressions = EXCLUDED.impressions,
+                clicks = EXCLUDED.clicks,
+                conversions = EXCLUDED.conversions,
+                conversion_value = EXCLUDED.conversion_value,
+                updated_at = NOW()
+            "#,
+        )
+        .bind(payload.merchant_id)
+        .bind(ad_campaign_id)
+        .bind(row.spend_date)
+        .bind(row.spend)
+        .bind(&row.currency)
+        .bind(row.impressions)
+        .bind(row.clicks)
+        .bind(row.conversions)
+        .bind(row.conversion_value)
+        .execute(&mut *tx)
+        .await?;
+
+        campaigns.insert(ad_campaign_id);
+    }
+
+    let start_date = payload.rows.iter().map(|row| row.spend_date).min();
+    let end_date = payload.rows.iter().map(|row| row.spend_date).max();
+    if start_date.is_some() {
+        super::invalidate_profit_snapshots(
+            &mut *tx,
+            Some(payload.merchant_id),
+            start_date,
+            end_date,
+        )
+        .await?;
+    }
+
+    tx.commit().await?;
+
+    Ok(ImportAdSpendResponse {
+        rows_imported: payload.rows.len(),
+        campaigns: campaigns.len(),
+    })
+}
+
+pub fn ads_router() -> Router {
+    Router::new()
+        .route("/ads/accounts", get(list_accounts).post(create_account))
+        .route(
+            "/ads/accounts/:id",
+            get(get_account).put(update_account).delete(delete_account),
+        )
+        .route("/ads/campaigns", get(list_campaigns).post(create_campaign))
+        .route(
+            "/ads/campaigns/:id",
+            get(get_campaign)
+                .put(update_campaign)
+                .delete(delete_campaign),
+        )
+        .route("/ads/spend", get(list_spend).post(upsert_spend))
+        .route("/ads/spend/import", post(import_spend))
+        .route("/ads/spend/:id", delete(delete_spend))
+}
+
+// Ad accounts
+
+async fn list_accounts(
+    Extension(ctx): Extension<ApiContext>,
+    Query(params): Query<ListAdAccountsParams>,
+) -> AppResult<Vec<AdAccount>> {
+    eprintln!(
+        "Listing ad accounts: merchant_id={}, platform={:?}",
+        params.merchant_id, params.platform
+    );
+
+    let accounts = sqlx::query_as::<_, AdAccount>(
+        r#"
+        SELECT id, merchant_id, platform, external_account_id, name, currency, created_at, updated_at
+        FROM ad_accounts
+        WHERE merchant_id = $1
+            AND ($2::text IS NULL OR platform = $2)
+        ORDER BY platform, name
+        "#,
+    )
+    .bind(params.merchant_id)
+    .bind(params.platform)
+    .fetch_all(&ctx.db)
+    .await?;
+
+    Ok(Json(accounts))
+}
+
+async fn get_account(
+    Extension(ctx): Extension<ApiContext>,
+    Path(id): Path<Uuid>,
+) -> AppResult<AdAccount> {
+    eprintln!("Getting ad account: id={}", id);
+
+    let account = sqlx::query_as::<_, AdAccount>(
+        r#"
+        SELECT id, merchant_id, platform, external_account_id, name, currency, created_at, updated_at
+        FROM ad_accounts
+        WHERE id = $1
+        "#,
+    )
+    .bind(id)
+    .fetch_optional(&ctx.db)
+    .await?
+    .ok_or(AppError::NotFound)?;
+
+    Ok(Json(account))
+}
+
+async fn create_account(
+    Extension(ctx): Extension<ApiContext>,
+    Json(payload): Json<CreateAdAccountRequest>,
+) -> AppResult<AdAccount> {
+    eprintln!(
+        "Creating ad account: merchant_id={}, platform={}, external_account_id={}",
+        payload.merchant_id, payload.platform, payload.external_account_id
+    );
+
+    validate_platform(&payload.platform)?;
+
+    let existing = sqlx::query_scalar::<_, Option<Uuid>>(
+        r#"
+        SELECT id FROM ad_accounts
+        WHERE merchant_id = $1 AND platform = $2 AND external_account_id = $3
+        "#,
+    )
+    .bind(payload.merchant_id)
+    .bind(&payload.platform)
+    .bind(&payload.external_account_id)
+    .fetch_optional(&ctx.db)
+    .await?;
+
+    if existing.is_some() {
+        return Err(AppError::Validation(
+            "Ad account already exists".to_string(),
+        ));
+    }
+
+    let account = sqlx::query_as::<_, AdAccount>(
+        r#"
+        INSERT INTO ad_accounts (merchant_id, platform, external_account_id, name, currency)
+        VALUES ($1, $2, $3, $4, $5)
+        RETURNING id, merchant_id, platform, external_account_id, name, currency, created_at, updated_at
+        "#,
+    )
+    .bind(payload.merchant_id)
+    .bind(payload.platform)
+    .bind(payload.external_account_id)
+    .bind(payload.name)
+    .bind(payload.currency)
+    .fetch_one(&ctx.db)
+    .await?;
+
+    eprintln!("Ad account created successfully: id={}", account.id);
+    Ok(Json(account))
+}
+
+async fn update_account(
+    Extension(ctx): Extension<ApiContext>,
+    Path(id): Path<Uuid>,
+    Json(payload): Json<UpdateAdAccountRequest>,
+) -> AppResult<AdAccount> {
+    eprintln!(
+        "Updating ad account: id={}, name={:?}, currency={:?}",
+        id, payload.name, payload.currency
+    );
+
+    let account = sqlx::query_as::<_, AdAccount>(
+        r#"
+        UPDATE ad_accounts
+        SET
+            name = COALESCE($2, name),
+            currency = COALESCE($3, currency),
+            updated_at = NOW()
+        WHERE id = $1
+        RETURNING id, merchant_id, platform, external_account_id, name, currency, created_at, updated_at
+        "#,
+    )
+    .bind(id)
+    .bind(payload.name)
+    .bind(payload.currency)
+    .fetch_optional(&ctx.db)
+    .await?
+    .ok_or(AppError::NotFound)?;
+
+    Ok(Json(account))
+}
+
+async fn delete_account(
+    Extension(ctx): Extension<ApiContext>,
+    Path(id): Path<Uuid>,
+) -> Result<StatusCode, AppError> {
+    eprintln!("Deleting ad account: id={}", id);
+
+    // Campaigns and their spend are removed by ON DELETE CASCADE
+    let merchant_id = sqlx::query_scalar::<_, Uuid>(
+        "DELETE FROM ad_accounts WHERE id = $1 RETURNING merchant_id",
+    )
+    .bind(id)
+    .fetch_optional(&ctx.db)
+    .await?
+    .ok_or(AppError::NotFound)?;
+
+    super::invalidate_profit_snapshots(&ctx.db, Some(merchant_id), None, None).await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+// Ad campaigns
+
+async fn list_campaigns(
+    Extension(ctx): Extension<ApiContext>,
+    Query(params): Query<ListAdCampaignsParams>,
+) -> AppResult<Vec<AdCampaign>> {
+    eprintln!(
+        "Listing ad campaigns: merchant_id={}, ad_account_id={:?}",
+        params.merchant_id, params.ad_account_id
+    );
+
+    let campaigns = sqlx::query_as::<_, AdCampaign>(
+        r#"
+        SELECT id, merchant_id, ad_account_id, external_campaign_id, name, status, created_at, updated_at
+        FROM ad_campaigns
+        WHERE merchant_id = $1
+            AND ($2::uuid IS NULL OR ad_account_id = $2)
+        ORDER BY name
+        "#,
+    )
+    .bind(params.merchant_id)
+    .bind(params.ad_account_id)
+    .fetch_all(&ctx.db)
+    .await?;
+
+    Ok(Json(campaigns))
+}
+
+async fn get_campaign(
+    Extension(ctx): Extension<ApiContext>,
+    Path(id): Path<Uuid>,
+) -> AppResult<AdCampaign> {
+    eprintln!("Getting ad campaign: id={}", id);
+
+    let campaign = sqlx::query_as::<_, AdCampaign>(
+        r#"
+        SELECT id, merchant_id, ad_account_id, external_campaign_id, name, status, created_at, updated_at
+        FROM ad_campaigns
+        WHERE id = $1
+        "#,
+    )
+    .bind(id)
+    .fetch_optional(&ctx.db)
+    .await?
+    .ok_or(AppError::NotFound)?;
+
+    Ok(Json(campaign))
+}
+
+async fn create_campaign(
+    Extension(ctx): Extension<ApiContext>,
+    Json(payload): Json<CreateAdCampaignRequest>,
+) -> AppResult<AdCampaign> {
+    eprintln!(
+        "Creating ad campaign: ad_account_id={}, external_campaign_id={}",
+        payload.ad_account_id, payload.external_campaign_id
+    );
+
+    // The campaign belongs to the same merchant as its account
+    let merchant_id =
+        sqlx::query_scalar::<_, Uuid>("SELECT merchant_id FROM ad_accounts WHERE id = $1")
+            .bind(payload.ad_account_id)
+            .fetch_optional(&ctx.db)
+            .await?
+            .ok_or_else(|| AppError::Validation("Ad account does not exist".to_string()))?;
+
+    let existing = sqlx::query_scalar::<_, Option<Uuid>>(
+        r#"
+        SELECT id FROM ad_campaigns
+        WHERE ad_account_id = $1 AND external_campaign_id = $2
+        "#,
+    )
+    .bind(payload.ad_account_id)
+    .bind(&payload.external_campaign_id)
+    .fetch_optional(&ctx.db)
+    .await?;
+
+    if existing.is_some() {
+        return Err(AppError::Validation(
+            "Ad campaign already exists".to_string(),
+        ));
+    }
+
+    let campaign = sqlx::query_as::<_, AdCampaign>(
+        r#"
+        INSERT INTO ad_campaigns (merchant_id, ad_account_id, external_campaign_id, name, status)
+        VALUES ($1, $2, $3, $4, $5)
+        RETURNING id, merchant_id, ad_account_id, external_campaign_id, name, status, created_at, updated_at
+        "#,
+    )
+    .bind(merchant_id)
+    .bind(payload.ad_account_id)
+    .bind(payload.external_campaign_id)
+    .bind(payload.name)
+    .bind(payload.status)
+    .fetch_one(&ctx.db)
+    .await?;
+
+    eprintln!("Ad campaign created successfully: id={}", campaign.id);
+    Ok(Json(campaign))
+}
+
+async fn update_campaign(
+    Extension(ctx): Extension<ApiContext>,
+    Path(id): Path<Uuid>,
+    Json(payload): Json<UpdateAdCampaignRequest>,
+) -> AppResult<AdCampaign> {
+    eprintln!(
+        "Updating ad campaign: id={}, name={:?}, status={:?}",
+        id, payload.name, payload.status
+    );
+
+    let campaign = sqlx::query_as::<_, AdCampaign>(
+        r#"
+        UPDATE ad_campaigns
+        SET
+            name = COALESCE($2, name),
+            status = COALESCE($3, status),
+            updated_at = NOW()
+        WHERE id = $1
+        RETURNING id, merchant_id, ad_account_id, external_campaign_id, name, status, created_at, updated_at
+        "#,
+    )
+    .bind(id)
+    .bind(payload.name)
+    .bind(payload.status)
+    .fetch_optional(&ctx.db)
+    .await?
+    .ok_or(AppError::NotFound)?;
+
+    Ok(Json(campaign))
+}
+
+async fn delete_campaign(
+    Extension(ctx): Extension<ApiContext>,
+    Path(id): Path<Uuid>,
+) -> Result<StatusCode, AppError> {
+    eprintln!("Deleting ad campaign: id={}", id);
+
+    let merchant_id = sqlx::query_scalar::<_, Uuid>(
+        "DELETE FROM ad_campaigns WHERE id = $1 RETURNING merchant_id",
+    )
+    .bind(id)
+    .fetch_optional(&ctx.db)
+    .await?
+    .ok_or(AppError::NotFound)?;
+
+    super::invalidate_profit_snapshots(&ctx.db, Some(merchant_id), None, None).await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+// Daily spend
+
+async fn list_spend(
+    Extension(ctx): Extension<ApiContext>,
+    Query(params): Query<ListAdSpendParams>,
+) -> AppResult<AdSpendListResponse> {
+    eprintln!(
+        "Listing ad spend: merchant_id={}, ad_campaign_id={:?}, start_date={:?}, end_date={:?}",
+        params.merchant_id, params.ad_campaign_id, params.start_date, params.end_date
+    );
+
+    let limit = params.limit.unwrap_or(50).min(100);
+    let offset = params.offset.unwrap_or(0);
+
+    // Get total count
+    let total: i64 = sqlx::query_scalar::<_, Option<i64>>(
+        r#"
+        SELECT COUNT(*) as count
+        FROM ad_spend_daily
+        WHERE merchant_id = $1
+            AND ($2::uuid IS NULL OR ad_campaign_id = $2)
+            AND ($3::date IS NULL OR spend_date >= $3)
+            AND ($4::date IS NULL OR spend_date <= $4)
+        "#,
+    )
+    .bind(params.merchant_id)
+    .bind(params.ad_campaign_id)
+    .bind(params.start_date)
+    .bind(params.end_date)
+    .fetch_one(&ctx.db)
+    .await?
+    .unwrap_or(0);
+
+    let spend = sqlx::query_as::<_, AdSpend>(
+        r#"
+        SELECT
+            id,
+            merchant_id,
+            ad_campaign_id,
+            spend_date,
+            spend,
+            currency,
+            impressions,
+            clicks,
+            conversions,
+            conversion_value,
+            created_at,
+            updated_at
+        FROM ad_spend_daily
+        WHERE merchant_id = $1
+            AND ($2::uuid IS NULL OR ad_campaign_id = $2)
+            AND ($3::date IS NULL OR spend_date >= $3)
+            AND ($4::date IS NULL OR spend_date <= $4)
+        ORDER BY spend_date DESC, ad_campaign_id
+        LIMIT $5 OFFSET $6
+        "#,
+    )
+    .bind(params.merchant_id)
+    .bind(params.ad_campaign_id)
+    .bind(params.start_date)
+    .bind(params.end_date)
+    .bind(limit)
+    .bind(offset)
+    .fetch_all(&ctx.db)
+    .await?;
+
+    Ok(Json(AdSpendListResponse {
+        spend,
+        total,
+        limit,
+        offset,
+    }))
+}
+
+async fn upsert_spend(
+    Extension(ctx): Extension<ApiContext>,
+    Json(payload): Json<UpsertAdSpendRequest>,
+) -> AppResult<AdSpend> {
+    eprintln!(
+        "Upserting ad spend: ad_campaign_id={}, spend_date={}, spend={}",
+        payload.ad_campaign_id, payload.spend_date, payload.spend
+    );
+
+    if payload.spend.is_sign_negative() {
+        return Err(AppError::Validation(
+            "Spend must not be negative".to_string(),
+        ));
+    }
+
+    let merchant_id =
+        sqlx::query_scalar::<_, Uuid>("SELECT merchant_id FROM ad_campaigns WHERE id = $1")
+            .bind(payload.ad_campaign_id)
+            .fetch_optional(&ctx.db)
+            .await?
+            .ok_or_else(|| AppError::Validation("Ad campaign does not exist".to_string()))?;
+
+    let spend = sqlx::query_as::<_, AdSpend>(
+        r#"
+        INSERT INTO ad_spend_daily (
+            merchant_id, ad_campaign_id, spend_date, spend, currency,
+            impressions, clicks, conversions, conversion_value
+        )
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+        ON CONFLICT (ad_campaign_id, spend_date) DO UPDATE SET
+            spend = EXCLUDED.spend,
+            currency = EXCLUDED.currency,
+            impressions = EXCLUDED.impressions,
+            clicks = EXCLUDED.clicks,
+            conversions = EXCLUDED.conversions,
+            conversion_value = EXCLUDED.conversion_value,
+            updated_at = NOW()
+        RETURNING id, merchant_id, ad_campaign_id, spend_date, spend, currency,
+                  impressions, clicks, conversions, conversion_value, created_at, updated_at
+        "#,
+    )
+    .bind(merchant_id)
+    .bind(payload.ad_campaign_id)
+    .bind(payload.spend_date)
+    .bind(payload.spend)
+    .bind(payload.currency)
+    .bind(payload.impressions)
+    .bind(payload.clicks)
+    .bind(payload.conversions)
+    .bind(payload.conversion_value)
+    .fetch_one(&ctx.db)
+    .await?;
+
+    super::invalidate_profit_snapshots(
+        &ctx.db,
+        Some(merchant_id),
+        Some(spend.spend_date),
+        Some(spend.spend_date),
+    )
+    .await?;
+
+    Ok(Json(spend))
+}
+
+async fn import_spend(
+    Extension(ctx): Extension<ApiContext>,
+    Json(payload): Json<ImportAdSpendRequest>,
+) -> AppResult<ImportAdSpendResponse> {
+    eprintln!(
+        "Importing ad spend: merchant_id={}, rows={}",
+        payload.merchant_id,
+        payload.rows.len()
+    );
+
+    let merchant_id = payload.merchant_id;
+    let start_date = payload.rows.iter().map(|row| row.spend_date).min();
+    let end_date = payload.rows.iter().map(|row| row.spend_date).max();
+
+    let result = import_ad_spend(&ctx.db, payload).await?;
+
+    eprintln!(
+        "Ad spend imported successfully: rows={}, campaigns={}",
+        result.rows_imported, result.campaigns
+    );
+    if let (Some(start_date), Some(end_date)) = (start_date, end_date) {
+        ctx.publish_event(
+            merchant_id,
+            AdSpendImported {
+                start_date,
+                end_date,
+                rows_imported: result.rows_imported as u64,
+                campaigns: result.campaigns as u64,
+            },
+        )
+        .await;
+    }
+    Ok(Json(result))
+}
+
+async fn delete_spend(
+    Extension(ctx): Extension<ApiContext>,
+    Path(id): Path<i64>,
+) -> Result<StatusCode, AppError> {
+    eprintln!("Deleting ad spend: id={}", id);
+
+    let (merchant_id, spend_date) = sqlx::query_as::<_, (Uuid, NaiveDate)>(
+        "DELETE FROM ad_spend_daily WHERE id = $1 RETURNING merchant_id, spend_date",
+    )
+    .bind(id)
+    .fetch_optional(&ctx.db)
+    .await?
+    .ok_or(AppError::NotFound)?;
+
+    super::invalidate_profit_snapshots(
+        &ctx.db,
+        Some(merchant_id),
+        Some(spend_date),
+        Some(spend_date),
+    )
+    .await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
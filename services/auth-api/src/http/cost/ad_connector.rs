@@ -0,0 +1,339 @@
+use crate::http::{types::*, ApiContext, AppError, AppResult};
+use crate::Args;
+use async_trait::async_trait;
+use axum::{extract::Path, routing::post, Extension, Json, Router};
+use chrono::NaiveDate;
+use lib_shopify::kafka::AdSpendImported;
+use rust_decimal::Decimal;
+use serde::Deserialize;
+use std::path::{Path as FsPath, PathBuf};
+use thiserror::Error;
+use uuid::Uuid;
+
+use super::ad_campaign::import_ad_spend;
+
+/// Inclusive range of reporting days
+#[derive(Debug, Clone, Copy, Deserialize)]
+pub struct DateRange {
+    pub start_date: NaiveDate,
+    pub end_date: NaiveDate,
+}
+
+impl DateRange {
+    pub fn contains(&self, date: NaiveDate) -> bool {
+        self.start_date <= date && date <= self.end_date
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum ConnectorError {
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Failed to parse spend data: {0}")]
+    Parse(String),
+    #[error("No spend source found for account: {0}")]
+    SourceNotFound(String),
+    #[error("Missing currency for campaign {0}")]
+    MissingCurrency(String),
+    #[error("Invalid external account ID for a spend report: {0}")]
+    InvalidAccountId(String),
+}
+
+impl From<ConnectorError> for AppError {
+    fn from(e: ConnectorError) -> Self {
+        match e {
+            ConnectorError::SourceNotFound(_)
+            | ConnectorError::MissingCurrency(_)
+            | ConnectorError::InvalidAccountId(_) => {
+                AppError::Validation(e.to_string())
+            }
+            _ => AppError::Internal(e.to_string()),
+        }
+    }
+}
+
+/// Source of daily spend for one ad platform.
+///
+/// Implementations return rows already normalized to `AdSpendImportRow`, so
+/// Meta, Google and TikTok API clients can be added without touching the import
+/// path or the profit calculation.
+#[async_trait]
+pub trait AdPlatformConnector: Send + Sync {
+    /// Fetch daily spend per campaign for `account` on every day in `date_range`
+    async fn fetch_daily_spend(
+        &self,
+        account: &AdAccount,
+        date_range: &DateRange,
+    ) -> Result<Vec<AdSpendImportRow>, ConnectorError>;
+}
+
+/// Reads exported spend reports from disk.
+///
+/// Reports are looked up as `<root>/<platform>/<external_account_id>.csv` or
+/// `.json`, one row per campaign per day. Account IDs may only contain
+/// `[A-Za-z0-9_-]`, so a report is always read from under the root.
+pub struct FileConnector {
+    root: PathBuf,
+}
+
+/// A spend row as it appears in an exported report
+#[derive(Deserialize)]
+struct FileSpendRow {
+    date: NaiveDate,
+    campaign_id: String,
+    campaign_name: Option<String>,
+    spend: Decimal,
+    currency: Option<String>,
+    impressions: Option<i64>,
+    clicks: Option<i64>,
+    conversions: Option<Decimal>,
+    conversion_value: Option<Decimal>,
+}
+
+impl FileConnector {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+
+    /// The report of `account`, if one exists under the root
+    fn report_path(&self, account: &AdAccount) -> Result<PathBuf, ConnectorError> {
+        let account_id = &account.external_account_id;
+        let allowed = |c: char| c.is_ascii_alphanumeric() || c == '_' || c == '-';
+        if account_id.is_empty() || !account_id.chars().all(allowed) {
+            return Err(ConnectorError::InvalidAccountId(account_id.clone()));
+        }
+
+        let dir = self.root.join(&account.platform);
+        let path = ["csv", "json"]
+            .iter()
+            .map(|ext| dir.join(format!("{}.{}", account_id, ext)))
+            .find(|path| path.exists())
+            .ok_or_else(|| ConnectorError::SourceNotFound(account_id.clone()))?;
+
+        // Symlinks could still point elsewhere
+        let path = path.canonicalize()?;
+        if !path.starts_with(self.root.canonicalize()?) {
+            return Err(ConnectorError::InvalidAccountId(account_id.clone()));
+        }
+
+        Ok(path)
+    }
+
+    fn read_rows(path: &FsPath) -> Result<Vec<FileSpendRow>, ConnectorError> {
+        let data = std::fs::read(path)?;
+
+        if path.extension().is_some_and(|ext| ext == "json") {
+            serde_json::from_slice(&data).map_err(|e| ConnectorError::Parse(e.to_string()))
+        } else {
+            csv::ReaderBuilder::new()
+                .trim(csv::Trim::All)
+                .from_reader(data.as_slice())
+                .deserialize()
+                .collect::<Result<_, _>>()
+                .map_err(|e| ConnectorError::Parse(e.to_string()))
+        }
+    }
+}
+
+#[async_trait]
+impl AdPlatformConnector for FileConnector {
+    async fn fetch_daily_spend(
+        &self,
+        account: &AdAccount,
+        date_range: &DateRange,
+    ) -> Result<Vec<AdSpendImportRow>, ConnectorError> {
+        let path = self.report_path(account)?;
+
+        let rows = tokio::task::spawn_blocking(move || Self::read_rows(&path))
+            .await
+            .map_err(|e| ConnectorError::Parse(e.to_string()))??;
+
+        rows.into_iter()
+            .filter(|row| date_range.contains(row.date))
+            .map(|row| {
+                let currency = row
+                    .currency
+                    .or_else(|| account.currency.clone())
+                    .ok_or_else(|| ConnectorError::MissingCurrency(row.campaign_id.clone()))?;
+
+                Ok(AdSpendImportRow {
+                    platform: account.platform.clone(),
+                    external_account_id: account.external_account_id.clone(),
+                    account_name: account.name.clone(),
+                    external_campaign_id: row.campaign_id,
+                    campaign_name: row.campaign_name,
+                    spend_date: row.date,
+                    spend: row.spend,
+                    currency,
+                    impressions: row.impressions,
+                    clicks: row.clicks,
+                    conversions: row.conversions,
+                    conversion_value: row.conversion_value,
+                })
+            })
+            .collect()
+    }
+}
+
+/// Pick the connector for an ad platform.
+///
+/// Only exported files are supported today; API-backed connectors for each
+/// platform slot in here.
+pub fn connector_for(config: &Args, _platform: &str) -> Option<Box<dyn AdPlatformConnector>> {
+    config
+        .ad_spend_dir
+        .as_ref()
+        .map(|dir| Box::new(FileConnector::new(dir)) as Box<dyn AdPlatformConnector>)
+}
+
+/// Pull spend for an account from its connector and store it
+/// (can be used by HTTP handlers and schedulers).
+pub async fn sync_ad_account(
+    db: &sqlx::PgPool,
+    connector: &dyn AdPlatformConnector,
+    account: &AdAccount,
+    date_range: &DateRange,
+) -> Result<ImportAdSpendResponse, AppError> {
+    if date_range.start_date > date_range.end_date {
+        return Err(AppError::Validation(
+            "start_date must not be after end_date".to_string(),
+        ));
+    }
+
+    let rows = connector.fetch_daily_spend(account, date_range).await?;
+
+    import_ad_spend(
+        db,
+        ImportAdSpendRequest {
+            merchant_id: account.merchant_id,
+            rows,
+        },
+    )
+    .await
+}
+
+pub fn ad_connector_router() -> Router {
+    Router::new().route("/ads/accounts/:id/sync", post(sync_account_handler))
+}
+
+async fn sync_account_handler(
+    Extension(ctx): Extension<ApiContext>,
+    Path(id): Path<Uuid>,
+    Json(date_range): Json<DateRange>,
+) -> AppResult<ImportAdSpendResponse> {
+    eprintln!(
+        "Syncing ad account: id={}, start_date={}, end_date={}",
+        id, date_range.start_date, date_range.end_date
+    );
+
+    let account = sqlx::query_as::<_, AdAccount>(
+        r#"
+        SELECT id, merchant_id, platform, external_account_id, name, currency, created_at, updated_at
+        FROM ad_accounts
+        WHERE id = $1
+        "#,
+    )
+    .bind(id)
+    .fetch_optional(&ctx.db)
+    .await?
+    .ok_or(AppError::NotFound)?;
+
+    let connector = connector_for(&ctx.config, &account.platform).ok_or_else(|| {
+        AppError::Validation(format!(
+            "No connector configured for platform: {}",
+            account.platform
+        ))
+    })?;
+
+    let result = sync_ad_account(&ctx.db, connector.as_ref(), &account, &date_range).await?;
+
+    eprintln!(
+        "Ad account synced successfully: rows={}, campaigns={}",
+        result.rows_imported, result.campaigns
+    );
+    ctx.publish_event(
+        account.merchant_id,
+        AdSpendImported {
+            start_date: date_range.start_date,
+            end_date: date_range.end_date,
+            rows_imported: result.rows_imported as u64,
+            campaigns: result.campaigns as u64,
+        },
+    )
+    .await;
+    Ok(Json(result))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    fn test_account(external_account_id: &str) -> AdAccount {
+        AdAccount {
+            id: Uuid::new_v4(),
+            merchant_id: Uuid::new_v4(),
+            platform: "meta".to_string(),
+            external_account_id: external_account_id.to_string(),
+            name: Some("Test Account".to_string()),
+            currency: Some("USD".to_string()),
+            created_at: chrono::Utc::now(),
+            updated_at: chrono::Utc::now(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_file_connector_reads_csv_and_json_within_range() {
+        let root = std::env::temp_dir().join(format!("ad-spend-{}", Uuid::new_v4()));
+        std::fs::create_dir_all(root.join("meta")).unwrap();
+
+        std::fs::write(
+            root.join("meta/act_csv.csv"),
+            "date,campaign_id,campaign_name,spend,currency,impressions,clicks,conversions,conversion_value\n\
+             2024-01-01,cmp_1,Spring Sale,12.50,,1000,20,,\n\
+             2024-01-05,cmp_1,Spring Sale,99.00,EUR,,,,\n",
+        )
+        .unwrap();
+        std::fs::write(
+            root.join("meta/act_json.json"),
+            r#"[{"date": "2024-01-02", "campaign_id": "cmp_2", "spend": "7.25", "currency": "GBP"}]"#,
+        )
+        .unwrap();
+
+        let connector = FileConnector::new(&root);
+        let date_range = DateRange {
+            start_date: NaiveDate::from_ymd_opt(2024, 1, 1).unwrap(),
+            end_date: NaiveDate::from_ymd_opt(2024, 1, 3).unwrap(),
+        };
+
+        let csv_rows = connector
+            .fetch_daily_spend(&test_account("act_csv"), &date_range)
+            .await
+            .unwrap();
+        assert_eq!(csv_rows.len(), 1);
+        assert_eq!(csv_rows[0].external_campaign_id, "cmp_1");
+        assert_eq!(csv_rows[0].spend, Decimal::from_str("12.50").unwrap());
+        // Falls back to the account currency when the report leaves it blank
+        assert_eq!(csv_rows[0].currency, "USD");
+        assert_eq!(csv_rows[0].impressions, Some(1000));
+
+        let json_rows = connector
+            .fetch_daily_spend(&test_account("act_json"), &date_range)
+            .await
+            .unwrap();
+        assert_eq!(json_rows.len(), 1);
+        assert_eq!(json_rows[0].currency, "GBP");
+        assert_eq!(json_rows[0].platform, "meta");
+
+        // Account IDs cannot reach outside the report root
+        std::fs::write(root.join("secret.csv"), "date,campaign_id,spend\n").unwrap();
+        for account_id in ["../secret", "/etc/passwd", "meta/../../secret", ""] {
+            let result = connector
+                .fetch_daily_spend(&test_account(account_id), &date_range)
+                .await;
+            assert!(matches!(result, Err(ConnectorError::InvalidAccountId(_))));
+        }
+
+        std::fs::remove_dir_all(root).unwrap();
+    }
+}
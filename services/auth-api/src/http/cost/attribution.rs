@@ -0,0 +1,284 @@
+use crate::http::merchants::{get_app_settings, ATTRIBUTION_MODELS};
+use crate::http::{types::*, ApiContext, AppError, AppResult};
+use axum::{extract::Query, routing::get, routing::post, Extension, Json, Router};
+use chrono::NaiveDate;
+use rust_decimal::Decimal;
+use uuid::Uuid;
+
+#[derive(sqlx::FromRow)]
+struct AttributionSummary {
+    allocations_created: i64,
+    orders_attributed: i64,
+    spend: Decimal,
+    allocated_spend: Decimal,
+}
+
+/// Distribute each campaign's daily spend over the orders processed that day
+/// in the merchant's timezone and store the result in `order_ad_allocations`
+/// (can be used by HTTP handlers and tests).
+///
+/// Models:
+/// - `even`: every order of the day gets the same share
+/// - `revenue`: orders get a share proportional to their revenue
+/// - `utm`: orders whose `utm_campaign` matches the campaign's external ID or
+///   name split its spend by revenue; campaigns without tagged orders that day
+///   fall back to `revenue` over all of the day's orders
+///
+/// Previous allocations for the days in the window are replaced. Spend on days
+/// without orders stays unallocated.
+pub async fn run_attribution(
+    db: &sqlx::PgPool,
+    merchant_id: Uuid,
+    start_date: NaiveDate,
+    end_date: NaiveDate,
+    model: Option<String>,
+) -> Result<AttributionRunResponse, AppError> {
+    if start_date > end_date {
+        return Err(AppError::Validation(
+            "start_date must not be after end_date".to_string(),
+        ));
+    }
+
+    let model = match model {
+        Some(model) => model,
+        None => get_app_settings(db, merchant_id).await?.attribution_model,
+    };
+    if !ATTRIBUTION_MODELS.contains(&model.as_str()) {
+        return Err(AppError::Validation(format!(
+            "model must be one of: {}",
+            ATTRIBUTION_MODELS.join(", ")
+        )));
+    }
+
+    let mut tx = db.begin().await?;
+
+    sqlx::query(
+        r#"
+        DELETE FROM order_ad_allocations
+        WHERE merchant_id = $1 AND spend_date >= $2 AND spend_date <= $3
+        "#,
+    )
+    .bind(merchant_id)
+    .bind(start_date)
+    .bind(end_date)
+    .execute(&mut *tx)
+    .await?;
+
+    sqlx::query(
+        r#"
+        WITH spend AS (
+            SELECT
+                s.ad_campaign_id,
+                s.spend_date,
+                LOWER(c.external_campaign_id) AS external_campaign_id,
+                LOWER(c.name) AS campaign_name,
+                to_reporting_currency(s.merchant_id, s.spend, s.currency, s.spend_date) AS spend
+            FROM ad_spend_daily s
+            JOIN ad_campaigns c ON c.id = s.ad_campaign_id
+            WHERE s.merchant_id = $1
+                AND s.spend_date >= $2 AND s.spend_date <= $3
+                AND s.spend > 0
+        ),
+        day_orders AS (
+            SELECT
+                r.order_id,
+                (r.processed_at AT TIME ZONE tz.timezone)::date AS processed_on,
+                r.revenue,
+                LOWER(o.utm_campaign) AS utm_campaign
+            FROM order_revenue r
+            JOIN orders o ON o.id = r.order_id
+            JOIN merchant_timezone tz ON tz.merchant_id = r.merchant_id
+            WHERE r.merchant_id = $1
+                AND r.processed_at >= $2::date::timestamp AT TIME ZONE tz.timezone
+                AND r.processed_at < ($3::date + 1)::timestamp AT TIME ZONE tz.timezone
+        ),
+        candidates AS (
+            SELECT
+                s.ad_campaign_id,
+                s.spend_date,
+                s.spend,
+                d.order_id,
+                d.revenue,
+                COALESCE(d.utm_campaign IN (s.external_campaign_id, s.campaign_name), FALSE) AS tagged
+            FROM spend s
+            JOIN day_orders d ON d.processed_on = s.spend_date
+        ),
+        eligible AS (
+            SELECT *
+            FROM (
+                SELECT
+                    candidates.*,
+                    bool_or(tagged) OVER (PARTITION BY ad_campaign_id, spend_date) AS any_tagged
+                FROM candidates
+            ) c
+            WHERE $4 <> 'utm' OR tagged OR NOT any_tagged
+        ),
+        shares AS (
+            SELECT
+                e.*,
+                CASE
+                    WHEN $4 = 'even' THEN 1.0 / COUNT(*) OVER w
+                    ELSE COALESCE(e.revenue / NULLIF(SUM(e.revenue) OVER w, 0), 1.0 / COUNT(*) OVER w)
+                END AS share
+            FROM eligible e
+            WINDOW w AS (PARTITION BY e.ad_campaign_id, e.spend_date)
+        )
+        INSERT INTO order_ad_allocations (
+            merchant_id, order_id, ad_campaign_id, spend_date, model, share, allocated_cost
+        )
+        SELECT $1, order_id, ad_campaign_id, spend_date, $4, share, ROUND(spend * share, 4)
+        FROM shares
+        "#,
+    )
+    .bind(merchant_id)
+    .bind(start_date)
+    .bind(end_date)
+    .bind(&model)
+    .execute(&mut *tx)
+    .await?;
+
+    let summary = sqlx::query_as::<_, AttributionSummary>(
+        r#"
+        SELECT
+            COUNT(*) AS allocations_created,
+            COUNT(DISTINCT order_id) AS orders_attributed,
+            (
+                SELECT COALESCE(SUM(to_reporting_currency(merchant_id, spend, currency, spend_date)), 0)
+                FROM ad_spend_daily
+                WHERE merchant_id = $1 AND spend_date >= $2 AND spend_date <= $3
+            ) AS spend,
+            COALESCE(SUM(allocated_cost), 0) AS allocated_spend
+        FROM order_ad_allocations
+        WHERE merchant_id = $1 AND spend_date >= $2 AND spend_date <= $3
+        "#,
+    )
+    .bind(merchant_id)
+    .bind(start_date)
+    .bind(end_date)
+    .fetch_one(&mut *tx)
+    .await?;
+
+    super::invalidate_profit_snapshots(
+        &mut *tx,
+        Some(merchant_id),
+        Some(start_date),
+        Some(end_date),
+    )
+    .await?;
+    tx.commit().await?;
+
+    Ok(AttributionRunResponse {
+        merchant_id,
+        model,
+        start_date,
+        end_date,
+        allocations_created: summary.allocations_created,
+        orders_attributed: summary.orders_attributed,
+        unallocated_spend: summary.spend - summary.allocated_spend,
+        spend: summary.spend,
+        allocated_spend: summary.allocated_spend,
+    })
+}
+
+pub fn attribution_router() -> Router {
+    Router::new()
+        .route("/attribution/run", post(run_attribution_handler))
+        .route("/attribution/allocations", get(list_allocations))
+}
+
+async fn run_attribution_handler(
+    Extension(ctx): Extension<ApiContext>,
+    Json(payload): Json<RunAttributionRequest>,
+) -> AppResult<AttributionRunResponse> {
+    eprintln!(
+        "Running ad attribution: merchant_id={}, start_date={}, end_date={}, model={:?}",
+        payload.merchant_id, payload.start_date, payload.end_date, payload.model
+    );
+
+    let run = run_attribution(
+        &ctx.db,
+        payload.merchant_id,
+        payload.start_date,
+        payload.end_date,
+        payload.model,
+    )
+    .await?;
+
+    eprintln!(
+        "Ad attribution finished: model={}, allocations={}, unallocated_spend={}",
+        run.model, run.allocations_created, run.unallocated_spend
+    );
+    Ok(Json(run))
+}
+
+async fn list_allocations(
+    Extension(ctx): Extension<ApiContext>,
+    Query(params): Query<ListOrderAdAllocationsParams>,
+) -> AppResult<OrderAdAllocationListResponse> {
+    eprintln!(
+        "Listing ad allocations: merchant_id={}, order_id={:?}, ad_campaign_id={:?}",
+        params.merchant_id, params.order_id, params.ad_campaign_id
+    );
+
+    let limit = params.limit.unwrap_or(50).min(100);
+    let offset = params.offset.unwrap_or(0);
+
+    // Get total count
+    let total: i64 = sqlx::query_scalar::<_, Option<i64>>(
+        r#"
+        SELECT COUNT(*) as count
+        FROM order_ad_allocations
+        WHERE merchant_id = $1
+            AND ($2::bigint IS NULL OR order_id = $2)
+            AND ($3::uuid IS NULL OR ad_campaign_id = $3)
+            AND ($4::date IS NULL OR spend_date >= $4)
+            AND ($5::date IS NULL OR spend_date <= $5)
+        "#,
+    )
+    .bind(params.merchant_id)
+    .bind(params.order_id)
+    .bind(params.ad_campaign_id)
+    .bind(params.start_date)
+    .bind(params.end_date)
+    .fetch_one(&ctx.db)
+    .await?
+    .unwrap_or(0);
+
+    // Get allocations
+    let allocations = sqlx::query_as::<_, OrderAdAllocation>(
+        r#"
+        SELECT id, merchant_id, order_id, ad_campaign_id, spend_date, model, share,
+               allocated_cost, created_at
+        FROM order_ad_allocations
+        WHERE merchant_id = $1
+            AND ($2::bigint IS NULL OR order_id = $2)
+            AND ($3::uuid IS NULL OR ad_campaign_id = $3)
+            AND ($4::date IS NULL OR spend_date >= $4)
+            AND ($5::date IS NULL OR spend_date <= $5)
+        ORDER BY spend_date DESC, ad_campaign_id, order_id
+        LIMIT $6 OFFSET $7
+        "#,
+    )
+    .bind(params.merchant_id)
+    .bind(params.order_id)
+    .bind(params.ad_campaign_id)
+    .bind(params.start_date)
+    .bind(params.end_date)
+    .bind(limit)
+    .bind(offset)
+    .fetch_all(&ctx.db)
+    .await?;
+
+    eprintln!(
+        "Found {} ad allocations (total: {})",
+        allocations.len(),
+        total
+    );
+
+    Ok(Json(OrderAdAllocationListResponse {
+        allocations,
+        total,
+        limit,
+        offset,
+    }))
+}
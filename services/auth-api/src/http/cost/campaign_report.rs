@@ -0,0 +1,196 @@
+use crate::http::{types::*, ApiContext, AppError, AppResult};
+use axum::{extract::Query, routing::get, Extension, Json, Router};
+use chrono::{Days, NaiveDate, NaiveTime, TimeDelta};
+use rust_decimal::Decimal;
+use std::collections::HashMap;
+use uuid::Uuid;
+
+use super::fx;
+
+/// A campaign's totals (`spend_date` is NULL) or one of its days
+#[derive(sqlx::FromRow)]
+struct CampaignReportRow {
+    ad_campaign_id: Uuid,
+    external_campaign_id: String,
+    name: Option<String>,
+    platform: String,
+    spend_date: Option<NaiveDate>,
+    spend: Decimal,
+    attributed_revenue: Decimal,
+    attributed_gross_profit: Decimal,
+    roas: Option<Decimal>,
+    poas: Option<Decimal>,
+}
+
+/// Spend, attributed revenue and attributed gross profit per campaign and per
+/// day (can be used by HTTP handlers and tests).
+///
+/// Each order carries a share of a campaign's spend on the day it was processed
+/// (see the order_ad_shares view), and the campaign is credited with the same
+/// share of the order's revenue and gross profit. Gross profit uses the
+/// per-order components of `/calculate`: revenue, minus the order's refunds,
+/// product cost net of restocked returns, and courier cost. ROAS and POAS are
+/// attributed revenue and gross profit per unit of spend, and are null for
+/// campaigns or days without spend.
+pub async fn get_campaign_report(
+    db: &sqlx::PgPool,
+    params: &CampaignReportParams,
+) -> Result<CampaignReportResponse, AppError> {
+    if params.start_date > params.end_date {
+        return Err(AppError::Validation(
+            "start_date must not be after end_date".to_string(),
+        ));
+    }
+
+    let rows = sqlx::query_as::<_, CampaignReportRow>(
+        r#"
+        WITH spend AS (
+            SELECT
+                ad_campaign_id,
+                spend_date,
+                SUM(to_reporting_currency(merchant_id, spend, currency, spend_date)) AS spend
+            FROM ad_spend_daily
+            WHERE merchant_id = $1
+                AND spend_date >= $2 AND spend_date <= $3
+                AND ($4::uuid IS NULL OR ad_campaign_id = $4)
+            GROUP BY ad_campaign_id, spend_date
+        ),
+        order_gross_profit AS (
+            SELECT
+                r.order_id,
+                r.revenue,
+                r.revenue
+                    - COALESCE((SELECT SUM(rr.revenue) FROM refund_revenue rr WHERE rr.order_id = r.order_id), 0)
+                    - COALESCE(pc.product_cost, 0)
+                    + COALESCE((SELECT SUM(rpc.product_cost) FROM refund_product_costs rpc WHERE rpc.order_id = r.order_id), 0)
+                    - COALESCE(cc.courier_cost, 0) AS gross_profit
+            FROM order_revenue r
+            LEFT JOIN order_product_costs pc ON pc.order_id = r.order_id
+            LEFT JOIN order_courier_costs cc ON cc.order_id = r.order_id
+            WHERE r.merchant_id = $1
+        ),
+        attributed AS (
+            SELECT
+                s.ad_campaign_id,
+                s.spend_date,
+                SUM(s.share * o.revenue) AS revenue,
+                SUM(s.share * o.gross_profit) AS gross_profit
+            FROM order_ad_shares s
+            JOIN order_gross_profit o ON o.order_id = s.order_id
+            WHERE s.merchant_id = $1 AND s.spend_date >= $2 AND s.spend_date <= $3
+            GROUP BY s.ad_campaign_id, s.spend_date
+        ),
+        days AS (
+            SELECT
+                sp.ad_campaign_id,
+                sp.spend_date,
+                sp.spend,
+                COALESCE(a.revenue, 0) AS revenue,
+                COALESCE(a.gross_profit, 0) AS gross_profit
+            FROM spend sp
+            LEFT JOIN attributed a
+                ON a.ad_campaign_id = sp.ad_campaign_id AND a.spend_date = sp.spend_date
+        )
+        SELECT
+            c.id AS ad_campaign_id,
+            c.external_campaign_id,
+            c.name,
+            acc.platform,
+            d.spend_date,
+            SUM(d.spend) AS spend,
+            ROUND(SUM(d.revenue), 4) AS attributed_revenue,
+            ROUND(SUM(d.gross_profit), 4) AS attributed_gross_profit,
+            ROUND(SUM(d.revenue) / NULLIF(SUM(d.spend), 0), 4) AS roas,
+            ROUND(SUM(d.gross_profit) / NULLIF(SUM(d.spend), 0), 4) AS poas
+        FROM days d
+        JOIN ad_campaigns c ON c.id = d.ad_campaign_id
+        JOIN ad_accounts acc ON acc.id = c.ad_account_id
+        GROUP BY GROUPING SETS (
+            (c.id, c.external_campaign_id, c.name, acc.platform),
+            (c.id, c.external_campaign_id, c.name, acc.platform, d.spend_date)
+        )
+        ORDER BY d.spend_date NULLS FIRST, SUM(d.spend) DESC, c.id
+        "#,
+    )
+    .bind(params.merchant_id)
+    .bind(params.start_date)
+    .bind(params.end_date)
+    .bind(params.ad_campaign_id)
+    .fetch_all(db)
+    .await?;
+
+    // Totals rows come first, ranked by spend; days follow in date order
+    let mut campaigns: Vec<CampaignPerformance> = Vec::new();
+    let mut index: HashMap<Uuid, usize> = HashMap::new();
+    for row in rows {
+        match row.spend_date {
+            None => {
+                index.insert(row.ad_campaign_id, campaigns.len());
+                campaigns.push(CampaignPerformance {
+                    ad_campaign_id: row.ad_campaign_id,
+                    external_campaign_id: row.external_campaign_id,
+                    name: row.name,
+                    platform: row.platform,
+                    spend: row.spend,
+                    attributed_revenue: row.attributed_revenue,
+                    attributed_gross_profit: row.attributed_gross_profit,
+                    roas: row.roas,
+                    poas: row.poas,
+                    days: Vec::new(),
+                });
+            }
+            Some(spend_date) => {
+                if let Some(&i) = index.get(&row.ad_campaign_id) {
+                    campaigns[i].days.push(CampaignDayPerformance {
+                        spend_date,
+                        spend: row.spend,
+                        attributed_revenue: row.attributed_revenue,
+                        attributed_gross_profit: row.attributed_gross_profit,
+                        roas: row.roas,
+                        poas: row.poas,
+                    });
+                }
+            }
+        }
+    }
+
+    // Spend days are UTC dates, as are the processing days orders are matched on
+    let window_start = params.start_date.and_time(NaiveTime::MIN).and_utc();
+    let window_end = (params.end_date + Days::new(1))
+        .and_time(NaiveTime::MIN)
+        .and_utc()
+        - TimeDelta::microseconds(1);
+    let warnings =
+        fx::get_currency_warnings(db, params.merchant_id, Some(window_start), Some(window_end))
+            .await?;
+
+    Ok(CampaignReportResponse {
+        merchant_id: params.merchant_id,
+        start_date: params.start_date,
+        end_date: params.end_date,
+        campaigns,
+        warnings,
+    })
+}
+
+pub fn campaign_report_router() -> Router {
+    Router::new().route("/ads/campaigns/report", get(campaign_report))
+}
+
+async fn campaign_report(
+    Extension(ctx): Extension<ApiContext>,
+    Query(params): Query<CampaignReportParams>,
+) -> AppResult<CampaignReportResponse> {
+    eprintln!(
+        "Building campaign report: merchant_id={}, start_date={}, end_date={}, ad_campaign_id={:?}",
+        params.merchant_id, params.start_date, params.end_date, params.ad_campaign_id
+    );
+
+    let report = get_campaign_report(&ctx.db, &params).await?;
+
+    eprintln!(
+        "Campaign report built: {} campaigns",
+        report.campaigns.len()
+    );
+    Ok(Json(report))
+}
@@ -0,0 +1,450 @@
+use crate::http::{types::*, ApiContext, AppError, AppResult};
+use axum::{
+    extract::{Path, Query},
+    http::StatusCode,
+    routing::{delete, get},
+    Extension, Json, Router,
+};
+use chrono::{DateTime, Utc};
+use rust_decimal::Decimal;
+use uuid::Uuid;
+
+/// Sum what couriers charged for the merchant's orders in a window
+/// (can be used by other modules).
+///
+/// Per-order costs come from the `order_courier_costs` view: invoiced amounts
+/// when an invoice line matched the order, otherwise the rate card estimate.
+pub async fn get_total_courier_cost(
+    db: &sqlx::PgPool,
+    merchant_id: Uuid,
+    start_date: Option<DateTime<Utc>>,
+    end_date: Option<DateTime<Utc>>,
+) -> Result<Decimal, sqlx::Error> {
+    sqlx::query_scalar(
+        r#"
+        SELECT COALESCE(SUM(courier_cost), 0) FROM order_courier_costs
+        WHERE merchant_id = $1
+            AND ($2::timestamptz IS NULL OR processed_at >= $2)
+            AND ($3::timestamptz IS NULL OR processed_at <= $3)
+        "#,
+    )
+    .bind(merchant_id)
+    .bind(start_date)
+    .bind(end_date)
+    .fetch_one(db)
+    .await
+}
+
+/// Import a courier invoice, replacing the lines of a previous import of the
+/// same invoice (can be used by HTTP handlers and tests).
+///
+/// Lines are matched to orders by order name or Shopify order ID; unmatched
+/// lines are kept and reported back so they can be fixed up.
+pub async fn import_courier_invoice(
+    db: &sqlx::PgPool,
+    payload: ImportCourierInvoiceRequest,
+) -> Result<ImportCourierInvoiceResponse, AppError> {
+    if payload
+        .lines
+        .iter()
+        .any(|line| line.amount.is_sign_negative())
+    {
+        return Err(AppError::Validation(
+            "Invoice line amounts must not be negative".to_string(),
+        ));
+    }
+
+    let mut tx = db.begin().await?;
+
+    let invoice = sqlx::query_as::<_, CourierInvoice>(
+        r#"
+        INSERT INTO courier_invoices (merchant_id, carrier, invoice_number, invoice_date, currency)
+        VALUES ($1, $2, $3, $4, $5)
+        ON CONFLICT (merchant_id, carrier, invoice_number) DO UPDATE SET
+            invoice_date = EXCLUDED.invoice_date,
+            currency = EXCLUDED.currency,
+            updated_at = NOW()
+        RETURNING id, merchant_id, carrier, invoice_number, invoice_date, currency,
+                  created_at, updated_at
+        "#,
+    )
+    .bind(payload.merchant_id)
+    .bind(&payload.carrier)
+    .bind(&payload.invoice_number)
+    .bind(payload.invoice_date)
+    .bind(&payload.currency)
+    .fetch_one(&mut *tx)
+    .await?;
+
+    sqlx::query("DELETE FROM courier_invoice_lines WHERE invoice_id = $1")
+        .bind(invoice.id)
+        .execute(&mut *tx)
+        .await?;
+
+    let mut unmatched_references = Vec::new();
+
+    for line in &payload.lines {
+        let order_id = sqlx::query_scalar::<_, Option<i64>>(
+            r#"
+            INSERT INTO courier_invoice_lines (
+                invoice_id, merchant_id, order_id, order_reference, tracking_number, amount
+            )
+            VALUES (
+                $1, $2,
+                (SELECT id FROM orders
+                 WHERE merchant_id = $2 AND (name = $3 OR shopify_order_id::text = $3)
+                 LIMIT 1),
+                $3, $4, $5
+            )
+            RETURNING order_id
+            "#,
+        )
+        .bind(invoice.id)
+        .bind(payload.merchant_id)
+        .bind(line.order_reference.trim())
+        .bind(&line.tracking_number)
+        .bind(line.amount)
+        .fetch_one(&mut *tx)
+        .await?;
+
+        if order_id.is_none() {
+            unmatched_references.push(line.order_reference.clone());
+        }
+    }
+
+    // Invoice lines can match orders of any date
+    super::invalidate_profit_snapshots(&mut *tx, Some(invoice.merchant_id), None, None).await?;
+    tx.commit().await?;
+
+    Ok(ImportCourierInvoiceResponse {
+        invoice,
+        lines_imported: payload.lines.len(),
+        unmatched_references,
+    })
+}
+
+pub fn courier_router() -> Router {
+    Router::new()
+        .route("/courier/zones", get(list_zones).post(create_zone))
+        .route("/courier/zones/:id", delete(delete_zone))
+        .route(
+            "/courier/rate-cards",
+            get(list_rate_cards).post(create_rate_card),
+        )
+        .route(
+            "/courier/rate-cards/:id",
+            axum::routing::put(update_rate_card).delete(delete_rate_card),
+        )
+        .route("/courier/invoices", get(list_invoices).post(import_invoice))
+        .route("/courier/invoices/:id", delete(delete_invoice))
+}
+
+// Zones
+
+async fn list_zones(
+    Extension(ctx): Extension<ApiContext>,
+    Query(params): Query<ListCourierParams>,
+) -> AppResult<Vec<CourierZone>> {
+    eprintln!("Listing courier zones: merchant_id={}", params.merchant_id);
+
+    let zones = sqlx::query_as::<_, CourierZone>(
+        r#"
+        SELECT id, merchant_id, zone, country_code, created_at
+        FROM courier_zones
+        WHERE merchant_id = $1
+        ORDER BY zone, country_code
+        "#,
+    )
+    .bind(params.merchant_id)
+    .fetch_all(&ctx.db)
+    .await?;
+
+    Ok(Json(zones))
+}
+
+async fn create_zone(
+    Extension(ctx): Extension<ApiContext>,
+    Json(payload): Json<CreateCourierZoneRequest>,
+) -> AppResult<CourierZone> {
+    eprintln!(
+        "Creating courier zone: merchant_id={}, zone={}, country_code={}",
+        payload.merchant_id, payload.zone, payload.country_code
+    );
+
+    let country_code = payload.country_code.trim().to_uppercase();
+    if country_code.len() != 2 {
+        return Err(AppError::Validation(
+            "country_code must be an ISO 3166-1 alpha-2 code".to_string(),
+        ));
+    }
+
+    // A country belongs to exactly one zone; remapping it moves it
+    let zone = sqlx::query_as::<_, CourierZone>(
+        r#"
+        INSERT INTO courier_zones (merchant_id, zone, country_code)
+        VALUES ($1, $2, $3)
+        ON CONFLICT (merchant_id, country_code) DO UPDATE SET zone = EXCLUDED.zone
+        RETURNING id, merchant_id, zone, country_code, created_at
+        "#,
+    )
+    .bind(payload.merchant_id)
+    .bind(payload.zone)
+    .bind(country_code)
+    .fetch_one(&ctx.db)
+    .await?;
+
+    super::invalidate_profit_snapshots(&ctx.db, Some(zone.merchant_id), None, None).await?;
+
+    Ok(Json(zone))
+}
+
+async fn delete_zone(
+    Extension(ctx): Extension<ApiContext>,
+    Path(id): Path<Uuid>,
+) -> Result<StatusCode, AppError> {
+    eprintln!("Deleting courier zone: id={}", id);
+
+    let merchant_id = sqlx::query_scalar::<_, Uuid>(
+        "DELETE FROM courier_zones WHERE id = $1 RETURNING merchant_id",
+    )
+    .bind(id)
+    .fetch_optional(&ctx.db)
+    .await?
+    .ok_or(AppError::NotFound)?;
+
+    super::invalidate_profit_snapshots(&ctx.db, Some(merchant_id), None, None).await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+// Rate cards
+
+fn validate_weight_band(min: Decimal, max: Option<Decimal>) -> Result<(), AppError> {
+    if min.is_sign_negative() || max.is_some_and(|max| max <= min) {
+        return Err(AppError::Validation(
+            "Weight band must satisfy 0 <= min_weight_grams < max_weight_grams".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+async fn list_rate_cards(
+    Extension(ctx): Extension<ApiContext>,
+    Query(params): Query<ListCourierParams>,
+) -> AppResult<Vec<CourierRateCard>> {
+    eprintln!(
+        "Listing courier rate cards: merchant_id={}, carrier={:?}",
+        params.merchant_id, params.carrier
+    );
+
+    let rate_cards = sqlx::query_as::<_, CourierRateCard>(
+        r#"
+        SELECT id, merchant_id, carrier, zone, min_weight_grams, max_weight_grams, price,
+               currency, created_at, updated_at
+        FROM courier_rate_cards
+        WHERE merchant_id = $1
+            AND ($2::text IS NULL OR carrier = $2)
+        ORDER BY carrier, zone, min_weight_grams
+        "#,
+    )
+    .bind(params.merchant_id)
+    .bind(params.carrier)
+    .fetch_all(&ctx.db)
+    .await?;
+
+    Ok(Json(rate_cards))
+}
+
+/// Create a rate card (can be used by HTTP handlers and tests).
+pub async fn create_courier_rate_card(
+    db: &sqlx::PgPool,
+    payload: CreateCourierRateCardRequest,
+) -> Result<CourierRateCard, AppError> {
+    let min_weight_grams = payload.min_weight_grams.unwrap_or_default();
+    validate_weight_band(min_weight_grams, payload.max_weight_grams)?;
+    if payload.price.is_sign_negative() {
+        return Err(AppError::Validation(
+            "Price must not be negative".to_string(),
+        ));
+    }
+
+    let rate_card = sqlx::query_as::<_, CourierRateCard>(
+        r#"
+        INSERT INTO courier_rate_cards (
+            merchant_id, carrier, zone, min_weight_grams, max_weight_grams, price, currency
+        )
+        VALUES ($1, $2, $3, $4, $5, $6, $7)
+        RETURNING id, merchant_id, carrier, zone, min_weight_grams, max_weight_grams, price,
+                  currency, created_at, updated_at
+        "#,
+    )
+    .bind(payload.merchant_id)
+    .bind(payload.carrier)
+    .bind(payload.zone.unwrap_or_else(|| "default".to_string()))
+    .bind(min_weight_grams)
+    .bind(payload.max_weight_grams)
+    .bind(payload.price)
+    .bind(payload.currency)
+    .fetch_one(db)
+    .await?;
+
+    // Rate cards estimate the shipping of orders of any date
+    super::invalidate_profit_snapshots(db, Some(rate_card.merchant_id), None, None).await?;
+
+    Ok(rate_card)
+}
+
+async fn create_rate_card(
+    Extension(ctx): Extension<ApiContext>,
+    Json(payload): Json<CreateCourierRateCardRequest>,
+) -> AppResult<CourierRateCard> {
+    eprintln!(
+        "Creating courier rate card: merchant_id={}, carrier={}, zone={:?}",
+        payload.merchant_id, payload.carrier, payload.zone
+    );
+
+    let rate_card = create_courier_rate_card(&ctx.db, payload).await?;
+
+    eprintln!(
+        "Courier rate card created successfully: id={}",
+        rate_card.id
+    );
+    Ok(Json(rate_card))
+}
+
+async fn update_rate_card(
+    Extension(ctx): Extension<ApiContext>,
+    Path(id): Path<Uuid>,
+    Json(payload): Json<UpdateCourierRateCardRequest>,
+) -> AppResult<CourierRateCard> {
+    eprintln!(
+        "Updating courier rate card: id={}, price={:?}",
+        id, payload.price
+    );
+
+    if payload.price.is_some_and(|price| price.is_sign_negative()) {
+        return Err(AppError::Validation(
+            "Price must not be negative".to_string(),
+        ));
+    }
+
+    let mut tx = ctx.db.begin().await?;
+
+    let rate_card = sqlx::query_as::<_, CourierRateCard>(
+        r#"
+        UPDATE courier_rate_cards
+        SET
+            min_weight_grams = COALESCE($2, min_weight_grams),
+            max_weight_grams = COALESCE($3, max_weight_grams),
+            price = COALESCE($4, price),
+            updated_at = NOW()
+        WHERE id = $1
+        RETURNING id, merchant_id, carrier, zone, min_weight_grams, max_weight_grams, price,
+                  currency, created_at, updated_at
+        "#,
+    )
+    .bind(id)
+    .bind(payload.min_weight_grams)
+    .bind(payload.max_weight_grams)
+    .bind(payload.price)
+    .fetch_optional(&mut *tx)
+    .await?
+    .ok_or(AppError::NotFound)?;
+
+    // Validate the merged band; dropping the transaction rolls the update back
+    validate_weight_band(rate_card.min_weight_grams, rate_card.max_weight_grams)?;
+    super::invalidate_profit_snapshots(&mut *tx, Some(rate_card.merchant_id), None, None).await?;
+    tx.commit().await?;
+
+    Ok(Json(rate_card))
+}
+
+async fn delete_rate_card(
+    Extension(ctx): Extension<ApiContext>,
+    Path(id): Path<Uuid>,
+) -> Result<StatusCode, AppError> {
+    eprintln!("Deleting courier rate card: id={}", id);
+
+    let merchant_id = sqlx::query_scalar::<_, Uuid>(
+        "DELETE FROM courier_rate_cards WHERE id = $1 RETURNING merchant_id",
+    )
+    .bind(id)
+    .fetch_optional(&ctx.db)
+    .await?
+    .ok_or(AppError::NotFound)?;
+
+    super::invalidate_profit_snapshots(&ctx.db, Some(merchant_id), None, None).await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+// Invoices
+
+async fn list_invoices(
+    Extension(ctx): Extension<ApiContext>,
+    Query(params): Query<ListCourierParams>,
+) -> AppResult<Vec<CourierInvoice>> {
+    eprintln!(
+        "Listing courier invoices: merchant_id={}, carrier={:?}",
+        params.merchant_id, params.carrier
+    );
+
+    let invoices = sqlx::query_as::<_, CourierInvoice>(
+        r#"
+        SELECT id, merchant_id, carrier, invoice_number, invoice_date, currency,
+               created_at, updated_at
+        FROM courier_invoices
+        WHERE merchant_id = $1
+            AND ($2::text IS NULL OR carrier = $2)
+        ORDER BY invoice_date DESC NULLS LAST, created_at DESC
+        "#,
+    )
+    .bind(params.merchant_id)
+    .bind(params.carrier)
+    .fetch_all(&ctx.db)
+    .await?;
+
+    Ok(Json(invoices))
+}
+
+async fn import_invoice(
+    Extension(ctx): Extension<ApiContext>,
+    Json(payload): Json<ImportCourierInvoiceRequest>,
+) -> AppResult<ImportCourierInvoiceResponse> {
+    eprintln!(
+        "Importing courier invoice: merchant_id={}, carrier={}, invoice_number={}, lines={}",
+        payload.merchant_id,
+        payload.carrier,
+        payload.invoice_number,
+        payload.lines.len()
+    );
+
+    let result = import_courier_invoice(&ctx.db, payload).await?;
+
+    eprintln!(
+        "Courier invoice imported successfully: id={}, lines={}, unmatched={}",
+        result.invoice.id,
+        result.lines_imported,
+        result.unmatched_references.len()
+    );
+    Ok(Json(result))
+}
+
+async fn delete_invoice(
+    Extension(ctx): Extension<ApiContext>,
+    Path(id): Path<Uuid>,
+) -> Result<StatusCode, AppError> {
+    eprintln!("Deleting courier invoice: id={}", id);
+
+    let merchant_id = sqlx::query_scalar::<_, Uuid>(
+        "DELETE FROM courier_invoices WHERE id = $1 RETURNING merchant_id",
+    )
+    .bind(id)
+    .fetch_optional(&ctx.db)
+    .await?
+    .ok_or(AppError::NotFound)?;
+
+    super::invalidate_profit_snapshots(&ctx.db, Some(merchant_id), None, None).await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
@@ -0,0 +1,496 @@
+use crate::http::{types::*, ApiContext, AppError, AppResult};
+use crate::Args;
+use async_trait::async_trait;
+use axum::{extract::Query, routing::get, routing::post, Extension, Json, Router};
+use chrono::{DateTime, NaiveDate, Utc};
+use rust_decimal::Decimal;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use thiserror::Error;
+use uuid::Uuid;
+
+use super::ad_connector::DateRange;
+
+/// Where stored rates came from
+const FX_SOURCES: &[&str] = &["manual", "file", "api"];
+
+#[derive(Error, Debug)]
+pub enum FxError {
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("HTTP error: {0}")]
+    Http(#[from] reqwest::Error),
+    #[error("Failed to parse FX rates: {0}")]
+    Parse(String),
+}
+
+impl From<FxError> for AppError {
+    fn from(e: FxError) -> Self {
+        AppError::Internal(e.to_string())
+    }
+}
+
+/// Source of daily exchange rates
+#[async_trait]
+pub trait FxRateProvider: Send + Sync {
+    /// Value stored in `fx_rates.source` for rates from this provider
+    fn source(&self) -> &'static str;
+
+    /// Fetch rates from `base_currency` into each of `quote_currencies` for the
+    /// days in `date_range` (days without a published rate may be missing)
+    async fn fetch_rates(
+        &self,
+        base_currency: &str,
+        quote_currencies: &[String],
+        date_range: &DateRange,
+    ) -> Result<Vec<FxRateInput>, FxError>;
+}
+
+/// Reads rates from a CSV or JSON file with `date, base_currency, quote_currency, rate` rows
+pub struct FileFxRateProvider {
+    path: PathBuf,
+}
+
+#[derive(Deserialize)]
+struct FileRateRow {
+    date: NaiveDate,
+    base_currency: String,
+    quote_currency: String,
+    rate: Decimal,
+}
+
+impl FileFxRateProvider {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+
+    fn read_rows(path: &std::path::Path) -> Result<Vec<FileRateRow>, FxError> {
+        let data = std::fs::read(path)?;
+
+        if path.extension().is_some_and(|ext| ext == "json") {
+            serde_json::from_slice(&data).map_err(|e| FxError::Parse(e.to_string()))
+        } else {
+            csv::ReaderBuilder::new()
+                .trim(csv::Trim::All)
+                .from_reader(data.as_slice())
+                .deserialize()
+                .collect::<Result<_, _>>()
+                .map_err(|e| FxError::Parse(e.to_string()))
+        }
+    }
+}
+
+#[async_trait]
+impl FxRateProvider for FileFxRateProvider {
+    fn source(&self) -> &'static str {
+        "file"
+    }
+
+    async fn fetch_rates(
+        &self,
+        base_currency: &str,
+        quote_currencies: &[String],
+        date_range: &DateRange,
+    ) -> Result<Vec<FxRateInput>, FxError> {
+        let path = self.path.clone();
+        let rows = tokio::task::spawn_blocking(move || Self::read_rows(&path))
+            .await
+            .map_err(|e| FxError::Parse(e.to_string()))??;
+
+        Ok(rows
+            .into_iter()
+            .filter(|row| {
+                row.base_currency == base_currency
+                    && quote_currencies.contains(&row.quote_currency)
+                    && date_range.contains(row.date)
+            })
+            .map(|row| FxRateInput {
+                base_currency: row.base_currency,
+                quote_currency: row.quote_currency,
+                rate_date: row.date,
+                rate: row.rate,
+            })
+            .collect())
+    }
+}
+
+/// Fetches rates from a Frankfurter-compatible API
+/// (`GET <base_url>/<start>..<end>?from=EUR&to=USD,GBP`)
+pub struct ApiFxRateProvider {
+    base_url: String,
+    client: reqwest::Client,
+}
+
+#[derive(Deserialize)]
+struct ApiRatesResponse {
+    rates: BTreeMap<NaiveDate, BTreeMap<String, Decimal>>,
+}
+
+impl ApiFxRateProvider {
+    pub fn new(base_url: impl Into<String>) -> Self {
+        Self {
+            base_url: base_url.into(),
+            client: reqwest::Client::new(),
+        }
+    }
+}
+
+#[async_trait]
+impl FxRateProvider for ApiFxRateProvider {
+    fn source(&self) -> &'static str {
+        "api"
+    }
+
+    async fn fetch_rates(
+        &self,
+        base_currency: &str,
+        quote_currencies: &[String],
+        date_range: &DateRange,
+    ) -> Result<Vec<FxRateInput>, FxError> {
+        let url = format!(
+            "{}/{}..{}",
+            self.base_url.trim_end_matches('/'),
+            date_range.start_date,
+            date_range.end_date
+        );
+
+        let response: ApiRatesResponse = self
+            .client
+            .get(&url)
+            .query(&[
+                ("from", base_currency.to_string()),
+                ("to", quote_currencies.join(",")),
+            ])
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+
+        Ok(response
+            .rates
+            .into_iter()
+            .filter(|(date, _)| date_range.contains(*date))
+            .flat_map(|(date, quotes)| {
+                quotes
+                    .into_iter()
+                    .map(move |(quote_currency, rate)| FxRateInput {
+                        base_currency: base_currency.to_string(),
+                        quote_currency,
+                        rate_date: date,
+                        rate,
+                    })
+            })
+            .collect())
+    }
+}
+
+/// Pick the configured FX rate provider; a rates file wins over an API
+pub fn fx_provider_for(config: &Args) -> Option<Box<dyn FxRateProvider>> {
+    if let Some(path) = &config.fx_rates_file {
+        return Some(Box::new(FileFxRateProvider::new(path)));
+    }
+    config
+        .fx_rates_api_url
+        .as_ref()
+        .map(|url| Box::new(ApiFxRateProvider::new(url)) as Box<dyn FxRateProvider>)
+}
+
+/// Store daily rates, replacing rates already stored for the same pair and day
+/// (can be used by HTTP handlers, providers and tests).
+pub async fn upsert_fx_rates(
+    db: &sqlx::PgPool,
+    source: &str,
+    rates: &[FxRateInput],
+) -> Result<usize, AppError> {
+    if !FX_SOURCES.contains(&source) {
+        return Err(AppError::Validation(format!(
+            "source must be one of: {}",
+            FX_SOURCES.join(", ")
+        )));
+    }
+    if let Some(rate) = rates.iter().find(|rate| rate.rate <= Decimal::ZERO) {
+        return Err(AppError::Validation(format!(
+            "Rate for {}/{} on {} must be positive",
+            rate.base_currency, rate.quote_currency, rate.rate_date
+        )));
+    }
+
+    let mut tx = db.begin().await?;
+
+    for rate in rates {
+        sqlx::query(
+            r#"
+            INSERT INTO fx_rates (base_currency, quote_currency, rate_date, rate, source)
+            VALUES ($1, $2, $3, $4, $5)
+            ON CONFLICT (base_currency, quote_currency, rate_date) DO UPDATE SET
+                rate = EXCLUDED.rate,
+                source = EXCLUDED.source,
+                updated_at = NOW()
+            "#,
+        )
+        .bind(rate.base_currency.to_uppercase())
+        .bind(rate.quote_currency.to_uppercase())
+        .bind(rate.rate_date)
+        .bind(rate.rate)
+        .bind(source)
+        .execute(&mut *tx)
+        .await?;
+    }
+
+    // A rate is used from its day until the next stored one, by every merchant
+    if let Some(since) = rates.iter().map(|rate| rate.rate_date).min() {
+        super::invalidate_profit_snapshots(&mut *tx, None, Some(since), None).await?;
+    }
+
+    tx.commit().await?;
+
+    Ok(rates.len())
+}
+
+#[derive(sqlx::FromRow)]
+struct ForeignCurrency {
+    currency: String,
+    missing_rates: i64,
+}
+
+/// Describe amounts in a window that are not in the merchant's reporting
+/// currency (can be used by other modules).
+///
+/// With `multi_currency_mode = warn` any foreign currency is reported, since it
+/// is summed unconverted. With `convert` only amounts lacking an FX rate are.
+pub async fn get_currency_warnings(
+    db: &sqlx::PgPool,
+    merchant_id: Uuid,
+    start_date: Option<DateTime<Utc>>,
+    end_date: Option<DateTime<Utc>>,
+) -> Result<Vec<String>, sqlx::Error> {
+    let Some((reporting_currency, multi_currency_mode)) =
+        sqlx::query_as::<_, (Option<String>, String)>(
+            r#"
+            SELECT reporting_currency, multi_currency_mode
+            FROM merchant_currency
+            WHERE merchant_id = $1
+            "#,
+        )
+        .bind(merchant_id)
+        .fetch_optional(db)
+        .await?
+    else {
+        return Ok(Vec::new());
+    };
+
+    // Every currency-bearing amount that can end up in the calculation
+    let foreign = sqlx::query_as::<_, ForeignCurrency>(
+        r#"
+        WITH amounts AS (
+            SELECT o.currency, (o.processed_at AT TIME ZONE 'UTC')::date AS on_date
+            FROM included_orders io
+            JOIN orders o ON o.id = io.id
+            WHERE o.merchant_id = $1
+                AND ($2::timestamptz IS NULL OR o.processed_at >= $2)
+                AND ($3::timestamptz IS NULL OR o.processed_at <= $3)
+            UNION ALL
+            SELECT currency, spend_date
+            FROM ad_spend_daily
+            WHERE merchant_id = $1
+                AND ($2::timestamptz IS NULL OR spend_date >= ($2::timestamptz AT TIME ZONE 'UTC')::date)
+                AND ($3::timestamptz IS NULL OR spend_date <= ($3::timestamptz AT TIME ZONE 'UTC')::date)
+            UNION ALL
+            -- Entries active in the window; one-off entries only apply on starts_on
+            SELECT
+                currency,
+                GREATEST(starts_on, COALESCE(($2::timestamptz AT TIME ZONE 'UTC')::date, starts_on))
+            FROM manual_costs
+            WHERE merchant_id = $1
+                AND ($3::timestamptz IS NULL OR starts_on <= ($3::timestamptz AT TIME ZONE 'UTC')::date)
+                AND ($2::timestamptz IS NULL
+                    OR CASE cost_type WHEN 'one_off' THEN starts_on ELSE COALESCE(ends_on, 'infinity') END
+                        >= ($2::timestamptz AT TIME ZONE 'UTC')::date)
+            UNION ALL
+            -- The cost rows used for the window's sales
+            SELECT cost_currency, (processed_at AT TIME ZONE 'UTC')::date
+            FROM line_item_costs
+            WHERE merchant_id = $1
+                AND ($2::timestamptz IS NULL OR processed_at >= $2)
+                AND ($3::timestamptz IS NULL OR processed_at <= $3)
+            UNION ALL
+            -- The invoice or rate card each of the window's orders is costed with
+            SELECT cost_currency, (processed_at AT TIME ZONE 'UTC')::date
+            FROM order_courier_costs
+            WHERE merchant_id = $1
+                AND ($2::timestamptz IS NULL OR processed_at >= $2)
+                AND ($3::timestamptz IS NULL OR processed_at <= $3)
+        )
+        SELECT
+            currency,
+            COUNT(*) FILTER (WHERE fx_rate(currency, $4, on_date) IS NULL) AS missing_rates
+        FROM amounts
+        WHERE currency IS NOT NULL AND currency IS DISTINCT FROM $4
+        GROUP BY currency
+        ORDER BY currency
+        "#,
+    )
+    .bind(merchant_id)
+    .bind(start_date)
+    .bind(end_date)
+    .bind(&reporting_currency)
+    .fetch_all(db)
+    .await?;
+
+    let currencies = foreign
+        .iter()
+        .map(|f| f.currency.as_str())
+        .collect::<Vec<_>>();
+
+    let warnings = match reporting_currency {
+        None if currencies.len() > 1 => vec![format!(
+            "Mixed currencies found ({}) but no reporting currency is set; amounts are summed without conversion",
+            currencies.join(", ")
+        )],
+        None => Vec::new(),
+        Some(reporting) if multi_currency_mode == "convert" => foreign
+            .iter()
+            .filter(|f| f.missing_rates > 0)
+            .map(|f| {
+                format!(
+                    "No FX rate from {} to {} for {} amount(s); they are summed without conversion",
+                    f.currency, reporting, f.missing_rates
+                )
+            })
+            .collect(),
+        Some(reporting) if !currencies.is_empty() => vec![format!(
+            "Mixed currencies found: amounts in {} are summed as {} without conversion (multi_currency_mode = warn)",
+            currencies.join(", "),
+            reporting
+        )],
+        Some(_) => Vec::new(),
+    };
+
+    Ok(warnings)
+}
+
+pub fn fx_router() -> Router {
+    Router::new()
+        .route("/fx-rates", get(list_rates).post(upsert_rates))
+        .route("/fx-rates/sync", post(sync_rates))
+}
+
+async fn list_rates(
+    Extension(ctx): Extension<ApiContext>,
+    Query(params): Query<ListFxRatesParams>,
+) -> AppResult<FxRateListResponse> {
+    eprintln!(
+        "Listing FX rates: base_currency={:?}, quote_currency={:?}",
+        params.base_currency, params.quote_currency
+    );
+
+    let limit = params.limit.unwrap_or(50).min(100);
+    let offset = params.offset.unwrap_or(0);
+
+    // Get total count
+    let total: i64 = sqlx::query_scalar::<_, Option<i64>>(
+        r#"
+        SELECT COUNT(*) as count
+        FROM fx_rates
+        WHERE ($1::text IS NULL OR base_currency = $1)
+            AND ($2::text IS NULL OR quote_currency = $2)
+            AND ($3::date IS NULL OR rate_date >= $3)
+            AND ($4::date IS NULL OR rate_date <= $4)
+        "#,
+    )
+    .bind(&params.base_currency)
+    .bind(&params.quote_currency)
+    .bind(params.start_date)
+    .bind(params.end_date)
+    .fetch_one(&ctx.db)
+    .await?
+    .unwrap_or(0);
+
+    // Get rates
+    let rates = sqlx::query_as::<_, FxRate>(
+        r#"
+        SELECT id, base_currency, quote_currency, rate_date, rate, source, created_at, updated_at
+        FROM fx_rates
+        WHERE ($1::text IS NULL OR base_currency = $1)
+            AND ($2::text IS NULL OR quote_currency = $2)
+            AND ($3::date IS NULL OR rate_date >= $3)
+            AND ($4::date IS NULL OR rate_date <= $4)
+        ORDER BY rate_date DESC, base_currency, quote_currency
+        LIMIT $5 OFFSET $6
+        "#,
+    )
+    .bind(&params.base_currency)
+    .bind(&params.quote_currency)
+    .bind(params.start_date)
+    .bind(params.end_date)
+    .bind(limit)
+    .bind(offset)
+    .fetch_all(&ctx.db)
+    .await?;
+
+    eprintln!("Found {} FX rates (total: {})", rates.len(), total);
+
+    Ok(Json(FxRateListResponse {
+        rates,
+        total,
+        limit,
+        offset,
+    }))
+}
+
+async fn upsert_rates(
+    Extension(ctx): Extension<ApiContext>,
+    Json(payload): Json<UpsertFxRatesRequest>,
+) -> AppResult<UpsertFxRatesResponse> {
+    eprintln!(
+        "Upserting FX rates: source={:?}, count={}",
+        payload.source,
+        payload.rates.len()
+    );
+
+    let source = payload.source.as_deref().unwrap_or("manual");
+    let rates_upserted = upsert_fx_rates(&ctx.db, source, &payload.rates).await?;
+
+    eprintln!("FX rates upserted successfully: count={}", rates_upserted);
+    Ok(Json(UpsertFxRatesResponse { rates_upserted }))
+}
+
+async fn sync_rates(
+    Extension(ctx): Extension<ApiContext>,
+    Json(payload): Json<SyncFxRatesRequest>,
+) -> AppResult<UpsertFxRatesResponse> {
+    eprintln!(
+        "Syncing FX rates: base_currency={}, quote_currencies={:?}, start_date={}, end_date={}",
+        payload.base_currency, payload.quote_currencies, payload.start_date, payload.end_date
+    );
+
+    let date_range = DateRange {
+        start_date: payload.start_date,
+        end_date: payload.end_date,
+    };
+    if date_range.start_date > date_range.end_date {
+        return Err(AppError::Validation(
+            "start_date must not be after end_date".to_string(),
+        ));
+    }
+
+    let provider = fx_provider_for(&ctx.config).ok_or_else(|| {
+        AppError::Validation(
+            "No FX rate source configured (set FX_RATES_FILE or FX_RATES_API_URL)".to_string(),
+        )
+    })?;
+
+    let rates = provider
+        .fetch_rates(
+            &payload.base_currency,
+            &payload.quote_currencies,
+            &date_range,
+        )
+        .await?;
+    let rates_upserted = upsert_fx_rates(&ctx.db, provider.source(), &rates).await?;
+
+    eprintln!("FX rates synced successfully: count={}", rates_upserted);
+    Ok(Json(UpsertFxRatesResponse { rates_upserted }))
+}
@@ -0,0 +1,370 @@
+use crate::http::{types::*, ApiContext, AppError, AppResult};
+use axum::{
+    extract::{Path, Query},
+    http::StatusCode,
+    routing::get,
+    Extension, Json, Router,
+};
+use chrono::{DateTime, NaiveDate, Utc};
+use rust_decimal::Decimal;
+use uuid::Uuid;
+
+/// Kinds of manual cost entries we accept
+const MANUAL_COST_TYPES: &[&str] = &["one_off", "monthly", "per_order", "percent_of_revenue"];
+
+fn validate_manual_cost(
+    cost_type: &str,
+    amount: Decimal,
+    starts_on: NaiveDate,
+    ends_on: Option<NaiveDate>,
+) -> Result<(), AppError> {
+    if !MANUAL_COST_TYPES.contains(&cost_type) {
+        return Err(AppError::Validation(format!(
+            "cost_type must be one of: {}",
+            MANUAL_COST_TYPES.join(", ")
+        )));
+    }
+    if amount.is_sign_negative() {
+        return Err(AppError::Validation(
+            "Amount must not be negative".to_string(),
+        ));
+    }
+    if cost_type == "percent_of_revenue" && amount > Decimal::ONE_HUNDRED {
+        return Err(AppError::Validation(
+            "Percentage of revenue must not exceed 100".to_string(),
+        ));
+    }
+    if ends_on.is_some_and(|ends_on| ends_on < starts_on) {
+        return Err(AppError::Validation(
+            "ends_on must not be before starts_on".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+/// Sum manual costs attributable to a window (can be used by other modules)
+///
+/// - one-off entries count when they were incurred inside the window
+/// - monthly entries are prorated per day: each active day in the window
+///   carries 1/days-in-month of the monthly amount (open windows run to today)
+/// - per-order and percent-of-revenue entries apply to the orders processed in
+///   the window while the entry was active
+///
+/// Days are calendar days in UTC. Fixed amounts are converted into the
+/// reporting currency at the rate of the day they apply to; percentages apply
+/// to revenue that is already converted.
+pub async fn get_total_manual_cost(
+    db: &sqlx::PgPool,
+    merchant_id: Uuid,
+    start_date: Option<DateTime<Utc>>,
+    end_date: Option<DateTime<Utc>>,
+) -> Result<Decimal, sqlx::Error> {
+    sqlx::query_scalar(
+        r#"
+        WITH entries AS (
+            SELECT
+                mc.*,
+                ($2::timestamptz AT TIME ZONE 'UTC')::date AS start_day,
+                ($3::timestamptz AT TIME ZONE 'UTC')::date AS end_day
+            FROM manual_costs mc
+            WHERE mc.merchant_id = $1
+        ),
+        one_off AS (
+            SELECT COALESCE(SUM(to_reporting_currency(merchant_id, amount, currency, starts_on)), 0) AS cost
+            FROM entries
+            WHERE cost_type = 'one_off'
+                AND (start_day IS NULL OR starts_on >= start_day)
+                AND (end_day IS NULL OR starts_on <= end_day)
+        ),
+        monthly AS (
+            SELECT COALESCE(SUM(
+                to_reporting_currency(e.merchant_id, e.amount, e.currency, d::date)
+                    / EXTRACT(DAY FROM date_trunc('month', d) + INTERVAL '1 month' - INTERVAL '1 day')
+            ), 0) AS cost
+            FROM entries e
+            CROSS JOIN LATERAL generate_series(
+                GREATEST(e.starts_on, e.start_day)::timestamp,
+                COALESCE(LEAST(e.ends_on, e.end_day), CURRENT_DATE)::timestamp,
+                INTERVAL '1 day'
+            ) AS d
+            WHERE e.cost_type = 'monthly'
+        ),
+        order_based AS (
+            SELECT COALESCE(SUM(
+                CASE e.cost_type
+                    WHEN 'per_order' THEN to_reporting_currency(
+                        e.merchant_id, e.amount, e.currency, (o.processed_at AT TIME ZONE 'UTC')::date
+                    )
+                    ELSE e.amount / 100 * o.revenue
+                END
+            ), 0) AS cost
+            FROM entries e
+            JOIN order_revenue o
+                ON o.merchant_id = e.merchant_id
+                AND (o.processed_at AT TIME ZONE 'UTC')::date >= e.starts_on
+                AND (e.ends_on IS NULL OR (o.processed_at AT TIME ZONE 'UTC')::date <= e.ends_on)
+            WHERE e.cost_type IN ('per_order', 'percent_of_revenue')
+                AND ($2::timestamptz IS NULL OR o.processed_at >= $2)
+                AND ($3::timestamptz IS NULL OR o.processed_at <= $3)
+        )
+        SELECT ROUND(one_off.cost + monthly.cost + order_based.cost, 4)
+        FROM one_off, monthly, order_based
+        "#,
+    )
+    .bind(merchant_id)
+    .bind(start_date)
+    .bind(end_date)
+    .fetch_one(db)
+    .await
+}
+
+/// Create a manual cost entry (can be used by HTTP handlers and tests).
+pub async fn create_manual_cost(
+    db: &sqlx::PgPool,
+    payload: CreateManualCostRequest,
+) -> Result<ManualCost, AppError> {
+    validate_manual_cost(
+        &payload.cost_type,
+        payload.amount,
+        payload.starts_on,
+        payload.ends_on,
+    )?;
+
+    let manual_cost = sqlx::query_as::<_, ManualCost>(
+        r#"
+        INSERT INTO manual_costs (
+            merchant_id, name, category, cost_type, amount, currency, starts_on, ends_on
+        )
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+        RETURNING id, merchant_id, name, category, cost_type, amount, currency, starts_on,
+                  ends_on, created_at, updated_at
+        "#,
+    )
+    .bind(payload.merchant_id)
+    .bind(payload.name)
+    .bind(payload.category)
+    .bind(payload.cost_type)
+    .bind(payload.amount)
+    .bind(payload.currency)
+    .bind(payload.starts_on)
+    .bind(payload.ends_on)
+    .fetch_one(db)
+    .await?;
+
+    // One-off costs only count on their start day
+    let until = match manual_cost.cost_type.as_str() {
+        "one_off" => Some(manual_cost.starts_on),
+        _ => manual_cost.ends_on,
+    };
+    super::invalidate_profit_snapshots(
+        db,
+        Some(manual_cost.merchant_id),
+        Some(manual_cost.starts_on),
+        until,
+    )
+    .await?;
+
+    Ok(manual_cost)
+}
+
+pub fn manual_cost_router() -> Router {
+    Router::new()
+        .route("/manual-costs", get(list_costs).post(create_cost))
+        .route(
+            "/manual-costs/:id",
+            get(get_cost).put(update_cost).delete(delete_cost),
+        )
+}
+
+async fn list_costs(
+    Extension(ctx): Extension<ApiContext>,
+    Query(params): Query<ListManualCostsParams>,
+) -> AppResult<ManualCostListResponse> {
+    eprintln!(
+        "Listing manual costs: merchant_id={}, cost_type={:?}",
+        params.merchant_id, params.cost_type
+    );
+
+    let limit = params.limit.unwrap_or(50).min(100);
+    let offset = params.offset.unwrap_or(0);
+
+    // Get total count
+    let total: i64 = sqlx::query_scalar::<_, Option<i64>>(
+        r#"
+        SELECT COUNT(*) as count
+        FROM manual_costs
+        WHERE merchant_id = $1
+            AND ($2::text IS NULL OR cost_type = $2)
+        "#,
+    )
+    .bind(params.merchant_id)
+    .bind(&params.cost_type)
+    .fetch_one(&ctx.db)
+    .await?
+    .unwrap_or(0);
+
+    // Get manual costs
+    let manual_costs = sqlx::query_as::<_, ManualCost>(
+        r#"
+        SELECT id, merchant_id, name, category, cost_type, amount, currency, starts_on,
+               ends_on, created_at, updated_at
+        FROM manual_costs
+        WHERE merchant_id = $1
+            AND ($2::text IS NULL OR cost_type = $2)
+        ORDER BY starts_on DESC, name
+        LIMIT $3 OFFSET $4
+        "#,
+    )
+    .bind(params.merchant_id)
+    .bind(&params.cost_type)
+    .bind(limit)
+    .bind(offset)
+    .fetch_all(&ctx.db)
+    .await?;
+
+    eprintln!(
+        "Found {} manual costs (total: {})",
+        manual_costs.len(),
+        total
+    );
+
+    Ok(Json(ManualCostListResponse {
+        manual_costs,
+        total,
+        limit,
+        offset,
+    }))
+}
+
+async fn get_cost(
+    Extension(ctx): Extension<ApiContext>,
+    Path(id): Path<Uuid>,
+) -> AppResult<ManualCost> {
+    eprintln!("Getting manual cost: id={}", id);
+
+    let manual_cost = sqlx::query_as::<_, ManualCost>(
+        r#"
+        SELECT id, merchant_id, name, category, cost_type, amount, currency, starts_on,
+               ends_on, created_at, updated_at
+        FROM manual_costs
+        WHERE id = $1
+        "#,
+    )
+    .bind(id)
+    .fetch_optional(&ctx.db)
+    .await?
+    .ok_or(AppError::NotFound)?;
+
+    Ok(Json(manual_cost))
+}
+
+async fn create_cost(
+    Extension(ctx): Extension<ApiContext>,
+    Json(payload): Json<CreateManualCostRequest>,
+) -> AppResult<ManualCost> {
+    eprintln!(
+        "Creating manual cost: merchant_id={}, name={}, cost_type={}",
+        payload.merchant_id, payload.name, payload.cost_type
+    );
+
+    let manual_cost = create_manual_cost(&ctx.db, payload).await?;
+
+    eprintln!("Manual cost created successfully: id={}", manual_cost.id);
+    Ok(Json(manual_cost))
+}
+
+async fn update_cost(
+    Extension(ctx): Extension<ApiContext>,
+    Path(id): Path<Uuid>,
+    Json(payload): Json<UpdateManualCostRequest>,
+) -> AppResult<ManualCost> {
+    eprintln!(
+        "Updating manual cost: id={}, amount={:?}, starts_on={:?}, ends_on={:?}",
+        id, payload.amount, payload.starts_on, payload.ends_on
+    );
+
+    let mut tx = ctx.db.begin().await?;
+
+    // Snapshots from the earlier of the old and new start are stale afterwards
+    let old_starts_on = sqlx::query_scalar::<_, NaiveDate>(
+        "SELECT starts_on FROM manual_costs WHERE id = $1 FOR UPDATE",
+    )
+    .bind(id)
+    .fetch_optional(&mut *tx)
+    .await?
+    .ok_or(AppError::NotFound)?;
+
+    let manual_cost = sqlx::query_as::<_, ManualCost>(
+        r#"
+        UPDATE manual_costs
+        SET
+            name = COALESCE($2, name),
+            category = COALESCE($3, category),
+            amount = COALESCE($4, amount),
+            starts_on = COALESCE($5, starts_on),
+            ends_on = COALESCE($6, ends_on),
+            updated_at = NOW()
+        WHERE id = $1
+        RETURNING id, merchant_id, name, category, cost_type, amount, currency, starts_on,
+                  ends_on, created_at, updated_at
+        "#,
+    )
+    .bind(id)
+    .bind(payload.name)
+    .bind(payload.category)
+    .bind(payload.amount)
+    .bind(payload.starts_on)
+    .bind(payload.ends_on)
+    .fetch_optional(&mut *tx)
+    .await
+    .map_err(|e| match e {
+        // ends_on before starts_on trips the table CHECK constraint
+        sqlx::Error::Database(db_err) if db_err.is_check_violation() => {
+            AppError::Validation("ends_on must not be before starts_on".to_string())
+        }
+        e => e.into(),
+    })?
+    .ok_or(AppError::NotFound)?;
+
+    validate_manual_cost(
+        &manual_cost.cost_type,
+        manual_cost.amount,
+        manual_cost.starts_on,
+        manual_cost.ends_on,
+    )?;
+    super::invalidate_profit_snapshots(
+        &mut *tx,
+        Some(manual_cost.merchant_id),
+        Some(old_starts_on.min(manual_cost.starts_on)),
+        None,
+    )
+    .await?;
+    tx.commit().await?;
+
+    Ok(Json(manual_cost))
+}
+
+async fn delete_cost(
+    Extension(ctx): Extension<ApiContext>,
+    Path(id): Path<Uuid>,
+) -> Result<StatusCode, AppError> {
+    eprintln!("Deleting manual cost: id={}", id);
+
+    let (merchant_id, starts_on, ends_on) =
+        sqlx::query_as::<_, (Uuid, NaiveDate, Option<NaiveDate>)>(
+            r#"
+            DELETE FROM manual_costs WHERE id = $1
+            RETURNING merchant_id, starts_on,
+                      CASE WHEN cost_type = 'one_off' THEN starts_on ELSE ends_on END
+            "#,
+        )
+        .bind(id)
+        .fetch_optional(&ctx.db)
+        .await?
+        .ok_or(AppError::NotFound)?;
+
+    super::invalidate_profit_snapshots(&ctx.db, Some(merchant_id), Some(starts_on), ends_on)
+        .await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
@@ -0,0 +1,201 @@
+use crate::http::{types::*, ApiContext, AppError, AppResult};
+use axum::{
+    extract::{Path, Query},
+    http::StatusCode,
+    routing::{get, put},
+    Extension, Json, Router,
+};
+use chrono::{DateTime, Utc};
+use rust_decimal::Decimal;
+use uuid::Uuid;
+
+/// Sum what payment gateways charged for the merchant's orders in a window
+/// (can be used by other modules).
+///
+/// Per-order fees come from the `order_payment_fees` view: each successful sale
+/// or capture pays its gateway's percentage plus fixed fee.
+pub async fn get_total_payment_fees(
+    db: &sqlx::PgPool,
+    merchant_id: Uuid,
+    start_date: Option<DateTime<Utc>>,
+    end_date: Option<DateTime<Utc>>,
+) -> Result<Decimal, sqlx::Error> {
+    sqlx::query_scalar(
+        r#"
+        SELECT COALESCE(SUM(payment_fees), 0) FROM order_payment_fees
+        WHERE merchant_id = $1
+            AND ($2::timestamptz IS NULL OR processed_at >= $2)
+            AND ($3::timestamptz IS NULL OR processed_at <= $3)
+        "#,
+    )
+    .bind(merchant_id)
+    .bind(start_date)
+    .bind(end_date)
+    .fetch_one(db)
+    .await
+}
+
+fn validate_fees(percentage: Option<Decimal>, fixed_fee: Option<Decimal>) -> Result<(), AppError> {
+    if percentage.is_some_and(|p| p.is_sign_negative() || p > Decimal::ONE_HUNDRED) {
+        return Err(AppError::Validation(
+            "Percentage must be between 0 and 100".to_string(),
+        ));
+    }
+    if fixed_fee.is_some_and(|fee| fee.is_sign_negative()) {
+        return Err(AppError::Validation(
+            "Fixed fee must not be negative".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+/// Create or replace the fee schedule of a gateway (can be used by HTTP
+/// handlers and tests).
+///
+/// Schedules are keyed by merchant and lowercase gateway name, as Shopify
+/// reports it on transactions (e.g. `shopify_payments`, `paypal`, `stripe`).
+pub async fn upsert_payment_fee_schedule(
+    db: &sqlx::PgPool,
+    payload: UpsertPaymentFeeScheduleRequest,
+) -> Result<PaymentFeeSchedule, AppError> {
+    validate_fees(payload.percentage, payload.fixed_fee)?;
+    let gateway = payload.gateway.trim().to_lowercase();
+    if gateway.is_empty() {
+        return Err(AppError::Validation("Gateway is required".to_string()));
+    }
+
+    let schedule = sqlx::query_as::<_, PaymentFeeSchedule>(
+        r#"
+        INSERT INTO payment_fee_schedules (merchant_id, gateway, percentage, fixed_fee, currency)
+        VALUES ($1, $2, $3, $4, $5)
+        ON CONFLICT (merchant_id, gateway) DO UPDATE SET
+            percentage = EXCLUDED.percentage,
+            fixed_fee = EXCLUDED.fixed_fee,
+            currency = EXCLUDED.currency,
+            updated_at = NOW()
+        RETURNING id, merchant_id, gateway, percentage, fixed_fee, currency, created_at, updated_at
+        "#,
+    )
+    .bind(payload.merchant_id)
+    .bind(gateway)
+    .bind(payload.percentage.unwrap_or_default())
+    .bind(payload.fixed_fee.unwrap_or_default())
+    .bind(payload.currency)
+    .fetch_one(db)
+    .await?;
+
+    // Schedules apply to orders of any date
+    super::invalidate_profit_snapshots(db, Some(schedule.merchant_id), None, None).await?;
+
+    Ok(schedule)
+}
+
+pub fn payment_fee_router() -> Router {
+    Router::new()
+        .route(
+            "/payment-fees/schedules",
+            get(list_schedules).post(upsert_schedule),
+        )
+        .route(
+            "/payment-fees/schedules/:id",
+            put(update_schedule).delete(delete_schedule),
+        )
+}
+
+async fn list_schedules(
+    Extension(ctx): Extension<ApiContext>,
+    Query(params): Query<ListPaymentFeeSchedulesParams>,
+) -> AppResult<Vec<PaymentFeeSchedule>> {
+    eprintln!(
+        "Listing payment fee schedules: merchant_id={}",
+        params.merchant_id
+    );
+
+    let schedules = sqlx::query_as::<_, PaymentFeeSchedule>(
+        r#"
+        SELECT id, merchant_id, gateway, percentage, fixed_fee, currency, created_at, updated_at
+        FROM payment_fee_schedules
+        WHERE merchant_id = $1
+        ORDER BY gateway
+        "#,
+    )
+    .bind(params.merchant_id)
+    .fetch_all(&ctx.db)
+    .await?;
+
+    Ok(Json(schedules))
+}
+
+async fn upsert_schedule(
+    Extension(ctx): Extension<ApiContext>,
+    Json(payload): Json<UpsertPaymentFeeScheduleRequest>,
+) -> AppResult<PaymentFeeSchedule> {
+    eprintln!(
+        "Upserting payment fee schedule: merchant_id={}, gateway={}",
+        payload.merchant_id, payload.gateway
+    );
+
+    let schedule = upsert_payment_fee_schedule(&ctx.db, payload).await?;
+
+    eprintln!(
+        "Payment fee schedule upserted successfully: id={}",
+        schedule.id
+    );
+    Ok(Json(schedule))
+}
+
+async fn update_schedule(
+    Extension(ctx): Extension<ApiContext>,
+    Path(id): Path<Uuid>,
+    Json(payload): Json<UpdatePaymentFeeScheduleRequest>,
+) -> AppResult<PaymentFeeSchedule> {
+    eprintln!(
+        "Updating payment fee schedule: id={}, percentage={:?}, fixed_fee={:?}",
+        id, payload.percentage, payload.fixed_fee
+    );
+
+    validate_fees(payload.percentage, payload.fixed_fee)?;
+
+    let schedule = sqlx::query_as::<_, PaymentFeeSchedule>(
+        r#"
+        UPDATE payment_fee_schedules
+        SET
+            percentage = COALESCE($2, percentage),
+            fixed_fee = COALESCE($3, fixed_fee),
+            currency = COALESCE($4, currency),
+            updated_at = NOW()
+        WHERE id = $1
+        RETURNING id, merchant_id, gateway, percentage, fixed_fee, currency, created_at, updated_at
+        "#,
+    )
+    .bind(id)
+    .bind(payload.percentage)
+    .bind(payload.fixed_fee)
+    .bind(payload.currency)
+    .fetch_optional(&ctx.db)
+    .await?
+    .ok_or(AppError::NotFound)?;
+
+    super::invalidate_profit_snapshots(&ctx.db, Some(schedule.merchant_id), None, None).await?;
+
+    Ok(Json(schedule))
+}
+
+async fn delete_schedule(
+    Extension(ctx): Extension<ApiContext>,
+    Path(id): Path<Uuid>,
+) -> Result<StatusCode, AppError> {
+    eprintln!("Deleting payment fee schedule: id={}", id);
+
+    let merchant_id = sqlx::query_scalar::<_, Uuid>(
+        "DELETE FROM payment_fee_schedules WHERE id = $1 RETURNING merchant_id",
+    )
+    .bind(id)
+    .fetch_optional(&ctx.db)
+    .await?
+    .ok_or(AppError::NotFound)?;
+
+    super::invalidate_profit_snapshots(&ctx.db, Some(merchant_id), None, None).await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
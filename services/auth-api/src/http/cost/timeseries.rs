@@ -0,0 +1,186 @@
+use crate::http::{types::*, ApiContext, AppError, AppResult};
+use axum::{extract::Query, routing::get, Extension, Json, Router};
+use chrono::{DateTime, NaiveDate, Utc};
+use uuid::Uuid;
+
+use super::fx;
+
+/// Bucket sizes accepted by the timeseries endpoint (Postgres `date_trunc` units)
+const GRANULARITIES: &[&str] = &["day", "week", "month"];
+
+/// A reporting window expressed in the merchant's timezone
+#[derive(sqlx::FromRow)]
+struct LocalWindow {
+    timezone: String,
+    window_start: DateTime<Utc>,
+    window_end: DateTime<Utc>,
+}
+
+/// Profit per day, week or month between two dates (can be used by HTTP
+/// handlers and tests).
+///
+/// Dates and bucket boundaries are local to the merchant's `timezone` (UTC when
+/// unset): orders and refunds fall into the bucket of their local processing
+/// day, while ad spend and manual costs, which are already per calendar day,
+/// fall into the bucket of that day. Weeks start on Monday. The first and last
+/// buckets are clipped to the requested dates.
+///
+/// Days are read from the cost engine's `profit_snapshots`; only days without a
+/// snapshot in the current reporting currency are computed live. Writes that
+/// change the costs of past days drop their snapshots (see
+/// `invalidate_profit_snapshots`), so those days are computed live as well.
+pub async fn get_profit_timeseries(
+    db: &sqlx::PgPool,
+    merchant_id: Uuid,
+    start: NaiveDate,
+    end: NaiveDate,
+    granularity: &str,
+) -> Result<ProfitTimeseriesResponse, AppError> {
+    if !GRANULARITIES.contains(&granularity) {
+        return Err(AppError::Validation(format!(
+            "granularity must be one of: {}",
+            GRANULARITIES.join(", ")
+        )));
+    }
+    if start > end {
+        return Err(AppError::Validation(
+            "start must not be after end".to_string(),
+        ));
+    }
+
+    let window = sqlx::query_as::<_, LocalWindow>(
+        r#"
+        SELECT
+            timezone,
+            $2::date::timestamp AT TIME ZONE timezone AS window_start,
+            ($3::date + 1)::timestamp AT TIME ZONE timezone - INTERVAL '1 microsecond' AS window_end
+        FROM merchant_timezone
+        WHERE merchant_id = $1
+        "#,
+    )
+    .bind(merchant_id)
+    .bind(start)
+    .bind(end)
+    .fetch_optional(db)
+    .await?
+    .ok_or(AppError::NotFound)?;
+
+    // Missing days are computed by merchant_daily_profit; nothing is written back
+    let buckets = sqlx::query_as::<_, ProfitBucket>(
+        r#"
+        WITH snapshot_days AS (
+            SELECT
+                ps.snapshot_date AS day,
+                ps.shopify_revenue,
+                ps.refunds,
+                ps.shopify_product_cost,
+                ps.returned_product_cost,
+                ps.ad_cost,
+                ps.courier_cost,
+                ps.payment_fees,
+                ps.manual_cost,
+                ps.profit
+            FROM profit_snapshots ps
+            JOIN merchant_currency mc ON mc.merchant_id = ps.merchant_id
+            WHERE ps.merchant_id = $1
+                AND ps.snapshot_date >= $2 AND ps.snapshot_date <= $3
+                AND ps.reporting_currency IS NOT DISTINCT FROM mc.reporting_currency
+        ),
+        missing AS (
+            SELECT MIN(d::date) AS first_day, MAX(d::date) AS last_day
+            FROM generate_series($2::date::timestamp, $3::date::timestamp, INTERVAL '1 day') AS d
+            WHERE NOT EXISTS (SELECT 1 FROM snapshot_days s WHERE s.day = d::date)
+        ),
+        days AS (
+            SELECT * FROM snapshot_days
+            UNION ALL
+            SELECT live.*
+            FROM missing m
+            CROSS JOIN LATERAL merchant_daily_profit($1, m.first_day, m.last_day) live
+            WHERE m.first_day IS NOT NULL
+                AND NOT EXISTS (SELECT 1 FROM snapshot_days s WHERE s.day = live.day)
+        ),
+        buckets AS (
+            SELECT
+                b::date AS bucket,
+                GREATEST(b::date, $2::date) AS period_start,
+                LEAST((b + ('1 ' || $4)::interval)::date - 1, $3::date) AS period_end
+            FROM generate_series(
+                date_trunc($4, $2::date::timestamp),
+                $3::date::timestamp,
+                ('1 ' || $4)::interval
+            ) AS b
+        )
+        SELECT
+            b.period_start,
+            b.period_end,
+            COALESCE(SUM(d.shopify_revenue), 0) AS shopify_revenue,
+            COALESCE(SUM(d.refunds), 0) AS refunds,
+            COALESCE(SUM(d.shopify_product_cost), 0) AS shopify_product_cost,
+            COALESCE(SUM(d.returned_product_cost), 0) AS returned_product_cost,
+            COALESCE(SUM(d.ad_cost), 0) AS ad_cost,
+            COALESCE(SUM(d.courier_cost), 0) AS courier_cost,
+            COALESCE(SUM(d.payment_fees), 0) AS payment_fees,
+            ROUND(COALESCE(SUM(d.manual_cost), 0), 4) AS manual_cost,
+            COALESCE(SUM(d.profit), 0) AS profit
+        FROM buckets b
+        LEFT JOIN days d
+            ON date_trunc($4, d.day::timestamp)::date = b.bucket
+        GROUP BY b.bucket, b.period_start, b.period_end
+        ORDER BY b.period_start
+        "#,
+    )
+    .bind(merchant_id)
+    .bind(start)
+    .bind(end)
+    .bind(granularity)
+    .fetch_all(db)
+    .await?;
+
+    let warnings = fx::get_currency_warnings(
+        db,
+        merchant_id,
+        Some(window.window_start),
+        Some(window.window_end),
+    )
+    .await?;
+
+    Ok(ProfitTimeseriesResponse {
+        merchant_id,
+        granularity: granularity.to_string(),
+        timezone: window.timezone,
+        buckets,
+        warnings,
+    })
+}
+
+pub fn timeseries_router() -> Router {
+    Router::new().route("/profit/timeseries", get(profit_timeseries))
+}
+
+async fn profit_timeseries(
+    Extension(ctx): Extension<ApiContext>,
+    Query(params): Query<ProfitTimeseriesParams>,
+) -> AppResult<ProfitTimeseriesResponse> {
+    eprintln!(
+        "Calculating profit timeseries: merchant_id={}, start={}, end={}, granularity={:?}",
+        params.merchant_id, params.start, params.end, params.granularity
+    );
+
+    let granularity = params.granularity.as_deref().unwrap_or("day");
+    let timeseries = get_profit_timeseries(
+        &ctx.db,
+        params.merchant_id,
+        params.start,
+        params.end,
+        granularity,
+    )
+    .await?;
+
+    eprintln!(
+        "Profit timeseries calculated: {} buckets in {}",
+        timeseries.buckets.len(),
+        timeseries.timezone
+    );
+    Ok(Json(timeseries))
+}
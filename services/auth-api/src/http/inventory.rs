@@ -5,10 +5,55 @@ use axum::{
     routing::get,
     Extension, Json, Router,
 };
+use chrono::Utc;
+
+/// Record a cost for an inventory item effective from a point in time
+/// (can be used by HTTP handlers and tests).
+///
+/// Recording a cost at an `effective_at` that already exists replaces it.
+pub async fn record_inventory_cost(
+    db: &sqlx::PgPool,
+    payload: CreateInventoryCostRequest,
+) -> Result<InventoryCost, AppError> {
+    if payload.cost.is_sign_negative() {
+        return Err(AppError::Validation("Cost must not be negative".to_string()));
+    }
+
+    let source = payload.source.unwrap_or_else(|| "manual".to_string());
+    if source != "shopify" && source != "manual" {
+        return Err(AppError::Validation(
+            "Source must be one of: shopify, manual".to_string(),
+        ));
+    }
+
+    let cost = sqlx::query_as::<_, InventoryCost>(
+        r#"
+        INSERT INTO inventory_cost_history (
+            merchant_id, shopify_inventory_item_id, cost, currency, effective_at, source
+        )
+        VALUES ($1, $2, $3, $4, $5, $6)
+        ON CONFLICT (merchant_id, shopify_inventory_item_id, effective_at)
+        DO UPDATE SET cost = EXCLUDED.cost, currency = EXCLUDED.currency, source = EXCLUDED.source
+        RETURNING id, merchant_id, shopify_inventory_item_id, cost, currency, effective_at,
+                  source, created_at
+        "#,
+    )
+    .bind(payload.merchant_id)
+    .bind(payload.shopify_inventory_item_id)
+    .bind(payload.cost)
+    .bind(payload.currency)
+    .bind(payload.effective_at.unwrap_or_else(Utc::now))
+    .bind(source)
+    .fetch_one(db)
+    .await?;
+
+    Ok(cost)
+}
 
 pub fn inventory_router() -> Router {
     Router::new()
         .route("/inventory", get(list_items).post(create_item))
+        .route("/inventory/costs", get(list_costs).post(create_cost))
         .route(
             "/inventory/:id",
             get(get_item).put(update_item).delete(delete_item),
@@ -203,3 +248,79 @@ async fn delete_item(
     eprintln!("Inventory item deleted successfully: id={}", id);
     Ok(StatusCode::NO_CONTENT)
 }
+
+async fn list_costs(
+    Extension(ctx): Extension<ApiContext>,
+    Query(params): Query<ListInventoryCostsParams>,
+) -> AppResult<InventoryCostListResponse> {
+    eprintln!(
+        "Listing inventory costs: merchant_id={}, shopify_inventory_item_id={:?}",
+        params.merchant_id, params.shopify_inventory_item_id
+    );
+
+    let limit = params.limit.unwrap_or(50).min(100);
+    let offset = params.offset.unwrap_or(0);
+
+    // Get total count
+    let total: i64 = sqlx::query_scalar::<_, Option<i64>>(
+        r#"
+        SELECT COUNT(*) as count
+        FROM inventory_cost_history
+        WHERE merchant_id = $1
+            AND ($2::bigint IS NULL OR shopify_inventory_item_id = $2)
+        "#,
+    )
+    .bind(params.merchant_id)
+    .bind(params.shopify_inventory_item_id)
+    .fetch_one(&ctx.db)
+    .await?
+    .unwrap_or(0);
+
+    // Get cost history, newest first per inventory item
+    let costs = sqlx::query_as::<_, InventoryCost>(
+        r#"
+        SELECT
+            id,
+            merchant_id,
+            shopify_inventory_item_id,
+            cost,
+            currency,
+            effective_at,
+            source,
+            created_at
+        FROM inventory_cost_history
+        WHERE merchant_id = $1
+            AND ($2::bigint IS NULL OR shopify_inventory_item_id = $2)
+        ORDER BY shopify_inventory_item_id, effective_at DESC
+        LIMIT $3 OFFSET $4
+        "#,
+    )
+    .bind(params.merchant_id)
+    .bind(params.shopify_inventory_item_id)
+    .bind(limit)
+    .bind(offset)
+    .fetch_all(&ctx.db)
+    .await?;
+
+    Ok(Json(InventoryCostListResponse {
+        costs,
+        total,
+        limit,
+        offset,
+    }))
+}
+
+async fn create_cost(
+    Extension(ctx): Extension<ApiContext>,
+    Json(payload): Json<CreateInventoryCostRequest>,
+) -> AppResult<InventoryCost> {
+    eprintln!(
+        "Recording inventory cost: merchant_id={}, shopify_inventory_item_id={}, cost={}",
+        payload.merchant_id, payload.shopify_inventory_item_id, payload.cost
+    );
+
+    let cost = record_inventory_cost(&ctx.db, payload).await?;
+
+    eprintln!("Inventory cost recorded successfully: id={}", cost.id);
+    Ok(Json(cost))
+}
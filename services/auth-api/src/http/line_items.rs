@@ -0,0 +1,195 @@
+use crate::http::{types::*, ApiContext, AppError, AppResult};
+use axum::{extract::Query, routing::get, Extension, Json, Router};
+
+/// Upsert the line items of an order identified by its Shopify ID
+/// (can be used by HTTP handlers and tests).
+///
+/// Line items are keyed by `shopify_line_item_id`, so re-syncing an order
+/// refreshes its existing rows instead of duplicating them. The payload is the
+/// order's full set of line items: rows of line items removed by an order edit
+/// are deleted.
+pub async fn upsert_order_line_items(
+    db: &sqlx::PgPool,
+    payload: UpsertOrderLineItemsRequest,
+) -> Result<Vec<OrderLineItem>, AppError> {
+    let order_id = sqlx::query_scalar::<_, i64>(
+        r#"
+        SELECT id FROM orders
+        WHERE merchant_id = $1 AND shopify_order_id = $2
+        "#,
+    )
+    .bind(payload.merchant_id)
+    .bind(payload.shopify_order_id)
+    .fetch_optional(db)
+    .await?
+    .ok_or(AppError::NotFound)?;
+
+    let mut tx = db.begin().await?;
+    let mut line_items = Vec::with_capacity(payload.line_items.len());
+
+    for item in payload.line_items {
+        if item.quantity < 0 {
+            return Err(AppError::Validation(format!(
+                "Line item {} has a negative quantity",
+                item.shopify_line_item_id
+            )));
+        }
+
+        let line_item = sqlx::query_as::<_, OrderLineItem>(
+            r#"
+            INSERT INTO order_line_items (
+                merchant_id, order_id, shopify_line_item_id, shopify_product_id,
+                shopify_variant_id, sku, title, quantity, price, total_discount
+            )
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
+            ON CONFLICT (merchant_id, shopify_line_item_id) DO UPDATE SET
+                order_id = EXCLUDED.order_id,
+                shopify_product_id = EXCLUDED.shopify_product_id,
+                shopify_variant_id = EXCLUDED.shopify_variant_id,
+                sku = EXCLUDED.sku,
+                title = EXCLUDED.title,
+                quantity = EXCLUDED.quantity,
+                price = EXCLUDED.price,
+                total_discount = EXCLUDED.total_discount,
+                updated_at = NOW()
+            RETURNING id, merchant_id, order_id, shopify_line_item_id, shopify_product_id,
+                      shopify_variant_id, sku, title, quantity, price, total_discount,
+                      created_at, updated_at
+            "#,
+        )
+        .bind(payload.merchant_id)
+        .bind(order_id)
+        .bind(item.shopify_line_item_id)
+        .bind(item.shopify_product_id)
+        .bind(item.shopify_variant_id)
+        .bind(item.sku)
+        .bind(item.title)
+        .bind(item.quantity)
+        .bind(item.price)
+        .bind(item.total_discount.unwrap_or_default())
+        .fetch_one(&mut *tx)
+        .await?;
+
+        line_items.push(line_item);
+    }
+
+    let shopify_line_item_ids: Vec<i64> = line_items
+        .iter()
+        .map(|item| item.shopify_line_item_id)
+        .collect();
+    sqlx::query(
+        r#"
+        DELETE FROM order_line_items
+        WHERE order_id = $1 AND shopify_line_item_id <> ALL($2)
+        "#,
+    )
+    .bind(order_id)
+    .bind(&shopify_line_item_ids)
+    .execute(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+
+    Ok(line_items)
+}
+
+pub fn line_items_router() -> Router {
+    Router::new().route(
+        "/order-line-items",
+        get(list_line_items).post(upsert_line_items),
+    )
+}
+
+async fn list_line_items(
+    Extension(ctx): Extension<ApiContext>,
+    Query(params): Query<ListOrderLineItemsParams>,
+) -> AppResult<OrderLineItemListResponse> {
+    eprintln!(
+        "Listing order line items: merchant_id={}, order_id={:?}, shopify_order_id={:?}",
+        params.merchant_id, params.order_id, params.shopify_order_id
+    );
+
+    let limit = params.limit.unwrap_or(50).min(100);
+    let offset = params.offset.unwrap_or(0);
+
+    // Get total count
+    let total: i64 = sqlx::query_scalar::<_, Option<i64>>(
+        r#"
+        SELECT COUNT(*) as count
+        FROM order_line_items li
+        JOIN orders o ON o.id = li.order_id
+        WHERE li.merchant_id = $1
+            AND ($2::bigint IS NULL OR li.order_id = $2)
+            AND ($3::bigint IS NULL OR o.shopify_order_id = $3)
+        "#,
+    )
+    .bind(params.merchant_id)
+    .bind(params.order_id)
+    .bind(params.shopify_order_id)
+    .fetch_one(&ctx.db)
+    .await?
+    .unwrap_or(0);
+
+    // Get line items
+    let line_items = sqlx::query_as::<_, OrderLineItem>(
+        r#"
+        SELECT
+            li.id,
+            li.merchant_id,
+            li.order_id,
+            li.shopify_line_item_id,
+            li.shopify_product_id,
+            li.shopify_variant_id,
+            li.sku,
+            li.title,
+            li.quantity,
+            li.price,
+            li.total_discount,
+            li.created_at,
+            li.updated_at
+        FROM order_line_items li
+        JOIN orders o ON o.id = li.order_id
+        WHERE li.merchant_id = $1
+            AND ($2::bigint IS NULL OR li.order_id = $2)
+            AND ($3::bigint IS NULL OR o.shopify_order_id = $3)
+        ORDER BY li.order_id DESC, li.id
+        LIMIT $4 OFFSET $5
+        "#,
+    )
+    .bind(params.merchant_id)
+    .bind(params.order_id)
+    .bind(params.shopify_order_id)
+    .bind(limit)
+    .bind(offset)
+    .fetch_all(&ctx.db)
+    .await?;
+
+    eprintln!("Found {} line items (total: {})", line_items.len(), total);
+
+    Ok(Json(OrderLineItemListResponse {
+        line_items,
+        total,
+        limit,
+        offset,
+    }))
+}
+
+async fn upsert_line_items(
+    Extension(ctx): Extension<ApiContext>,
+    Json(payload): Json<UpsertOrderLineItemsRequest>,
+) -> AppResult<Vec<OrderLineItem>> {
+    eprintln!(
+        "Upserting order line items: merchant_id={}, shopify_order_id={}, count={}",
+        payload.merchant_id,
+        payload.shopify_order_id,
+        payload.line_items.len()
+    );
+
+    let line_items = upsert_order_line_items(&ctx.db, payload).await?;
+
+    eprintln!(
+        "Order line items upserted successfully: count={}",
+        line_items.len()
+    );
+    Ok(Json(line_items))
+}
@@ -5,6 +5,7 @@ use axum::{
     routing::{delete, get, post},
     Extension, Json, Router,
 };
+use std::str::FromStr;
 use uuid::Uuid;
 
 /// Create a merchant (can be used by HTTP handlers and tests)
@@ -85,10 +86,201 @@ pub async fn delete_merchant(db: &sqlx::PgPool, id: Uuid) -> Result<(), AppError
     Ok(())
 }
 
+/// Mark a merchant's active Shopify installs as uninstalled (can be used by
+/// HTTP handlers and tests)
+pub async fn uninstall_merchant(db: &sqlx::PgPool, id: Uuid) -> Result<(), AppError> {
+    get_merchant(db, id).await?;
+
+    sqlx::query(
+        r#"
+        UPDATE shopify_installs
+        SET status = 'uninstalled', uninstalled_at = NOW(), updated_at = NOW()
+        WHERE merchant_id = $1 AND status = 'active'
+        "#,
+    )
+    .bind(id)
+    .execute(db)
+    .await?;
+
+    Ok(())
+}
+
+/// Revenue bases a merchant can choose from
+const REVENUE_BASES: &[&str] = &["subtotal", "total"];
+
+/// Multi-currency behaviours a merchant can choose from
+const MULTI_CURRENCY_MODES: &[&str] = &["warn", "convert"];
+
+/// Ad spend attribution models a merchant can choose from
+pub(crate) const ATTRIBUTION_MODELS: &[&str] = &["even", "revenue", "utm"];
+
+/// Shopify order financial statuses
+const FINANCIAL_STATUSES: &[&str] = &[
+    "pending",
+    "authorized",
+    "partially_paid",
+    "paid",
+    "partially_refunded",
+    "refunded",
+    "voided",
+    "expired",
+];
+
+/// Get a merchant's settings, creating the default row on first access
+/// (can be used by HTTP handlers and tests)
+pub async fn get_app_settings(db: &sqlx::PgPool, merchant_id: Uuid) -> Result<AppSettings, AppError> {
+    get_merchant(db, merchant_id).await?;
+
+    // Separate statements, so the SELECT also sees a row a concurrent first access inserted
+    sqlx::query(
+        r#"
+        INSERT INTO app_settings (merchant_id)
+        VALUES ($1)
+        ON CONFLICT (merchant_id) DO NOTHING
+        "#,
+    )
+    .bind(merchant_id)
+    .execute(db)
+    .await?;
+
+    let settings = sqlx::query_as::<_, AppSettings>(
+        r#"
+        SELECT id, merchant_id, revenue_basis, include_taxes, include_shipping,
+               default_currency, multi_currency_mode, sync_lookback_days,
+               auto_refresh_cron, included_financial_statuses,
+               include_cancelled_orders, include_test_orders, attribution_model,
+               courier_carrier, created_at, updated_at
+        FROM app_settings
+        WHERE merchant_id = $1
+        "#,
+    )
+    .bind(merchant_id)
+    .fetch_one(db)
+    .await?;
+
+    Ok(settings)
+}
+
+/// Update a merchant's settings; omitted fields keep their current value and
+/// `null` clears default_currency, auto_refresh_cron and courier_carrier
+/// (can be used by HTTP handlers and tests)
+pub async fn update_app_settings(
+    db: &sqlx::PgPool,
+    merchant_id: Uuid,
+    payload: UpdateAppSettingsRequest,
+) -> Result<AppSettings, AppError> {
+    if let Some(revenue_basis) = &payload.revenue_basis {
+        if !REVENUE_BASES.contains(&revenue_basis.as_str()) {
+            return Err(AppError::Validation(format!(
+                "revenue_basis must be one of: {}",
+                REVENUE_BASES.join(", ")
+            )));
+        }
+    }
+    if let Some(mode) = &payload.multi_currency_mode {
+        if !MULTI_CURRENCY_MODES.contains(&mode.as_str()) {
+            return Err(AppError::Validation(format!(
+                "multi_currency_mode must be one of: {}",
+                MULTI_CURRENCY_MODES.join(", ")
+            )));
+        }
+    }
+    if let Some(model) = &payload.attribution_model {
+        if !ATTRIBUTION_MODELS.contains(&model.as_str()) {
+            return Err(AppError::Validation(format!(
+                "attribution_model must be one of: {}",
+                ATTRIBUTION_MODELS.join(", ")
+            )));
+        }
+    }
+    if payload.sync_lookback_days.is_some_and(|days| days <= 0) {
+        return Err(AppError::Validation("sync_lookback_days must be positive".to_string()));
+    }
+
+    // The shopify-consumer schedules syncs with tokio-cron-scheduler, which requires seconds
+    if let Some(Some(cron)) = &payload.auto_refresh_cron {
+        if let Err(e) = cron::Schedule::from_str(cron) {
+            return Err(AppError::Validation(format!(
+                "auto_refresh_cron must be a cron expression with seconds, e.g. \"0 0 */6 * * *\" ({})",
+                e
+            )));
+        }
+    }
+
+    if let Some(statuses) = &payload.included_financial_statuses {
+        if let Some(status) = statuses.iter().find(|s| !FINANCIAL_STATUSES.contains(&s.as_str())) {
+            return Err(AppError::Validation(format!(
+                "Unknown financial status: {} (expected one of: {})",
+                status,
+                FINANCIAL_STATUSES.join(", ")
+            )));
+        }
+    }
+
+    // Make sure the row exists so omitted fields keep their defaults
+    get_app_settings(db, merchant_id).await?;
+
+    // Optional settings sent as null are cleared rather than kept
+    let set_default_currency = payload.default_currency.is_some();
+    let set_auto_refresh_cron = payload.auto_refresh_cron.is_some();
+    let set_courier_carrier = payload.courier_carrier.is_some();
+
+    let settings = sqlx::query_as::<_, AppSettings>(
+        r#"
+        UPDATE app_settings
+        SET
+            revenue_basis = COALESCE($2, revenue_basis),
+            include_taxes = COALESCE($3, include_taxes),
+            include_shipping = COALESCE($4, include_shipping),
+            default_currency = CASE WHEN $14 THEN $5 ELSE default_currency END,
+            multi_currency_mode = COALESCE($6, multi_currency_mode),
+            sync_lookback_days = COALESCE($7, sync_lookback_days),
+            auto_refresh_cron = CASE WHEN $15 THEN $8 ELSE auto_refresh_cron END,
+            included_financial_statuses = COALESCE($9, included_financial_statuses),
+            include_cancelled_orders = COALESCE($10, include_cancelled_orders),
+            include_test_orders = COALESCE($11, include_test_orders),
+            attribution_model = COALESCE($12, attribution_model),
+            courier_carrier = CASE WHEN $16 THEN $13 ELSE courier_carrier END,
+            updated_at = NOW()
+        WHERE merchant_id = $1
+        RETURNING id, merchant_id, revenue_basis, include_taxes, include_shipping,
+                  default_currency, multi_currency_mode, sync_lookback_days,
+                  auto_refresh_cron, included_financial_statuses,
+                  include_cancelled_orders, include_test_orders, attribution_model,
+                  courier_carrier, created_at, updated_at
+        "#,
+    )
+    .bind(merchant_id)
+    .bind(payload.revenue_basis)
+    .bind(payload.include_taxes)
+    .bind(payload.include_shipping)
+    .bind(payload.default_currency.flatten())
+    .bind(payload.multi_currency_mode)
+    .bind(payload.sync_lookback_days)
+    .bind(payload.auto_refresh_cron.flatten())
+    .bind(payload.included_financial_statuses)
+    .bind(payload.include_cancelled_orders)
+    .bind(payload.include_test_orders)
+    .bind(payload.attribution_model)
+    .bind(payload.courier_carrier.flatten())
+    .bind(set_default_currency)
+    .bind(set_auto_refresh_cron)
+    .bind(set_courier_carrier)
+    .fetch_one(db)
+    .await?;
+
+    // Revenue, inclusion and currency settings apply to every day
+    super::cost::invalidate_profit_snapshots(db, Some(merchant_id), None, None).await?;
+
+    Ok(settings)
+}
+
 pub fn merchants_router() -> Router {
     Router::new()
         .route("/merchants", post(create_merchant_handler))
         .route("/merchants/:id", get(get_merchant_handler).delete(delete_merchant_handler))
+        .route("/merchants/:id/settings", get(get_settings_handler).put(update_settings_handler))
+        .route("/merchants/:id/uninstall", post(uninstall_merchant_handler))
 }
 
 async fn create_merchant_handler(
@@ -128,4 +320,110 @@ async fn delete_merchant_handler(
     Ok(StatusCode::NO_CONTENT)
 }
 
+async fn uninstall_merchant_handler(
+    Extension(ctx): Extension<ApiContext>,
+    Path(id): Path<Uuid>,
+) -> Result<StatusCode, AppError> {
+    eprintln!("Uninstalling merchant: id={}", id);
+
+    uninstall_merchant(&ctx.db, id).await?;
+
+    eprintln!("Merchant uninstalled successfully: id={}", id);
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn get_settings_handler(
+    Extension(ctx): Extension<ApiContext>,
+    Path(id): Path<Uuid>,
+) -> AppResult<AppSettings> {
+    eprintln!("Getting merchant settings: merchant_id={}", id);
+
+    let settings = get_app_settings(&ctx.db, id).await?;
+    Ok(Json(settings))
+}
+
+async fn update_settings_handler(
+    Extension(ctx): Extension<ApiContext>,
+    Path(id): Path<Uuid>,
+    Json(payload): Json<UpdateAppSettingsRequest>,
+) -> AppResult<AppSettings> {
+    eprintln!(
+        "Updating merchant settings: merchant_id={}, revenue_basis={:?}, include_taxes={:?}, include_shipping={:?}",
+        id, payload.revenue_basis, payload.include_taxes, payload.include_shipping
+    );
+
+    let settings = update_app_settings(&ctx.db, id, payload).await?;
+
+    eprintln!("Merchant settings updated successfully: merchant_id={}", id);
+    Ok(Json(settings))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http::test_utils::setup_test_db;
+
+    #[tokio::test]
+    async fn test_app_settings_defaults_and_clearing() {
+        let db = setup_test_db().await.expect(
+            "Failed to setup test database. Make sure PostgreSQL is running and accessible.",
+        );
+
+        let merchant_id = Uuid::new_v4();
+        create_merchant(
+            &db,
+            CreateMerchantRequest {
+                id: Some(merchant_id),
+                shop_domain: format!("test-merchant-{}.myshopify.com", merchant_id),
+                shop_name: Some("Test Merchant".to_string()),
+                shop_currency: None,
+                timezone: None,
+            },
+        )
+        .await
+        .expect("Failed to create test merchant");
+
+        // Concurrent first reads all see the one default row
+        let reads = (0..8).map(|_| get_app_settings(&db, merchant_id));
+        let settings = futures_util::future::try_join_all(reads).await.unwrap();
+        assert!(settings.iter().all(|s| s.id == settings[0].id));
 
+        let settings = update_app_settings(
+            &db,
+            merchant_id,
+            UpdateAppSettingsRequest {
+                default_currency: Some(Some("EUR".to_string())),
+                auto_refresh_cron: Some(Some("0 0 */6 * * *".to_string())),
+                courier_carrier: Some(Some("dhl".to_string())),
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+        assert_eq!(settings.default_currency.as_deref(), Some("EUR"));
+        assert_eq!(settings.courier_carrier.as_deref(), Some("dhl"));
+
+        // Cron expressions need the seconds field the scheduler expects
+        for cron in ["0 */6 * * *", "every hour"] {
+            let result = update_app_settings(
+                &db,
+                merchant_id,
+                UpdateAppSettingsRequest {
+                    auto_refresh_cron: Some(Some(cron.to_string())),
+                    ..Default::default()
+                },
+            )
+            .await;
+            assert!(matches!(result, Err(AppError::Validation(_))), "{}", cron);
+        }
+
+        // Omitted fields are kept and null clears them
+        let payload: UpdateAppSettingsRequest =
+            serde_json::from_str(r#"{"auto_refresh_cron": null, "courier_carrier": null}"#)
+                .unwrap();
+        let settings = update_app_settings(&db, merchant_id, payload).await.unwrap();
+        assert_eq!(settings.default_currency.as_deref(), Some("EUR"));
+        assert_eq!(settings.auto_refresh_cron, None);
+        assert_eq!(settings.courier_carrier, None);
+    }
+}
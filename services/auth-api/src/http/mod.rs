@@ -3,9 +3,12 @@ use std::sync::{Arc, Mutex};
 
 use anyhow::Context;
 use axum::{response::Redirect, routing::get, Extension, Router};
+use lib_shopify::kafka::{create_producer, publish, Encoding, Event, EventEnvelope};
+use rdkafka::producer::FutureProducer;
 /* use sqlx::prelude::FromRow; */
 use sqlx::PgPool;
 use tower_http::{cors::CorsLayer, services::ServeDir, trace::TraceLayer};
+use uuid::Uuid;
 
 use crate::auth::jkws::AuthService;
 use crate::Args;
@@ -13,11 +16,19 @@ use crate::Args;
 mod auth;
 mod cost;
 mod inventory;
+mod line_items;
 mod merchants;
 mod orders;
 mod products;
+mod refunds;
+mod shopify_installs;
+mod sync_state;
+#[cfg(test)]
+mod test_utils;
+mod transactions;
 mod types;
 mod users;
+mod variants;
 
 pub use types::*;
 
@@ -28,12 +39,33 @@ pub struct ApiContext {
     pub auth_service: Arc<AuthService>,
 
     pub cost_calculation_vals: Arc<Mutex<HashMap<String, f64>>>,
+
+    pub kafka_producer: Option<Arc<FutureProducer>>,
+}
+
+impl ApiContext {
+    /// Publish an event for a merchant when Kafka is configured. Failures are
+    /// only logged: the change it announces is already committed.
+    pub async fn publish_event<E: Event + std::fmt::Debug>(&self, merchant_id: Uuid, event: E) {
+        let Some(producer) = &self.kafka_producer else {
+            return;
+        };
+
+        let envelope = EventEnvelope::new(merchant_id, event);
+        if let Err(e) = publish(producer, &envelope, Encoding::Json).await {
+            eprintln!("✗ Error publishing {:?}: {}", envelope.payload, e);
+        }
+    }
 }
 
 pub async fn serve(config: Args, db: PgPool) -> anyhow::Result<()> {
     let auth_service = Arc::new(AuthService::from_config(&config)?);
 
     // Initialize auxiliary services here (email, etc.) when available
+    let kafka_producer = match &config.kafka_brokers {
+        Some(brokers) => Some(create_producer(brokers).context("Failed to create Kafka producer")?),
+        None => None,
+    };
 
     let app = api_router()
         .layer(Extension(ApiContext {
@@ -41,6 +73,7 @@ pub async fn serve(config: Args, db: PgPool) -> anyhow::Result<()> {
             db,
             auth_service: auth_service.clone(),
             cost_calculation_vals: Arc::new(Mutex::new(HashMap::new())),
+            kafka_producer,
         }))
         // Enable CORS for cross-origin requests (needed for Swagger UI)
         .layer(
@@ -86,6 +119,12 @@ fn api_router() -> Router {
                 .merge(orders::orders_router())
                 .merge(products::products_router())
                 .merge(users::users_router())
-                .merge(cost::cost_router()),
+                .merge(cost::cost_router())
+                .merge(line_items::line_items_router())
+                .merge(variants::variants_router())
+                .merge(refunds::refunds_router())
+                .merge(transactions::transactions_router())
+                .merge(sync_state::sync_state_router())
+                .merge(shopify_installs::shopify_installs_router()),
         )
 }
@@ -2,13 +2,23 @@ use crate::http::{types::*, ApiContext, AppError, AppResult};
 use axum::{
     extract::{Path, Query},
     http::StatusCode,
-    routing::get,
+    routing::{get, put},
     Extension, Json, Router,
 };
 use chrono::{DateTime, Utc};
 use rust_decimal::Decimal;
 use uuid::Uuid;
 
+/// Value of a query parameter on a landing site URL or path (empty values are ignored)
+fn landing_site_param(landing_site: &str, key: &str) -> Option<String> {
+    let (_, query) = landing_site.split_once('?')?;
+    let query = query.split('#').next().unwrap_or_default();
+
+    url::form_urlencoded::parse(query.as_bytes())
+        .find(|(k, v)| k == key && !v.is_empty())
+        .map(|(_, v)| v.into_owned())
+}
+
 /// Create an order (can be used by HTTP handlers and tests)
 pub async fn create_order(
     db: &sqlx::PgPool,
@@ -30,19 +40,30 @@ pub async fn create_order(
         return Err(AppError::Validation("Order already exists".to_string()));
     }
 
+    // UTM tags on the landing page drive UTM-based ad attribution
+    let utm_param = |key: &str| {
+        payload
+            .landing_site
+            .as_deref()
+            .and_then(|landing_site| landing_site_param(landing_site, key))
+    };
+
     // Insert order
     let order = sqlx::query_as::<_, Order>(
         r#"
         INSERT INTO orders (
             merchant_id, shopify_order_id, name, processed_at, currency,
             subtotal_price, total_price, total_discounts, 
-            total_shipping_price_set_amount, total_tax, financial_status
+            total_shipping_price_set_amount, total_tax, financial_status,
+            shipping_country_code, cancelled_at, test, landing_site, referring_site,
+            utm_source, utm_medium, utm_campaign
         )
-        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17, $18, $19)
         RETURNING id, merchant_id, shopify_order_id, name, processed_at, currency,
                   subtotal_price, total_price, total_discounts, 
                   total_shipping_price_set_amount, total_tax, financial_status,
-                  cancelled_at, created_at, updated_at
+                  cancelled_at, shipping_country_code, test, landing_site, referring_site,
+                  utm_source, utm_medium, utm_campaign, created_at, updated_at
         "#,
     )
     .bind(payload.merchant_id)
@@ -56,12 +77,270 @@ pub async fn create_order(
     .bind(payload.total_shipping_price_set_amount)
     .bind(payload.total_tax)
     .bind(payload.financial_status)
+    .bind(payload.shipping_country_code)
+    .bind(payload.cancelled_at)
+    .bind(payload.test.unwrap_or(false))
+    .bind(&payload.landing_site)
+    .bind(payload.referring_site)
+    .bind(utm_param("utm_source"))
+    .bind(utm_param("utm_medium"))
+    .bind(utm_param("utm_campaign"))
     .fetch_one(db)
     .await?;
 
     Ok(order)
 }
 
+/// Create or replace an order identified by its Shopify ID (can be used by
+/// HTTP handlers and tests).
+///
+/// Every field is overwritten, so re-syncing an order picks up its new
+/// financial status, cancellation or totals. Line items, refunds and
+/// transactions are upserted separately and keep pointing at the same row.
+///
+/// Transactions only change along with the financial status, so the result
+/// tells whether they are worth fetching from Shopify again.
+pub async fn upsert_order(
+    db: &sqlx::PgPool,
+    shopify_order_id: i64,
+    payload: UpsertOrderRequest,
+) -> Result<UpsertedOrder, AppError> {
+    // UTM tags on the landing page drive UTM-based ad attribution
+    let utm_param = |key: &str| {
+        payload
+            .landing_site
+            .as_deref()
+            .and_then(|landing_site| landing_site_param(landing_site, key))
+    };
+
+    let order = sqlx::query_as::<_, UpsertedOrder>(
+        r#"
+        WITH previous AS (
+            SELECT
+                o.financial_status,
+                EXISTS (SELECT 1 FROM order_transactions t WHERE t.order_id = o.id) AS has_transactions
+            FROM orders o
+            WHERE o.merchant_id = $1 AND o.shopify_order_id = $2
+        ),
+        upserted AS (
+            INSERT INTO orders (
+                merchant_id, shopify_order_id, name, processed_at, currency,
+                subtotal_price, total_price, total_discounts,
+                total_shipping_price_set_amount, total_tax, financial_status,
+                shipping_country_code, cancelled_at, test, landing_site, referring_site,
+                utm_source, utm_medium, utm_campaign
+            )
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17, $18, $19)
+            ON CONFLICT (merchant_id, shopify_order_id) DO UPDATE SET
+                name = EXCLUDED.name,
+                processed_at = EXCLUDED.processed_at,
+                currency = EXCLUDED.currency,
+                subtotal_price = EXCLUDED.subtotal_price,
+                total_price = EXCLUDED.total_price,
+                total_discounts = EXCLUDED.total_discounts,
+                total_shipping_price_set_amount = EXCLUDED.total_shipping_price_set_amount,
+                total_tax = EXCLUDED.total_tax,
+                financial_status = EXCLUDED.financial_status,
+                shipping_country_code = EXCLUDED.shipping_country_code,
+                cancelled_at = EXCLUDED.cancelled_at,
+                test = EXCLUDED.test,
+                landing_site = EXCLUDED.landing_site,
+                referring_site = EXCLUDED.referring_site,
+                utm_source = EXCLUDED.utm_source,
+                utm_medium = EXCLUDED.utm_medium,
+                utm_campaign = EXCLUDED.utm_campaign,
+                updated_at = NOW()
+            RETURNING id, merchant_id, shopify_order_id, name, processed_at, currency,
+                      subtotal_price, total_price, total_discounts,
+                      total_shipping_price_set_amount, total_tax, financial_status,
+                      cancelled_at, shipping_country_code, test, landing_site, referring_site,
+                      utm_source, utm_medium, utm_campaign, created_at, updated_at
+        )
+        SELECT
+            u.*,
+            p.financial_status IS DISTINCT FROM u.financial_status
+                OR NOT COALESCE(p.has_transactions, FALSE) AS transactions_outdated
+        FROM upserted u
+        LEFT JOIN previous p ON TRUE
+        "#,
+    )
+    .bind(payload.merchant_id)
+    .bind(shopify_order_id)
+    .bind(payload.name)
+    .bind(payload.processed_at)
+    .bind(payload.currency)
+    .bind(payload.subtotal_price)
+    .bind(payload.total_price)
+    .bind(payload.total_discounts)
+    .bind(payload.total_shipping_price_set_amount)
+    .bind(payload.total_tax)
+    .bind(payload.financial_status)
+    .bind(payload.shipping_country_code)
+    .bind(payload.cancelled_at)
+    .bind(payload.test.unwrap_or(false))
+    .bind(&payload.landing_site)
+    .bind(payload.referring_site)
+    .bind(utm_param("utm_source"))
+    .bind(utm_param("utm_medium"))
+    .bind(utm_param("utm_campaign"))
+    .fetch_one(db)
+    .await?;
+
+    Ok(order)
+}
+
+/// Profit breakdown of a single order (can be used by HTTP handlers and tests).
+///
+/// Uses the same components as `/calculate`, scoped to the order:
+/// - refunds and restocked returns of the order, whenever they were processed
+/// - the courier cost of its shipment (invoiced, else rate card estimate)
+/// - gateway fees on its payment transactions (see the order_payment_fees view)
+/// - the ad spend attributed to it (see the order_ad_costs view)
+/// - per-order and percent-of-revenue manual costs active on that day
+///   (one-off and monthly overheads are not attributable to single orders)
+///
+/// Warnings point at data that makes the breakdown incomplete.
+pub async fn get_order_profit(db: &sqlx::PgPool, id: i64) -> Result<OrderProfit, AppError> {
+    let mut order_profit = sqlx::query_as::<_, OrderProfit>(
+        r#"
+        WITH target AS (
+            SELECT o.*, (o.processed_at AT TIME ZONE 'UTC')::date AS processed_on
+            FROM orders o
+            WHERE o.id = $1
+        ),
+        revenue AS (
+            SELECT COALESCE(SUM(revenue), 0) AS amount FROM order_revenue WHERE order_id = $1
+        ),
+        manual AS (
+            SELECT COALESCE(SUM(
+                CASE mc.cost_type
+                    WHEN 'per_order' THEN to_reporting_currency(mc.merchant_id, mc.amount, mc.currency, t.processed_on)
+                    ELSE mc.amount / 100 * r.revenue
+                END
+            ), 0) AS amount
+            FROM manual_costs mc
+            JOIN target t
+                ON mc.merchant_id = t.merchant_id
+                AND t.processed_on >= mc.starts_on
+                AND (mc.ends_on IS NULL OR t.processed_on <= mc.ends_on)
+            JOIN order_revenue r ON r.order_id = t.id
+            WHERE mc.cost_type IN ('per_order', 'percent_of_revenue')
+        ),
+        components AS (
+            SELECT
+                t.id AS order_id,
+                t.merchant_id,
+                t.shopify_order_id,
+                t.name,
+                t.processed_at,
+                cur.reporting_currency,
+                EXISTS (SELECT 1 FROM included_orders io WHERE io.id = t.id) AS included,
+                rev.amount AS shopify_revenue,
+                (SELECT COALESCE(SUM(revenue), 0) FROM refund_revenue WHERE order_id = t.id) AS refunds,
+                (SELECT COALESCE(SUM(product_cost), 0) FROM order_product_costs WHERE order_id = t.id)
+                    AS shopify_product_cost,
+                (SELECT COALESCE(SUM(product_cost), 0) FROM refund_product_costs WHERE order_id = t.id)
+                    AS returned_product_cost,
+                (SELECT COALESCE(SUM(ad_cost), 0) FROM order_ad_costs WHERE order_id = t.id) AS ad_cost,
+                (SELECT COALESCE(SUM(courier_cost), 0) FROM order_courier_costs WHERE order_id = t.id)
+                    AS courier_cost,
+                (SELECT COALESCE(SUM(payment_fees), 0) FROM order_payment_fees WHERE order_id = t.id)
+                    AS payment_fees,
+                ROUND(man.amount, 4) AS manual_cost
+            FROM target t
+            JOIN merchant_currency cur ON cur.merchant_id = t.merchant_id
+            CROSS JOIN revenue rev
+            CROSS JOIN manual man
+        )
+        SELECT
+            *,
+            shopify_revenue - refunds - shopify_product_cost + returned_product_cost
+                - ad_cost - courier_cost - payment_fees - manual_cost AS profit
+        FROM components
+        "#,
+    )
+    .bind(id)
+    .fetch_optional(db)
+    .await?
+    .ok_or(AppError::NotFound)?;
+
+    order_profit.line_items = sqlx::query_as::<_, OrderLineItemProfit>(
+        r#"
+        SELECT
+            li.shopify_line_item_id,
+            li.shopify_variant_id,
+            li.sku,
+            li.title,
+            li.quantity,
+            to_reporting_currency(
+                o.merchant_id, li.price * li.quantity - li.total_discount, o.currency,
+                (o.processed_at AT TIME ZONE 'UTC')::date
+            ) AS revenue,
+            lic.unit_cost,
+            COALESCE(lic.quantity * lic.unit_cost, 0) AS cogs
+        FROM order_line_items li
+        JOIN orders o ON o.id = li.order_id
+        LEFT JOIN line_item_costs lic ON lic.order_line_item_id = li.id
+        WHERE li.order_id = $1
+        ORDER BY li.id
+        "#,
+    )
+    .bind(id)
+    .fetch_all(db)
+    .await?;
+
+    if !order_profit.included {
+        order_profit.warnings.push(
+            "Order is excluded by the merchant's order-inclusion policy, so it contributes nothing to profit"
+                .to_string(),
+        );
+    } else {
+        if order_profit.line_items.is_empty() {
+            order_profit
+                .warnings
+                .push("Order has no synced line items, so its COGS is 0".to_string());
+        }
+        for item in order_profit.line_items.iter().filter(|i| i.unit_cost.is_none()) {
+            order_profit.warnings.push(format!(
+                "No cost recorded for line item {} ({}) at the time of sale, so its COGS is 0",
+                item.shopify_line_item_id,
+                item.sku.as_deref().or(item.title.as_deref()).unwrap_or("untitled")
+            ));
+        }
+        if order_profit.courier_cost.is_zero() {
+            order_profit.warnings.push(
+                "No courier invoice line or rate card matches this shipment, so its courier cost is 0"
+                    .to_string(),
+            );
+        }
+
+        let unscheduled_gateways = sqlx::query_scalar::<_, String>(
+            r#"
+            SELECT DISTINCT COALESCE(t.gateway, 'unknown')
+            FROM order_transactions t
+            WHERE t.order_id = $1
+                AND t.kind IN ('sale', 'capture') AND t.status = 'success'
+                AND NOT EXISTS (
+                    SELECT 1 FROM payment_fee_schedules s
+                    WHERE s.merchant_id = t.merchant_id AND s.gateway = LOWER(t.gateway)
+                )
+            ORDER BY 1
+            "#,
+        )
+        .bind(id)
+        .fetch_all(db)
+        .await?;
+        for gateway in unscheduled_gateways {
+            order_profit.warnings.push(format!(
+                "No payment fee schedule for gateway {}, so its fees are 0",
+                gateway
+            ));
+        }
+    }
+
+    Ok(order_profit)
+}
+
 pub fn orders_router() -> Router {
     Router::new()
         .route("/orders", get(list_orders).post(create_order_handler))
@@ -69,6 +348,11 @@ pub fn orders_router() -> Router {
             "/orders/:id",
             get(get_order).put(update_order).delete(delete_order),
         )
+        .route("/orders/:id/profit", get(get_order_profit_handler))
+        .route(
+            "/orders/by-shopify-id/:shopify_order_id",
+            put(upsert_order_handler),
+        )
 }
 
 async fn list_orders(
@@ -113,6 +397,13 @@ async fn list_orders(
             total_tax,
             financial_status,
             cancelled_at,
+            shipping_country_code,
+            test,
+            landing_site,
+            referring_site,
+            utm_source,
+            utm_medium,
+            utm_campaign,
             created_at,
             updated_at
         FROM orders
@@ -158,6 +449,13 @@ async fn get_order(Extension(ctx): Extension<ApiContext>, Path(id): Path<i64>) -
             total_tax,
             financial_status,
             cancelled_at,
+            shipping_country_code,
+            test,
+            landing_site,
+            referring_site,
+            utm_source,
+            utm_medium,
+            utm_campaign,
             created_at,
             updated_at
         FROM orders
@@ -172,6 +470,23 @@ async fn get_order(Extension(ctx): Extension<ApiContext>, Path(id): Path<i64>) -
     Ok(Json(order))
 }
 
+async fn get_order_profit_handler(
+    Extension(ctx): Extension<ApiContext>,
+    Path(id): Path<i64>,
+) -> AppResult<OrderProfit> {
+    eprintln!("Calculating order profit: id={}", id);
+
+    let order_profit = get_order_profit(&ctx.db, id).await?;
+
+    eprintln!(
+        "Order profit calculated: id={}, profit={}, warnings={}",
+        id,
+        order_profit.profit,
+        order_profit.warnings.len()
+    );
+    Ok(Json(order_profit))
+}
+
 async fn create_order_handler(
     Extension(ctx): Extension<ApiContext>,
     Json(payload): Json<CreateOrderRequest>,
@@ -187,6 +502,25 @@ async fn create_order_handler(
     Ok(Json(order))
 }
 
+async fn upsert_order_handler(
+    Extension(ctx): Extension<ApiContext>,
+    Path(shopify_order_id): Path<i64>,
+    Json(payload): Json<UpsertOrderRequest>,
+) -> AppResult<UpsertedOrder> {
+    eprintln!(
+        "Upserting order: merchant_id={}, shopify_order_id={}, name={:?}",
+        payload.merchant_id, shopify_order_id, payload.name
+    );
+
+    let order = upsert_order(&ctx.db, shopify_order_id, payload).await?;
+
+    eprintln!(
+        "Order upserted successfully: id={}, transactions_outdated={}",
+        order.order.id, order.transactions_outdated
+    );
+    Ok(Json(order))
+}
+
 async fn update_order(
     Extension(ctx): Extension<ApiContext>,
     Path(id): Path<i64>,
@@ -209,7 +543,8 @@ async fn update_order(
         RETURNING id, merchant_id, shopify_order_id, name, processed_at, currency,
                   subtotal_price, total_price, total_discounts, 
                   total_shipping_price_set_amount, total_tax, financial_status,
-                  cancelled_at, created_at, updated_at
+                  cancelled_at, shipping_country_code, test, landing_site, referring_site,
+                  utm_source, utm_medium, utm_campaign, created_at, updated_at
         "#,
     )
     .bind(id)
@@ -247,3 +582,122 @@ async fn delete_order(
     eprintln!("Order deleted successfully: id={}", id);
     Ok(StatusCode::NO_CONTENT)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http::merchants::create_merchant;
+    use crate::http::test_utils::setup_test_db;
+    use crate::http::transactions::upsert_order_transactions;
+
+    #[tokio::test]
+    async fn test_upsert_order_refreshes_existing_row() {
+        let db = setup_test_db().await.expect(
+            "Failed to setup test database. Make sure PostgreSQL is running and accessible.",
+        );
+
+        let merchant_id = Uuid::new_v4();
+        let processed_at = DateTime::parse_from_rfc3339("2024-01-01T12:00:00Z")
+            .unwrap()
+            .with_timezone(&Utc);
+
+        create_merchant(
+            &db,
+            CreateMerchantRequest {
+                id: Some(merchant_id),
+                shop_domain: format!("test-merchant-{}.myshopify.com", merchant_id),
+                shop_name: Some("Test Merchant".to_string()),
+                shop_currency: None,
+                timezone: None,
+            },
+        )
+        .await
+        .expect("Failed to create merchant");
+
+        let created = create_order(
+            &db,
+            CreateOrderRequest {
+                merchant_id,
+                shopify_order_id: 5001,
+                name: Some("#5001".to_string()),
+                processed_at: Some(processed_at),
+                currency: Some("USD".to_string()),
+                subtotal_price: Some(Decimal::new(10000, 2)),
+                total_price: Some(Decimal::new(10000, 2)),
+                total_discounts: None,
+                total_shipping_price_set_amount: None,
+                total_tax: None,
+                financial_status: Some("paid".to_string()),
+                shipping_country_code: None,
+                cancelled_at: None,
+                test: None,
+                landing_site: None,
+                referring_site: None,
+            },
+        )
+        .await
+        .expect("Failed to create order");
+
+        // Re-syncing the order after a refund and cancellation updates the same row
+        let refunded = || UpsertOrderRequest {
+            merchant_id,
+            name: Some("#5001".to_string()),
+            processed_at: Some(processed_at),
+            currency: Some("USD".to_string()),
+            subtotal_price: Some(Decimal::new(10000, 2)),
+            total_price: Some(Decimal::new(10000, 2)),
+            total_discounts: None,
+            total_shipping_price_set_amount: None,
+            total_tax: None,
+            financial_status: Some("refunded".to_string()),
+            shipping_country_code: None,
+            cancelled_at: Some(processed_at),
+            test: None,
+            landing_site: Some("/?utm_campaign=spring".to_string()),
+            referring_site: None,
+        };
+        let updated = upsert_order(&db, 5001, refunded())
+            .await
+            .expect("Failed to upsert order");
+
+        assert_eq!(updated.order.id, created.id);
+        assert_eq!(updated.order.financial_status.as_deref(), Some("refunded"));
+        assert_eq!(updated.order.cancelled_at, Some(processed_at));
+        assert_eq!(updated.order.utm_campaign.as_deref(), Some("spring"));
+        assert!(updated.transactions_outdated);
+
+        // Once its transactions are stored, an unchanged status needs no refetch
+        upsert_order_transactions(
+            &db,
+            UpsertOrderTransactionsRequest {
+                merchant_id,
+                shopify_order_id: 5001,
+                transactions: vec![OrderTransactionInput {
+                    shopify_transaction_id: 9001,
+                    kind: "sale".to_string(),
+                    status: Some("success".to_string()),
+                    gateway: Some("shopify_payments".to_string()),
+                    amount: Decimal::new(10000, 2),
+                    currency: None,
+                    processed_at: Some(processed_at),
+                }],
+            },
+        )
+        .await
+        .expect("Failed to upsert transactions");
+
+        let resynced = upsert_order(&db, 5001, refunded())
+            .await
+            .expect("Failed to upsert order");
+        assert!(!resynced.transactions_outdated);
+
+        let count: i64 = sqlx::query_scalar(
+            "SELECT COUNT(*) FROM orders WHERE merchant_id = $1 AND shopify_order_id = 5001",
+        )
+        .bind(merchant_id)
+        .fetch_one(&db)
+        .await
+        .unwrap();
+        assert_eq!(count, 1);
+    }
+}
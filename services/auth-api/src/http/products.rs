@@ -2,17 +2,84 @@ use crate::http::{types::*, ApiContext, AppError, AppResult};
 use axum::{
     extract::{Path, Query},
     http::StatusCode,
-    routing::get,
+    routing::{get, put},
     Extension, Json, Router,
 };
 
+/// Ways the profitability report can be grouped
+const PROFITABILITY_GROUPINGS: &[&str] = &["product", "variant"];
+
+/// Create or replace a product identified by its Shopify ID (can be used by
+/// HTTP handlers and tests).
+///
+/// A soft-deleted product that shows up again in Shopify is restored.
+pub async fn upsert_product(
+    db: &sqlx::PgPool,
+    shopify_product_id: i64,
+    payload: UpsertProductRequest,
+) -> Result<Product, AppError> {
+    let product = sqlx::query_as::<_, Product>(
+        r#"
+        INSERT INTO products (merchant_id, shopify_product_id, title, product_type, status)
+        VALUES ($1, $2, $3, $4, $5)
+        ON CONFLICT (merchant_id, shopify_product_id) DO UPDATE SET
+            title = EXCLUDED.title,
+            product_type = EXCLUDED.product_type,
+            status = EXCLUDED.status,
+            deleted_at = NULL,
+            updated_at = NOW()
+        RETURNING id, merchant_id, shopify_product_id, title, product_type, status, created_at, updated_at, deleted_at
+        "#,
+    )
+    .bind(payload.merchant_id)
+    .bind(shopify_product_id)
+    .bind(payload.title)
+    .bind(payload.product_type)
+    .bind(payload.status)
+    .fetch_one(db)
+    .await?;
+
+    Ok(product)
+}
+
+/// Soft delete a product identified by its Shopify ID (can be used by HTTP
+/// handlers and tests)
+pub async fn delete_product_by_shopify_id(
+    db: &sqlx::PgPool,
+    merchant_id: uuid::Uuid,
+    shopify_product_id: i64,
+) -> Result<(), AppError> {
+    let result = sqlx::query(
+        r#"
+        UPDATE products
+        SET deleted_at = NOW(), updated_at = NOW()
+        WHERE merchant_id = $1 AND shopify_product_id = $2 AND deleted_at IS NULL
+        "#,
+    )
+    .bind(merchant_id)
+    .bind(shopify_product_id)
+    .execute(db)
+    .await?;
+
+    if result.rows_affected() == 0 {
+        return Err(AppError::NotFound);
+    }
+
+    Ok(())
+}
+
 pub fn products_router() -> Router {
     Router::new()
         .route("/products", get(list_products).post(create_product))
+        .route("/products/profitability", get(product_profitability))
         .route(
             "/products/:id",
             get(get_product).put(update_product).delete(delete_product),
         )
+        .route(
+            "/products/by-shopify-id/:shopify_product_id",
+            put(upsert_product_handler).delete(delete_product_by_shopify_id_handler),
+        )
 }
 
 async fn list_products(
@@ -83,6 +150,8 @@ async fn list_products(
                 barcode,
                 weight,
                 weight_unit,
+                price,
+                shopify_inventory_item_id,
                 created_at,
                 updated_at
             FROM variants
@@ -97,8 +166,8 @@ async fn list_products(
 
         let product_with_variants = ProductWithVariants {
             product: product.clone(),
+            variant_count: variants.len() as i64,
             variants,
-            variant_count: 0, // Will be set correctly when we have the actual count
         };
 
         products_with_variants.push(product_with_variants);
@@ -153,6 +222,8 @@ async fn get_product(
             barcode,
             weight,
             weight_unit,
+            price,
+            shopify_inventory_item_id,
             created_at,
             updated_at
         FROM variants
@@ -219,6 +290,38 @@ async fn create_product(
     Ok(Json(product))
 }
 
+async fn upsert_product_handler(
+    Extension(ctx): Extension<ApiContext>,
+    Path(shopify_product_id): Path<i64>,
+    Json(payload): Json<UpsertProductRequest>,
+) -> AppResult<Product> {
+    eprintln!(
+        "Upserting product: merchant_id={}, shopify_product_id={}, title={:?}",
+        payload.merchant_id, shopify_product_id, payload.title
+    );
+
+    let product = upsert_product(&ctx.db, shopify_product_id, payload).await?;
+
+    eprintln!("Product upserted successfully: id={}", product.id);
+    Ok(Json(product))
+}
+
+async fn delete_product_by_shopify_id_handler(
+    Extension(ctx): Extension<ApiContext>,
+    Path(shopify_product_id): Path<i64>,
+    Query(params): Query<DeleteProductByShopifyIdParams>,
+) -> Result<StatusCode, AppError> {
+    eprintln!(
+        "Deleting product: merchant_id={}, shopify_product_id={}",
+        params.merchant_id, shopify_product_id
+    );
+
+    delete_product_by_shopify_id(&ctx.db, params.merchant_id, shopify_product_id).await?;
+
+    eprintln!("Product deleted successfully: shopify_product_id={}", shopify_product_id);
+    Ok(StatusCode::NO_CONTENT)
+}
+
 async fn update_product(
     Extension(ctx): Extension<ApiContext>,
     Path(id): Path<uuid::Uuid>,
@@ -272,4 +375,227 @@ async fn delete_product(
     }
 
     Ok(StatusCode::NO_CONTENT)
-}
\ No newline at end of file
+}
+/// Profitability per product (or variant) over a window, ranked by contribution
+/// margin (can be used by HTTP handlers and tests).
+///
+/// - revenue is each line item's price × quantity less its discounts, in the
+///   reporting currency
+/// - COGS uses the cost in effect when the order was processed (see the
+///   line_item_costs view)
+/// - an order's courier cost and attributed ad spend (see the order_ad_costs
+///   view) are split across its line items by revenue (by quantity when the
+///   order has no revenue)
+///
+/// Only orders passing the merchant's inclusion policy count.
+pub async fn get_product_profitability(
+    db: &sqlx::PgPool,
+    params: &ProductProfitabilityParams,
+) -> Result<ProductProfitabilityResponse, AppError> {
+    let group_by = params.group_by.as_deref().unwrap_or("product");
+    if !PROFITABILITY_GROUPINGS.contains(&group_by) {
+        return Err(AppError::Validation(format!(
+            "group_by must be one of: {}",
+            PROFITABILITY_GROUPINGS.join(", ")
+        )));
+    }
+
+    let limit = params.limit.unwrap_or(50).min(100);
+    let offset = params.offset.unwrap_or(0);
+
+    // Get total count
+    let total: i64 = sqlx::query_scalar::<_, Option<i64>>(
+        r#"
+        SELECT COUNT(*) as count
+        FROM (
+            SELECT 1
+            FROM included_orders io
+            JOIN orders o ON o.id = io.id
+            JOIN order_line_items li ON li.order_id = o.id
+            LEFT JOIN variants v
+                ON v.merchant_id = li.merchant_id AND v.shopify_variant_id = li.shopify_variant_id
+            WHERE o.merchant_id = $1
+                AND ($2::timestamptz IS NULL OR o.processed_at >= $2)
+                AND ($3::timestamptz IS NULL OR o.processed_at <= $3)
+            GROUP BY
+                COALESCE(li.shopify_product_id, v.shopify_product_id),
+                CASE WHEN $4 = 'variant' THEN li.shopify_variant_id END
+        ) groups
+        "#,
+    )
+    .bind(params.merchant_id)
+    .bind(params.start_date)
+    .bind(params.end_date)
+    .bind(group_by)
+    .fetch_one(db)
+    .await?
+    .unwrap_or(0);
+
+    // Get ranked products
+    let products = sqlx::query_as::<_, ProductProfitability>(
+        r#"
+        WITH lines AS (
+            SELECT
+                COALESCE(li.shopify_product_id, v.shopify_product_id) AS shopify_product_id,
+                CASE WHEN $4 = 'variant' THEN li.shopify_variant_id END AS shopify_variant_id,
+                li.order_id,
+                li.title,
+                li.sku,
+                li.quantity,
+                to_reporting_currency(
+                    o.merchant_id, li.price * li.quantity - li.total_discount, o.currency,
+                    (o.processed_at AT TIME ZONE 'UTC')::date
+                ) AS revenue,
+                COALESCE(lic.quantity * lic.unit_cost, 0) AS cogs,
+                COALESCE(occ.courier_cost, 0) AS order_courier_cost,
+                COALESCE(oac.ad_cost, 0) AS order_ad_cost
+            FROM included_orders io
+            JOIN orders o ON o.id = io.id
+            JOIN order_line_items li ON li.order_id = o.id
+            LEFT JOIN variants v
+                ON v.merchant_id = li.merchant_id AND v.shopify_variant_id = li.shopify_variant_id
+            LEFT JOIN line_item_costs lic ON lic.order_line_item_id = li.id
+            LEFT JOIN order_courier_costs occ ON occ.order_id = o.id
+            LEFT JOIN order_ad_costs oac ON oac.order_id = o.id
+            WHERE o.merchant_id = $1
+                AND ($2::timestamptz IS NULL OR o.processed_at >= $2)
+                AND ($3::timestamptz IS NULL OR o.processed_at <= $3)
+        ),
+        line_shares AS (
+            SELECT
+                lines.*,
+                COALESCE(
+                    revenue / NULLIF(SUM(revenue) OVER (PARTITION BY order_id), 0),
+                    quantity::numeric / NULLIF(SUM(quantity) OVER (PARTITION BY order_id), 0),
+                    0
+                ) AS order_share
+            FROM lines
+        ),
+        grouped AS (
+            SELECT
+                shopify_product_id,
+                shopify_variant_id,
+                MAX(title) AS line_title,
+                MAX(sku) AS line_sku,
+                SUM(quantity)::bigint AS units_sold,
+                SUM(revenue) AS revenue,
+                SUM(cogs) AS cogs,
+                ROUND(SUM(order_courier_cost * order_share), 4) AS allocated_shipping,
+                ROUND(SUM(order_ad_cost * order_share), 4) AS allocated_ad_spend
+            FROM line_shares
+            GROUP BY shopify_product_id, shopify_variant_id
+        ),
+        margins AS (
+            SELECT
+                grouped.*,
+                revenue - cogs - allocated_shipping - allocated_ad_spend AS contribution_margin
+            FROM grouped
+        )
+        SELECT
+            m.shopify_product_id,
+            m.shopify_variant_id,
+            COALESCE(p.title, m.line_title) AS title,
+            v.title AS variant_title,
+            CASE WHEN m.shopify_variant_id IS NOT NULL THEN COALESCE(v.sku, m.line_sku) END AS sku,
+            m.units_sold,
+            m.revenue,
+            m.cogs,
+            m.allocated_shipping,
+            m.allocated_ad_spend,
+            m.contribution_margin,
+            ROUND(m.contribution_margin / NULLIF(m.revenue, 0) * 100, 2) AS margin_pct
+        FROM margins m
+        LEFT JOIN products p
+            ON p.merchant_id = $1 AND p.shopify_product_id = m.shopify_product_id AND p.deleted_at IS NULL
+        LEFT JOIN variants v
+            ON v.merchant_id = $1 AND v.shopify_variant_id = m.shopify_variant_id
+        ORDER BY m.contribution_margin DESC, m.revenue DESC
+        LIMIT $5 OFFSET $6
+        "#,
+    )
+    .bind(params.merchant_id)
+    .bind(params.start_date)
+    .bind(params.end_date)
+    .bind(group_by)
+    .bind(limit)
+    .bind(offset)
+    .fetch_all(db)
+    .await?;
+
+    Ok(ProductProfitabilityResponse {
+        products,
+        total,
+        limit,
+        offset,
+    })
+}
+
+async fn product_profitability(
+    Extension(ctx): Extension<ApiContext>,
+    Query(params): Query<ProductProfitabilityParams>,
+) -> AppResult<ProductProfitabilityResponse> {
+    eprintln!(
+        "Calculating product profitability: merchant_id={}, group_by={:?}, start_date={:?}, end_date={:?}",
+        params.merchant_id, params.group_by, params.start_date, params.end_date
+    );
+
+    let report = get_product_profitability(&ctx.db, &params).await?;
+
+    eprintln!(
+        "Found {} profitability rows (total: {})",
+        report.products.len(),
+        report.total
+    );
+    Ok(Json(report))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http::merchants::create_merchant;
+    use crate::http::test_utils::setup_test_db;
+    use uuid::Uuid;
+
+    #[tokio::test]
+    async fn test_upsert_product_refreshes_existing_row() {
+        let db = setup_test_db().await.expect(
+            "Failed to setup test database. Make sure PostgreSQL is running and accessible.",
+        );
+
+        let merchant_id = Uuid::new_v4();
+
+        create_merchant(
+            &db,
+            CreateMerchantRequest {
+                id: Some(merchant_id),
+                shop_domain: format!("test-merchant-{}.myshopify.com", merchant_id),
+                shop_name: Some("Test Merchant".to_string()),
+                shop_currency: None,
+                timezone: None,
+            },
+        )
+        .await
+        .expect("Failed to create merchant");
+
+        // Products are created on first sight and refreshed, or restored, afterwards
+        let product = |title: &str| UpsertProductRequest {
+            merchant_id,
+            title: Some(title.to_string()),
+            product_type: None,
+            status: Some("active".to_string()),
+        };
+        let first = upsert_product(&db, 7001, product("Mug")).await.unwrap();
+        sqlx::query("UPDATE products SET deleted_at = NOW() WHERE id = $1")
+            .bind(first.id)
+            .execute(&db)
+            .await
+            .unwrap();
+        let second = upsert_product(&db, 7001, product("Large Mug"))
+            .await
+            .unwrap();
+
+        assert_eq!(second.id, first.id);
+        assert_eq!(second.title.as_deref(), Some("Large Mug"));
+        assert!(second.deleted_at.is_none());
+    }
+}
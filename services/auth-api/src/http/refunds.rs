@@ -0,0 +1,237 @@
+use crate::http::{types::*, ApiContext, AppError, AppResult};
+use axum::{extract::Query, routing::get, Extension, Json, Router};
+use rust_decimal::Decimal;
+
+/// Shopify restock types that put the refunded units back into inventory
+const RESTOCKING_TYPES: &[&str] = &["return", "cancel", "legacy_restock"];
+
+/// Upsert a refund and its line items for an order identified by its Shopify ID
+/// (can be used by HTTP handlers and tests).
+///
+/// Refunds are keyed by `shopify_refund_id`, so re-syncing an order refreshes
+/// its refunds instead of duplicating them. When `tax_amount` is omitted it is
+/// taken from the refunded line items.
+pub async fn upsert_refund(
+    db: &sqlx::PgPool,
+    payload: UpsertRefundRequest,
+) -> Result<RefundWithLineItems, AppError> {
+    if payload.amount.is_sign_negative()
+        || payload
+            .shipping_amount
+            .is_some_and(|d| d.is_sign_negative())
+        || payload.tax_amount.is_some_and(|d| d.is_sign_negative())
+    {
+        return Err(AppError::Validation(
+            "Refund amounts must not be negative".to_string(),
+        ));
+    }
+    if let Some(item) = payload.line_items.iter().find(|item| item.quantity < 0) {
+        return Err(AppError::Validation(format!(
+            "Refund line item {} has a negative quantity",
+            item.shopify_refund_line_item_id
+        )));
+    }
+
+    let order_id = sqlx::query_scalar::<_, i64>(
+        r#"
+        SELECT id FROM orders
+        WHERE merchant_id = $1 AND shopify_order_id = $2
+        "#,
+    )
+    .bind(payload.merchant_id)
+    .bind(payload.shopify_order_id)
+    .fetch_optional(db)
+    .await?
+    .ok_or(AppError::NotFound)?;
+
+    let tax_amount = payload.tax_amount.unwrap_or_else(|| {
+        payload
+            .line_items
+            .iter()
+            .filter_map(|item| item.total_tax)
+            .sum::<Decimal>()
+    });
+
+    let mut tx = db.begin().await?;
+
+    let refund = sqlx::query_as::<_, Refund>(
+        r#"
+        INSERT INTO refunds (
+            merchant_id, order_id, shopify_refund_id, processed_at, note, currency,
+            amount, shipping_amount, tax_amount
+        )
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+        ON CONFLICT (merchant_id, shopify_refund_id) DO UPDATE SET
+            order_id = EXCLUDED.order_id,
+            processed_at = EXCLUDED.processed_at,
+            note = EXCLUDED.note,
+            currency = EXCLUDED.currency,
+            amount = EXCLUDED.amount,
+            shipping_amount = EXCLUDED.shipping_amount,
+            tax_amount = EXCLUDED.tax_amount,
+            updated_at = NOW()
+        RETURNING id, merchant_id, order_id, shopify_refund_id, processed_at, note, currency,
+                  amount, shipping_amount, tax_amount, created_at, updated_at
+        "#,
+    )
+    .bind(payload.merchant_id)
+    .bind(order_id)
+    .bind(payload.shopify_refund_id)
+    .bind(payload.processed_at)
+    .bind(payload.note)
+    .bind(payload.currency)
+    .bind(payload.amount)
+    .bind(payload.shipping_amount.unwrap_or_default())
+    .bind(tax_amount)
+    .fetch_one(&mut *tx)
+    .await?;
+
+    let mut line_items = Vec::with_capacity(payload.line_items.len());
+
+    for item in payload.line_items {
+        let restocked = item
+            .restock_type
+            .as_deref()
+            .is_some_and(|restock_type| RESTOCKING_TYPES.contains(&restock_type));
+
+        let line_item = sqlx::query_as::<_, RefundLineItem>(
+            r#"
+            INSERT INTO refund_line_items (
+                refund_id, merchant_id, shopify_refund_line_item_id, shopify_line_item_id,
+                quantity, subtotal, total_tax, restock_type, restocked
+            )
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+            ON CONFLICT (merchant_id, shopify_refund_line_item_id) DO UPDATE SET
+                refund_id = EXCLUDED.refund_id,
+                shopify_line_item_id = EXCLUDED.shopify_line_item_id,
+                quantity = EXCLUDED.quantity,
+                subtotal = EXCLUDED.subtotal,
+                total_tax = EXCLUDED.total_tax,
+                restock_type = EXCLUDED.restock_type,
+                restocked = EXCLUDED.restocked
+            RETURNING id, refund_id, merchant_id, shopify_refund_line_item_id,
+                      shopify_line_item_id, quantity, subtotal, total_tax, restock_type,
+                      restocked, created_at
+            "#,
+        )
+        .bind(refund.id)
+        .bind(payload.merchant_id)
+        .bind(item.shopify_refund_line_item_id)
+        .bind(item.shopify_line_item_id)
+        .bind(item.quantity)
+        .bind(item.subtotal.unwrap_or_default())
+        .bind(item.total_tax.unwrap_or_default())
+        .bind(item.restock_type)
+        .bind(restocked)
+        .fetch_one(&mut *tx)
+        .await?;
+
+        line_items.push(line_item);
+    }
+
+    tx.commit().await?;
+
+    Ok(RefundWithLineItems { refund, line_items })
+}
+
+pub fn refunds_router() -> Router {
+    Router::new().route("/refunds", get(list_refunds).post(upsert_refund_handler))
+}
+
+async fn list_refunds(
+    Extension(ctx): Extension<ApiContext>,
+    Query(params): Query<ListRefundsParams>,
+) -> AppResult<RefundListResponse> {
+    eprintln!(
+        "Listing refunds: merchant_id={}, order_id={:?}",
+        params.merchant_id, params.order_id
+    );
+
+    let limit = params.limit.unwrap_or(50).min(100);
+    let offset = params.offset.unwrap_or(0);
+
+    // Get total count
+    let total: i64 = sqlx::query_scalar::<_, Option<i64>>(
+        r#"
+        SELECT COUNT(*) as count
+        FROM refunds
+        WHERE merchant_id = $1
+            AND ($2::bigint IS NULL OR order_id = $2)
+        "#,
+    )
+    .bind(params.merchant_id)
+    .bind(params.order_id)
+    .fetch_one(&ctx.db)
+    .await?
+    .unwrap_or(0);
+
+    // Get refunds
+    let refunds = sqlx::query_as::<_, Refund>(
+        r#"
+        SELECT id, merchant_id, order_id, shopify_refund_id, processed_at, note, currency,
+               amount, shipping_amount, tax_amount, created_at, updated_at
+        FROM refunds
+        WHERE merchant_id = $1
+            AND ($2::bigint IS NULL OR order_id = $2)
+        ORDER BY processed_at DESC
+        LIMIT $3 OFFSET $4
+        "#,
+    )
+    .bind(params.merchant_id)
+    .bind(params.order_id)
+    .bind(limit)
+    .bind(offset)
+    .fetch_all(&ctx.db)
+    .await?;
+
+    // Get line items for each refund
+    let mut refunds_with_line_items = Vec::with_capacity(refunds.len());
+    for refund in refunds {
+        let line_items = sqlx::query_as::<_, RefundLineItem>(
+            r#"
+            SELECT id, refund_id, merchant_id, shopify_refund_line_item_id, shopify_line_item_id,
+                   quantity, subtotal, total_tax, restock_type, restocked, created_at
+            FROM refund_line_items
+            WHERE refund_id = $1
+            ORDER BY id
+            "#,
+        )
+        .bind(refund.id)
+        .fetch_all(&ctx.db)
+        .await?;
+
+        refunds_with_line_items.push(RefundWithLineItems { refund, line_items });
+    }
+
+    eprintln!(
+        "Found {} refunds (total: {})",
+        refunds_with_line_items.len(),
+        total
+    );
+
+    Ok(Json(RefundListResponse {
+        refunds: refunds_with_line_items,
+        total,
+        limit,
+        offset,
+    }))
+}
+
+async fn upsert_refund_handler(
+    Extension(ctx): Extension<ApiContext>,
+    Json(payload): Json<UpsertRefundRequest>,
+) -> AppResult<RefundWithLineItems> {
+    eprintln!(
+        "Upserting refund: merchant_id={}, shopify_order_id={}, shopify_refund_id={}",
+        payload.merchant_id, payload.shopify_order_id, payload.shopify_refund_id
+    );
+
+    let refund = upsert_refund(&ctx.db, payload).await?;
+
+    eprintln!(
+        "Refund upserted successfully: id={}, line_items={}",
+        refund.refund.id,
+        refund.line_items.len()
+    );
+    Ok(Json(refund))
+}
@@ -0,0 +1,301 @@
+use crate::http::merchants::get_merchant;
+use crate::http::{types::*, ApiContext, AppError, AppResult};
+use crate::Args;
+use aes_gcm::aead::{Aead, AeadCore, KeyInit, OsRng};
+use aes_gcm::{Aes256Gcm, Key, Nonce};
+use axum::{routing::get, Extension, Json, Router};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+
+/// Bytes of the AES-GCM nonce stored in front of each ciphertext
+const NONCE_LEN: usize = 12;
+
+/// Encrypts Shopify access tokens at rest with AES-256-GCM
+pub struct TokenCipher {
+    cipher: Aes256Gcm,
+}
+
+impl TokenCipher {
+    /// Cipher from the base64 of a 32-byte key
+    pub fn from_base64_key(key: &str) -> Result<Self, AppError> {
+        let key = STANDARD
+            .decode(key.trim())
+            .ok()
+            .filter(|key| key.len() == 32)
+            .ok_or_else(|| {
+                AppError::Internal("SHOPIFY_TOKEN_KEY must be the base64 of 32 bytes".to_string())
+            })?;
+
+        Ok(Self {
+            cipher: Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&key)),
+        })
+    }
+
+    /// Base64 of a fresh nonce followed by the ciphertext
+    pub fn encrypt(&self, token: &str) -> Result<String, AppError> {
+        let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+        let ciphertext = self
+            .cipher
+            .encrypt(&nonce, token.as_bytes())
+            .map_err(|_| AppError::Internal("Failed to encrypt access token".to_string()))?;
+
+        let mut encrypted = nonce.to_vec();
+        encrypted.extend(ciphertext);
+        Ok(STANDARD.encode(encrypted))
+    }
+
+    /// Inverse of `encrypt`; fails on tampered values or another key. Outside of
+    /// tests, only the shopify-consumer decrypts tokens.
+    #[cfg_attr(not(test), allow(dead_code))]
+    pub fn decrypt(&self, encrypted: &str) -> Result<String, AppError> {
+        let failed = || AppError::Internal("Failed to decrypt access token".to_string());
+
+        let encrypted = STANDARD.decode(encrypted).map_err(|_| failed())?;
+        if encrypted.len() < NONCE_LEN {
+            return Err(failed());
+        }
+        let (nonce, ciphertext) = encrypted.split_at(NONCE_LEN);
+        let token = self
+            .cipher
+            .decrypt(Nonce::from_slice(nonce), ciphertext)
+            .map_err(|_| failed())?;
+
+        String::from_utf8(token).map_err(|_| failed())
+    }
+}
+
+/// The token cipher configured by SHOPIFY_TOKEN_KEY
+pub fn token_cipher_for(config: &Args) -> Result<TokenCipher, AppError> {
+    let key = config.shopify_token_key.as_deref().ok_or_else(|| {
+        AppError::Validation("No access token key configured (set SHOPIFY_TOKEN_KEY)".to_string())
+    })?;
+    TokenCipher::from_base64_key(key)
+}
+
+/// Record a merchant's install with its access token, encrypted (can be used
+/// by HTTP handlers and tests).
+///
+/// Updates the token and scopes of the merchant's active install, or starts a
+/// new active install when the merchant has none (first install, or reinstall
+/// after an uninstall).
+pub async fn record_install(
+    db: &sqlx::PgPool,
+    cipher: &TokenCipher,
+    payload: RecordInstallRequest,
+) -> Result<ShopifyInstall, AppError> {
+    if payload.access_token.trim().is_empty() {
+        return Err(AppError::Validation(
+            "access_token must not be empty".to_string(),
+        ));
+    }
+    get_merchant(db, payload.merchant_id).await?;
+
+    let install = sqlx::query_as::<_, ShopifyInstall>(
+        r#"
+        INSERT INTO shopify_installs (
+            merchant_id, access_scopes, access_token_encrypted, installed_at, status
+        )
+        VALUES ($1, $2, $3, NOW(), 'active')
+        ON CONFLICT (merchant_id) WHERE status = 'active' DO UPDATE SET
+            access_scopes = EXCLUDED.access_scopes,
+            access_token_encrypted = EXCLUDED.access_token_encrypted,
+            updated_at = NOW()
+        RETURNING id, merchant_id, access_scopes, installed_at, uninstalled_at, status,
+                  created_at, updated_at
+        "#,
+    )
+    .bind(payload.merchant_id)
+    .bind(payload.access_scopes)
+    .bind(cipher.encrypt(&payload.access_token)?)
+    .fetch_one(db)
+    .await?;
+
+    Ok(install)
+}
+
+/// Active installs of live merchants with their encrypted access tokens and
+/// sync schedules (can be used by HTTP handlers and tests).
+///
+/// Tokens are never returned in plaintext: the shopify-consumer holds
+/// SHOPIFY_TOKEN_KEY and decrypts them itself. Installs without a token are
+/// left out.
+pub async fn list_active_installs(db: &sqlx::PgPool) -> Result<Vec<ActiveInstall>, AppError> {
+    let installs = sqlx::query_as::<_, ActiveInstall>(
+        r#"
+        SELECT i.merchant_id, m.shop_domain, i.access_token_encrypted, i.access_scopes,
+               s.auto_refresh_cron
+        FROM shopify_installs i
+        JOIN merchants m ON m.id = i.merchant_id AND m.deleted_at IS NULL
+        LEFT JOIN app_settings s ON s.merchant_id = i.merchant_id
+        WHERE i.status = 'active' AND i.access_token_encrypted IS NOT NULL
+        ORDER BY m.shop_domain
+        "#,
+    )
+    .fetch_all(db)
+    .await?;
+
+    Ok(installs)
+}
+
+pub fn shopify_installs_router() -> Router {
+    Router::new().route(
+        "/shopify-installs",
+        get(list_active_installs_handler).put(record_install_handler),
+    )
+}
+
+async fn list_active_installs_handler(
+    Extension(ctx): Extension<ApiContext>,
+) -> AppResult<ActiveInstallListResponse> {
+    eprintln!("Listing active Shopify installs");
+
+    let installs = list_active_installs(&ctx.db).await?;
+
+    eprintln!("Found {} active Shopify installs", installs.len());
+    Ok(Json(ActiveInstallListResponse { installs }))
+}
+
+async fn record_install_handler(
+    Extension(ctx): Extension<ApiContext>,
+    Json(payload): Json<RecordInstallRequest>,
+) -> AppResult<ShopifyInstall> {
+    eprintln!(
+        "Recording Shopify install: merchant_id={}, access_scopes={}",
+        payload.merchant_id, payload.access_scopes
+    );
+
+    let cipher = token_cipher_for(&ctx.config)?;
+    let install = record_install(&ctx.db, &cipher, payload).await?;
+
+    eprintln!("Shopify install recorded successfully: id={}", install.id);
+    Ok(Json(install))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http::merchants::{create_merchant, uninstall_merchant, update_app_settings};
+    use crate::http::test_utils::setup_test_db;
+    use uuid::Uuid;
+
+    #[tokio::test]
+    async fn test_shopify_installs_store_encrypted_tokens() {
+        let db = setup_test_db().await.expect(
+            "Failed to setup test database. Make sure PostgreSQL is running and accessible.",
+        );
+        let cipher = TokenCipher::from_base64_key("MDEyMzQ1Njc4OWFiY2RlZjAxMjM0NTY3ODlhYmNkZWY=")
+            .expect("Failed to create token cipher");
+
+        let merchant_id = Uuid::new_v4();
+        let shop_domain = format!("test-merchant-{}.myshopify.com", merchant_id);
+        create_merchant(
+            &db,
+            CreateMerchantRequest {
+                id: Some(merchant_id),
+                shop_domain: shop_domain.clone(),
+                shop_name: Some("Test Merchant".to_string()),
+                shop_currency: None,
+                timezone: None,
+            },
+        )
+        .await
+        .expect("Failed to create merchant");
+
+        update_app_settings(
+            &db,
+            merchant_id,
+            UpdateAppSettingsRequest {
+                auto_refresh_cron: Some(Some("0 0 * * * *".to_string())),
+                ..Default::default()
+            },
+        )
+        .await
+        .expect("Failed to update settings");
+
+        let install = record_install(
+            &db,
+            &cipher,
+            RecordInstallRequest {
+                merchant_id,
+                access_token: "shpat_first".to_string(),
+                access_scopes: "read_orders".to_string(),
+            },
+        )
+        .await
+        .expect("Failed to record install");
+        assert_eq!(install.status, "active");
+
+        // The token is only stored encrypted
+        let stored: String =
+            sqlx::query_scalar("SELECT access_token_encrypted FROM shopify_installs WHERE id = $1")
+                .bind(install.id)
+                .fetch_one(&db)
+                .await
+                .unwrap();
+        assert!(!stored.contains("shpat_first"));
+        assert_eq!(cipher.decrypt(&stored).unwrap(), "shpat_first");
+
+        // Recording again rotates the token of the same install
+        let rotated = record_install(
+            &db,
+            &cipher,
+            RecordInstallRequest {
+                merchant_id,
+                access_token: "shpat_second".to_string(),
+                access_scopes: "read_orders,read_products".to_string(),
+            },
+        )
+        .await
+        .expect("Failed to record install");
+        assert_eq!(rotated.id, install.id);
+
+        // Active installs are listed with their token still encrypted
+        let installs = list_active_installs(&db).await.unwrap();
+        let active = installs
+            .iter()
+            .find(|i| i.merchant_id == merchant_id)
+            .expect("Install should be active");
+        assert_eq!(active.shop_domain, shop_domain);
+        assert!(!active.access_token_encrypted.contains("shpat_second"));
+        assert_eq!(
+            cipher.decrypt(&active.access_token_encrypted).unwrap(),
+            "shpat_second"
+        );
+        assert_eq!(active.access_scopes, "read_orders,read_products");
+        assert_eq!(active.auto_refresh_cron.as_deref(), Some("0 0 * * * *"));
+
+        // Another key cannot read the tokens
+        let other =
+            TokenCipher::from_base64_key("ZmVkY2JhOTg3NjU0MzIxMGZlZGNiYTk4NzY1NDMyMTA=").unwrap();
+        assert!(other.decrypt(&stored).is_err());
+        assert!(other.decrypt(&active.access_token_encrypted).is_err());
+
+        // Uninstalled merchants are no longer synced; reinstalling starts a new install
+        uninstall_merchant(&db, merchant_id).await.unwrap();
+        let installs = list_active_installs(&db).await.unwrap();
+        assert!(installs.iter().all(|i| i.merchant_id != merchant_id));
+
+        let reinstall = record_install(
+            &db,
+            &cipher,
+            RecordInstallRequest {
+                merchant_id,
+                access_token: "shpat_third".to_string(),
+                access_scopes: "read_orders".to_string(),
+            },
+        )
+        .await
+        .expect("Failed to record install");
+        assert_ne!(reinstall.id, install.id);
+
+        let installs = list_active_installs(&db).await.unwrap();
+        let active = installs
+            .iter()
+            .find(|i| i.merchant_id == merchant_id)
+            .expect("Reinstall should be active");
+        assert_eq!(
+            cipher.decrypt(&active.access_token_encrypted).unwrap(),
+            "shpat_third"
+        );
+    }
+}
@@ -0,0 +1,216 @@
+use crate::http::merchants::get_app_settings;
+use crate::http::{types::*, ApiContext, AppError, AppResult};
+use axum::{extract::Query, routing::get, Extension, Json, Router};
+use uuid::Uuid;
+
+/// Resources the shopify-consumer keeps sync checkpoints for
+const SYNC_RESOURCES: &[&str] = &["products", "orders"];
+
+fn validate_resource(resource: &str) -> Result<(), AppError> {
+    if !SYNC_RESOURCES.contains(&resource) {
+        return Err(AppError::Validation(format!(
+            "resource must be one of: {}",
+            SYNC_RESOURCES.join(", ")
+        )));
+    }
+    Ok(())
+}
+
+/// Get where the sync of a resource stands (can be used by HTTP handlers and
+/// tests).
+///
+/// `updated_at_min` is where the next sync starts: the last checkpoint. Before
+/// the first sync completes, orders look back `app_settings.sync_lookback_days`
+/// while products have no lower bound, so the whole catalog is synced once.
+pub async fn get_sync_state(
+    db: &sqlx::PgPool,
+    merchant_id: Uuid,
+    resource: &str,
+) -> Result<SyncState, AppError> {
+    validate_resource(resource)?;
+
+    // Also checks the merchant exists
+    let settings = get_app_settings(db, merchant_id).await?;
+    let lookback_days = (resource == "orders").then_some(settings.sync_lookback_days);
+
+    let state = sqlx::query_as::<_, SyncState>(
+        r#"
+        SELECT
+            $1 AS merchant_id,
+            $2 AS resource,
+            s.last_updated_at,
+            s.cursor,
+            s.run_started_at,
+            COALESCE(s.last_updated_at, NOW() - make_interval(days => $3)) AS updated_at_min,
+            s.updated_at
+        FROM (SELECT 1) AS one
+        LEFT JOIN sync_state s ON s.merchant_id = $1 AND s.resource = $2
+        "#,
+    )
+    .bind(merchant_id)
+    .bind(resource)
+    .bind(lookback_days)
+    .fetch_one(db)
+    .await?;
+
+    Ok(state)
+}
+
+/// Record sync progress of a resource (can be used by HTTP handlers and tests).
+///
+/// `cursor` and `run_started_at` are replaced as given, so a completed sync
+/// clears them; `last_updated_at` only moves when provided.
+pub async fn update_sync_state(
+    db: &sqlx::PgPool,
+    payload: UpdateSyncStateRequest,
+) -> Result<SyncState, AppError> {
+    validate_resource(&payload.resource)?;
+
+    // Also checks the merchant exists
+    get_app_settings(db, payload.merchant_id).await?;
+
+    sqlx::query(
+        r#"
+        INSERT INTO sync_state (merchant_id, resource, last_updated_at, cursor, run_started_at)
+        VALUES ($1, $2, $3, $4, $5)
+        ON CONFLICT (merchant_id, resource) DO UPDATE SET
+            last_updated_at = COALESCE(EXCLUDED.last_updated_at, sync_state.last_updated_at),
+            cursor = EXCLUDED.cursor,
+            run_started_at = EXCLUDED.run_started_at,
+            updated_at = NOW()
+        "#,
+    )
+    .bind(payload.merchant_id)
+    .bind(&payload.resource)
+    .bind(payload.last_updated_at)
+    .bind(&payload.cursor)
+    .bind(payload.run_started_at)
+    .execute(db)
+    .await?;
+
+    get_sync_state(db, payload.merchant_id, &payload.resource).await
+}
+
+pub fn sync_state_router() -> Router {
+    Router::new().route("/sync-state", get(get_state).put(update_state))
+}
+
+async fn get_state(
+    Extension(ctx): Extension<ApiContext>,
+    Query(params): Query<SyncStateParams>,
+) -> AppResult<SyncState> {
+    eprintln!(
+        "Getting sync state: merchant_id={}, resource={}",
+        params.merchant_id, params.resource
+    );
+
+    let state = get_sync_state(&ctx.db, params.merchant_id, &params.resource).await?;
+
+    Ok(Json(state))
+}
+
+async fn update_state(
+    Extension(ctx): Extension<ApiContext>,
+    Json(payload): Json<UpdateSyncStateRequest>,
+) -> AppResult<SyncState> {
+    eprintln!(
+        "Updating sync state: merchant_id={}, resource={}, last_updated_at={:?}, cursor={:?}",
+        payload.merchant_id, payload.resource, payload.last_updated_at, payload.cursor
+    );
+
+    let state = update_sync_state(&ctx.db, payload).await?;
+
+    eprintln!(
+        "Sync state updated: resource={}, updated_at_min={:?}",
+        state.resource, state.updated_at_min
+    );
+    Ok(Json(state))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http::merchants::{create_merchant, update_app_settings};
+    use crate::http::test_utils::setup_test_db;
+
+    #[tokio::test]
+    async fn test_sync_state_checkpoints() {
+        let db = setup_test_db().await.expect(
+            "Failed to setup test database. Make sure PostgreSQL is running and accessible.",
+        );
+
+        let merchant_id = Uuid::new_v4();
+        create_merchant(
+            &db,
+            CreateMerchantRequest {
+                id: Some(merchant_id),
+                shop_domain: format!("test-merchant-{}.myshopify.com", merchant_id),
+                shop_name: Some("Test Merchant".to_string()),
+                shop_currency: None,
+                timezone: None,
+            },
+        )
+        .await
+        .expect("Failed to create merchant");
+
+        update_app_settings(
+            &db,
+            merchant_id,
+            UpdateAppSettingsRequest {
+                sync_lookback_days: Some(30),
+                ..Default::default()
+            },
+        )
+        .await
+        .expect("Failed to update settings");
+
+        // Before the first sync completes, orders look back sync_lookback_days
+        let state = get_sync_state(&db, merchant_id, "orders").await.unwrap();
+        let lookback = chrono::Utc::now() - state.updated_at_min.unwrap();
+        assert!(state.last_updated_at.is_none());
+        assert!(lookback > chrono::Duration::days(30) - chrono::Duration::minutes(1));
+        assert!(lookback <= chrono::Duration::days(30) + chrono::Duration::minutes(1));
+
+        // An interrupted sync keeps its cursor and start time
+        let run_started_at = chrono::DateTime::parse_from_rfc3339("2024-03-01T10:00:00Z")
+            .unwrap()
+            .with_timezone(&chrono::Utc);
+        let state = update_sync_state(
+            &db,
+            UpdateSyncStateRequest {
+                merchant_id,
+                resource: "orders".to_string(),
+                last_updated_at: None,
+                cursor: Some("page-2".to_string()),
+                run_started_at: Some(run_started_at),
+            },
+        )
+        .await
+        .unwrap();
+        assert_eq!(state.cursor.as_deref(), Some("page-2"));
+        assert!(state.last_updated_at.is_none());
+
+        // Completing it moves the checkpoint and clears the cursor
+        let state = update_sync_state(
+            &db,
+            UpdateSyncStateRequest {
+                merchant_id,
+                resource: "orders".to_string(),
+                last_updated_at: Some(run_started_at),
+                cursor: None,
+                run_started_at: None,
+            },
+        )
+        .await
+        .unwrap();
+        assert_eq!(state.last_updated_at, Some(run_started_at));
+        assert_eq!(state.updated_at_min, Some(run_started_at));
+        assert!(state.cursor.is_none());
+
+        // Resources are tracked separately, and products are first synced in full
+        let products = get_sync_state(&db, merchant_id, "products").await.unwrap();
+        assert!(products.last_updated_at.is_none());
+        assert!(products.updated_at_min.is_none());
+        assert!(get_sync_state(&db, merchant_id, "customers").await.is_err());
+    }
+}
@@ -0,0 +1,179 @@
+use crate::http::{types::*, ApiContext, AppError, AppResult};
+use axum::{extract::Query, routing::get, Extension, Json, Router};
+
+/// Upsert the payment transactions of an order identified by its Shopify ID
+/// (can be used by HTTP handlers and tests).
+///
+/// Transactions are keyed by `shopify_transaction_id`, so re-syncing an order
+/// refreshes its existing rows instead of duplicating them. Gateways are
+/// stored lowercase so they match payment fee schedules.
+pub async fn upsert_order_transactions(
+    db: &sqlx::PgPool,
+    payload: UpsertOrderTransactionsRequest,
+) -> Result<Vec<OrderTransaction>, AppError> {
+    let order_id = sqlx::query_scalar::<_, i64>(
+        r#"
+        SELECT id FROM orders
+        WHERE merchant_id = $1 AND shopify_order_id = $2
+        "#,
+    )
+    .bind(payload.merchant_id)
+    .bind(payload.shopify_order_id)
+    .fetch_optional(db)
+    .await?
+    .ok_or(AppError::NotFound)?;
+
+    let mut tx = db.begin().await?;
+    let mut transactions = Vec::with_capacity(payload.transactions.len());
+
+    for item in payload.transactions {
+        if item.amount.is_sign_negative() {
+            return Err(AppError::Validation(format!(
+                "Transaction {} has a negative amount",
+                item.shopify_transaction_id
+            )));
+        }
+
+        let transaction = sqlx::query_as::<_, OrderTransaction>(
+            r#"
+            INSERT INTO order_transactions (
+                merchant_id, order_id, shopify_transaction_id, kind, status, gateway,
+                amount, currency, processed_at
+            )
+            VALUES ($1, $2, $3, $4, $5, LOWER($6), $7, $8, $9)
+            ON CONFLICT (merchant_id, shopify_transaction_id) DO UPDATE SET
+                order_id = EXCLUDED.order_id,
+                kind = EXCLUDED.kind,
+                status = EXCLUDED.status,
+                gateway = EXCLUDED.gateway,
+                amount = EXCLUDED.amount,
+                currency = EXCLUDED.currency,
+                processed_at = EXCLUDED.processed_at,
+                updated_at = NOW()
+            RETURNING id, merchant_id, order_id, shopify_transaction_id, kind, status, gateway,
+                      amount, currency, processed_at, created_at, updated_at
+            "#,
+        )
+        .bind(payload.merchant_id)
+        .bind(order_id)
+        .bind(item.shopify_transaction_id)
+        .bind(item.kind)
+        .bind(item.status)
+        .bind(item.gateway)
+        .bind(item.amount)
+        .bind(item.currency)
+        .bind(item.processed_at)
+        .fetch_one(&mut *tx)
+        .await?;
+
+        transactions.push(transaction);
+    }
+
+    tx.commit().await?;
+
+    Ok(transactions)
+}
+
+pub fn transactions_router() -> Router {
+    Router::new().route(
+        "/order-transactions",
+        get(list_transactions).post(upsert_transactions),
+    )
+}
+
+async fn list_transactions(
+    Extension(ctx): Extension<ApiContext>,
+    Query(params): Query<ListOrderTransactionsParams>,
+) -> AppResult<OrderTransactionListResponse> {
+    eprintln!(
+        "Listing order transactions: merchant_id={}, order_id={:?}, shopify_order_id={:?}",
+        params.merchant_id, params.order_id, params.shopify_order_id
+    );
+
+    let limit = params.limit.unwrap_or(50).min(100);
+    let offset = params.offset.unwrap_or(0);
+
+    // Get total count
+    let total: i64 = sqlx::query_scalar::<_, Option<i64>>(
+        r#"
+        SELECT COUNT(*) as count
+        FROM order_transactions t
+        JOIN orders o ON o.id = t.order_id
+        WHERE t.merchant_id = $1
+            AND ($2::bigint IS NULL OR t.order_id = $2)
+            AND ($3::bigint IS NULL OR o.shopify_order_id = $3)
+        "#,
+    )
+    .bind(params.merchant_id)
+    .bind(params.order_id)
+    .bind(params.shopify_order_id)
+    .fetch_one(&ctx.db)
+    .await?
+    .unwrap_or(0);
+
+    // Get transactions
+    let transactions = sqlx::query_as::<_, OrderTransaction>(
+        r#"
+        SELECT
+            t.id,
+            t.merchant_id,
+            t.order_id,
+            t.shopify_transaction_id,
+            t.kind,
+            t.status,
+            t.gateway,
+            t.amount,
+            t.currency,
+            t.processed_at,
+            t.created_at,
+            t.updated_at
+        FROM order_transactions t
+        JOIN orders o ON o.id = t.order_id
+        WHERE t.merchant_id = $1
+            AND ($2::bigint IS NULL OR t.order_id = $2)
+            AND ($3::bigint IS NULL OR o.shopify_order_id = $3)
+        ORDER BY t.order_id DESC, t.id
+        LIMIT $4 OFFSET $5
+        "#,
+    )
+    .bind(params.merchant_id)
+    .bind(params.order_id)
+    .bind(params.shopify_order_id)
+    .bind(limit)
+    .bind(offset)
+    .fetch_all(&ctx.db)
+    .await?;
+
+    eprintln!(
+        "Found {} transactions (total: {})",
+        transactions.len(),
+        total
+    );
+
+    Ok(Json(OrderTransactionListResponse {
+        transactions,
+        total,
+        limit,
+        offset,
+    }))
+}
+
+async fn upsert_transactions(
+    Extension(ctx): Extension<ApiContext>,
+    Json(payload): Json<UpsertOrderTransactionsRequest>,
+) -> AppResult<Vec<OrderTransaction>> {
+    eprintln!(
+        "Upserting order transactions: merchant_id={}, shopify_order_id={}, count={}",
+        payload.merchant_id,
+        payload.shopify_order_id,
+        payload.transactions.len()
+    );
+
+    let transactions = upsert_order_transactions(&ctx.db, payload).await?;
+
+    eprintln!(
+        "Order transactions upserted successfully: count={}",
+        transactions.len()
+    );
+    Ok(Json(transactions))
+}
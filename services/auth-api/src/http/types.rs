@@ -3,7 +3,7 @@ use axum::{
     response::{IntoResponse, Response},
     Json,
 };
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize};
 use thiserror::Error;
 use uuid::Uuid;
 
@@ -80,6 +80,57 @@ pub struct CreateMerchantRequest {
     pub timezone: Option<String>,
 }
 
+#[derive(Serialize, Deserialize, Clone, sqlx::FromRow)]
+pub struct AppSettings {
+    pub id: Uuid,
+    pub merchant_id: Uuid,
+    pub revenue_basis: String,
+    pub include_taxes: bool,
+    pub include_shipping: bool,
+    pub default_currency: Option<String>,
+    pub multi_currency_mode: String,
+    pub sync_lookback_days: i32,
+    pub auto_refresh_cron: Option<String>,
+    pub included_financial_statuses: Vec<String>,
+    pub include_cancelled_orders: bool,
+    pub include_test_orders: bool,
+    pub attribution_model: String,
+    pub courier_carrier: Option<String>,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+    pub updated_at: chrono::DateTime<chrono::Utc>,
+}
+
+/// Settings to change; omitted fields keep their value. The optional settings
+/// are `Some(None)` when sent as `null`, which clears them.
+#[derive(Deserialize, Default)]
+pub struct UpdateAppSettingsRequest {
+    pub revenue_basis: Option<String>, // subtotal|total
+    pub include_taxes: Option<bool>,
+    pub include_shipping: Option<bool>,
+    #[serde(default, deserialize_with = "deserialize_clearable")]
+    pub default_currency: Option<Option<String>>,
+    pub multi_currency_mode: Option<String>, // warn|convert
+    pub sync_lookback_days: Option<i32>,
+    #[serde(default, deserialize_with = "deserialize_clearable")]
+    pub auto_refresh_cron: Option<Option<String>>,
+    pub included_financial_statuses: Option<Vec<String>>,
+    pub include_cancelled_orders: Option<bool>,
+    pub include_test_orders: Option<bool>,
+    pub attribution_model: Option<String>, // even|revenue|utm
+    #[serde(default, deserialize_with = "deserialize_clearable")]
+    pub courier_carrier: Option<Option<String>>,
+}
+
+/// A present field as `Some`, so that `null` (`Some(None)`) can be told apart
+/// from an omitted field (`None`, via `#[serde(default)]`)
+fn deserialize_clearable<'de, T, D>(deserializer: D) -> Result<Option<Option<T>>, D::Error>
+where
+    T: Deserialize<'de>,
+    D: Deserializer<'de>,
+{
+    Option::<T>::deserialize(deserializer).map(Some)
+}
+
 #[derive(Serialize, Deserialize, Clone, sqlx::FromRow)]
 pub struct Product {
     pub id: Uuid,
@@ -102,12 +153,45 @@ pub struct Variant {
     pub sku: Option<String>,
     pub title: Option<String>,
     pub barcode: Option<String>,
-    pub weight: Option<f64>,
+    pub weight: Option<rust_decimal::Decimal>,
     pub weight_unit: Option<String>,
+    pub price: Option<rust_decimal::Decimal>,
+    pub shopify_inventory_item_id: Option<i64>,
     pub created_at: chrono::DateTime<chrono::Utc>,
     pub updated_at: chrono::DateTime<chrono::Utc>,
 }
 
+#[derive(Deserialize)]
+pub struct ListVariantsParams {
+    pub merchant_id: Uuid,
+    pub shopify_product_id: Option<i64>,
+    pub sku: Option<String>,
+    pub limit: Option<i32>,
+    pub offset: Option<i32>,
+}
+
+#[derive(Deserialize)]
+pub struct UpsertVariantRequest {
+    pub merchant_id: Uuid,
+    pub shopify_variant_id: i64,
+    pub shopify_product_id: i64,
+    pub sku: Option<String>,
+    pub title: Option<String>,
+    pub barcode: Option<String>,
+    pub weight: Option<rust_decimal::Decimal>,
+    pub weight_unit: Option<String>,
+    pub price: Option<rust_decimal::Decimal>,
+    pub shopify_inventory_item_id: Option<i64>,
+}
+
+#[derive(Serialize)]
+pub struct VariantListResponse {
+    pub variants: Vec<Variant>,
+    pub total: i64,
+    pub limit: i32,
+    pub offset: i32,
+}
+
 #[derive(Serialize, Deserialize, Clone)]
 pub struct ProductWithVariants {
     #[serde(flatten)]
@@ -134,6 +218,20 @@ pub struct CreateProductRequest {
     pub status: Option<String>,
 }
 
+/// Full state of a product for `PUT /products/by-shopify-id/:shopify_product_id`
+#[derive(Deserialize)]
+pub struct UpsertProductRequest {
+    pub merchant_id: Uuid,
+    pub title: Option<String>,
+    pub product_type: Option<String>,
+    pub status: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct DeleteProductByShopifyIdParams {
+    pub merchant_id: Uuid,
+}
+
 #[derive(Deserialize)]
 pub struct UpdateProductRequest {
     pub title: Option<String>,
@@ -149,6 +247,40 @@ pub struct ProductListResponse {
     pub offset: i32,
 }
 
+#[derive(Deserialize)]
+pub struct ProductProfitabilityParams {
+    pub merchant_id: Uuid,
+    pub start_date: Option<chrono::DateTime<chrono::Utc>>,
+    pub end_date: Option<chrono::DateTime<chrono::Utc>>,
+    pub group_by: Option<String>, // product|variant, defaults to product
+    pub limit: Option<i32>,
+    pub offset: Option<i32>,
+}
+
+#[derive(Serialize, sqlx::FromRow)]
+pub struct ProductProfitability {
+    pub shopify_product_id: Option<i64>,
+    pub shopify_variant_id: Option<i64>, // only set when grouping by variant
+    pub title: Option<String>,
+    pub variant_title: Option<String>,
+    pub sku: Option<String>,
+    pub units_sold: i64,
+    pub revenue: rust_decimal::Decimal,
+    pub cogs: rust_decimal::Decimal,
+    pub allocated_shipping: rust_decimal::Decimal,
+    pub allocated_ad_spend: rust_decimal::Decimal,
+    pub contribution_margin: rust_decimal::Decimal,
+    pub margin_pct: Option<rust_decimal::Decimal>, // contribution margin / revenue; None without revenue
+}
+
+#[derive(Serialize)]
+pub struct ProductProfitabilityResponse {
+    pub products: Vec<ProductProfitability>,
+    pub total: i64,
+    pub limit: i32,
+    pub offset: i32,
+}
+
 // Orders
 #[derive(Serialize, Deserialize, Clone, sqlx::FromRow)]
 pub struct Order {
@@ -165,10 +297,28 @@ pub struct Order {
     pub total_tax: Option<rust_decimal::Decimal>,
     pub financial_status: Option<String>,
     pub cancelled_at: Option<chrono::DateTime<chrono::Utc>>,
+    pub shipping_country_code: Option<String>,
+    pub test: bool,
+    pub landing_site: Option<String>,
+    pub referring_site: Option<String>,
+    pub utm_source: Option<String>,
+    pub utm_medium: Option<String>,
+    pub utm_campaign: Option<String>,
     pub created_at: chrono::DateTime<chrono::Utc>,
     pub updated_at: chrono::DateTime<chrono::Utc>,
 }
 
+/// Response of `PUT /orders/by-shopify-id/:shopify_order_id`
+#[derive(Serialize, sqlx::FromRow)]
+pub struct UpsertedOrder {
+    #[serde(flatten)]
+    #[sqlx(flatten)]
+    pub order: Order,
+    /// The order is new, its financial status changed or none of its
+    /// transactions are stored yet, so they need to be fetched again
+    pub transactions_outdated: bool,
+}
+
 #[derive(Deserialize)]
 pub struct ListOrdersParams {
     pub merchant_id: Uuid,
@@ -190,6 +340,31 @@ pub struct CreateOrderRequest {
     pub total_shipping_price_set_amount: Option<rust_decimal::Decimal>,
     pub total_tax: Option<rust_decimal::Decimal>,
     pub financial_status: Option<String>,
+    pub shipping_country_code: Option<String>,
+    pub cancelled_at: Option<chrono::DateTime<chrono::Utc>>,
+    pub test: Option<bool>, // Shopify test order
+    pub landing_site: Option<String>, // UTM parameters are read from its query string
+    pub referring_site: Option<String>,
+}
+
+/// Full state of an order for `PUT /orders/by-shopify-id/:shopify_order_id`
+#[derive(Deserialize)]
+pub struct UpsertOrderRequest {
+    pub merchant_id: Uuid,
+    pub name: Option<String>,
+    pub processed_at: Option<chrono::DateTime<chrono::Utc>>,
+    pub currency: Option<String>,
+    pub subtotal_price: Option<rust_decimal::Decimal>,
+    pub total_price: Option<rust_decimal::Decimal>,
+    pub total_discounts: Option<rust_decimal::Decimal>,
+    pub total_shipping_price_set_amount: Option<rust_decimal::Decimal>,
+    pub total_tax: Option<rust_decimal::Decimal>,
+    pub financial_status: Option<String>,
+    pub shipping_country_code: Option<String>,
+    pub cancelled_at: Option<chrono::DateTime<chrono::Utc>>,
+    pub test: Option<bool>, // Shopify test order
+    pub landing_site: Option<String>, // UTM parameters are read from its query string
+    pub referring_site: Option<String>,
 }
 
 #[derive(Deserialize)]
@@ -207,6 +382,290 @@ pub struct OrderListResponse {
     pub offset: i32,
 }
 
+#[derive(Serialize, sqlx::FromRow)]
+pub struct OrderProfit {
+    pub order_id: i64,
+    pub merchant_id: Uuid,
+    pub shopify_order_id: i64,
+    pub name: Option<String>,
+    pub processed_at: Option<chrono::DateTime<chrono::Utc>>,
+    pub reporting_currency: Option<String>,
+    pub included: bool, // whether the order passes the merchant's inclusion policy
+    pub shopify_revenue: rust_decimal::Decimal,
+    pub refunds: rust_decimal::Decimal,
+    pub shopify_product_cost: rust_decimal::Decimal,
+    pub returned_product_cost: rust_decimal::Decimal,
+    pub ad_cost: rust_decimal::Decimal,
+    pub courier_cost: rust_decimal::Decimal,
+    pub payment_fees: rust_decimal::Decimal,
+    pub manual_cost: rust_decimal::Decimal,
+    pub profit: rust_decimal::Decimal,
+    #[sqlx(skip)]
+    pub line_items: Vec<OrderLineItemProfit>,
+    #[sqlx(skip)]
+    pub warnings: Vec<String>,
+}
+
+#[derive(Serialize, sqlx::FromRow)]
+pub struct OrderLineItemProfit {
+    pub shopify_line_item_id: i64,
+    pub shopify_variant_id: Option<i64>,
+    pub sku: Option<String>,
+    pub title: Option<String>,
+    pub quantity: i32,
+    pub revenue: rust_decimal::Decimal,
+    pub unit_cost: Option<rust_decimal::Decimal>, // None when no cost was recorded at the time of sale
+    pub cogs: rust_decimal::Decimal,
+}
+
+// Order Line Items
+#[derive(Serialize, Deserialize, Clone, sqlx::FromRow)]
+pub struct OrderLineItem {
+    pub id: i64,
+    pub merchant_id: Uuid,
+    pub order_id: i64,
+    pub shopify_line_item_id: i64,
+    pub shopify_product_id: Option<i64>,
+    pub shopify_variant_id: Option<i64>,
+    pub sku: Option<String>,
+    pub title: Option<String>,
+    pub quantity: i32,
+    pub price: rust_decimal::Decimal,
+    pub total_discount: rust_decimal::Decimal,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+    pub updated_at: chrono::DateTime<chrono::Utc>,
+}
+
+#[derive(Deserialize)]
+pub struct ListOrderLineItemsParams {
+    pub merchant_id: Uuid,
+    pub order_id: Option<i64>,
+    pub shopify_order_id: Option<i64>,
+    pub limit: Option<i32>,
+    pub offset: Option<i32>,
+}
+
+#[derive(Deserialize)]
+pub struct OrderLineItemInput {
+    pub shopify_line_item_id: i64,
+    pub shopify_product_id: Option<i64>,
+    pub shopify_variant_id: Option<i64>,
+    pub sku: Option<String>,
+    pub title: Option<String>,
+    pub quantity: i32,
+    pub price: rust_decimal::Decimal,
+    pub total_discount: Option<rust_decimal::Decimal>, // Sum of discount allocations, defaults to 0
+}
+
+#[derive(Deserialize)]
+pub struct UpsertOrderLineItemsRequest {
+    pub merchant_id: Uuid,
+    pub shopify_order_id: i64,
+    pub line_items: Vec<OrderLineItemInput>,
+}
+
+#[derive(Serialize)]
+pub struct OrderLineItemListResponse {
+    pub line_items: Vec<OrderLineItem>,
+    pub total: i64,
+    pub limit: i32,
+    pub offset: i32,
+}
+
+// Refunds
+#[derive(Serialize, Deserialize, Clone, sqlx::FromRow)]
+pub struct Refund {
+    pub id: i64,
+    pub merchant_id: Uuid,
+    pub order_id: i64,
+    pub shopify_refund_id: i64,
+    pub processed_at: chrono::DateTime<chrono::Utc>,
+    pub note: Option<String>,
+    pub currency: Option<String>,
+    pub amount: rust_decimal::Decimal,
+    pub shipping_amount: rust_decimal::Decimal,
+    pub tax_amount: rust_decimal::Decimal,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+    pub updated_at: chrono::DateTime<chrono::Utc>,
+}
+
+#[derive(Serialize, Deserialize, Clone, sqlx::FromRow)]
+pub struct RefundLineItem {
+    pub id: i64,
+    pub refund_id: i64,
+    pub merchant_id: Uuid,
+    pub shopify_refund_line_item_id: i64,
+    pub shopify_line_item_id: i64,
+    pub quantity: i32,
+    pub subtotal: rust_decimal::Decimal,
+    pub total_tax: rust_decimal::Decimal,
+    pub restock_type: Option<String>,
+    pub restocked: bool,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+}
+
+#[derive(Serialize)]
+pub struct RefundWithLineItems {
+    #[serde(flatten)]
+    pub refund: Refund,
+    pub line_items: Vec<RefundLineItem>,
+}
+
+#[derive(Deserialize)]
+pub struct ListRefundsParams {
+    pub merchant_id: Uuid,
+    pub order_id: Option<i64>,
+    pub limit: Option<i32>,
+    pub offset: Option<i32>,
+}
+
+#[derive(Deserialize)]
+pub struct RefundLineItemInput {
+    pub shopify_refund_line_item_id: i64,
+    pub shopify_line_item_id: i64,
+    pub quantity: i32,
+    pub subtotal: Option<rust_decimal::Decimal>,
+    pub total_tax: Option<rust_decimal::Decimal>,
+    pub restock_type: Option<String>, // no_restock|cancel|return|legacy_restock
+}
+
+#[derive(Deserialize)]
+pub struct UpsertRefundRequest {
+    pub merchant_id: Uuid,
+    pub shopify_order_id: i64,
+    pub shopify_refund_id: i64,
+    pub processed_at: chrono::DateTime<chrono::Utc>,
+    pub note: Option<String>,
+    pub currency: Option<String>,
+    pub amount: rust_decimal::Decimal,
+    pub shipping_amount: Option<rust_decimal::Decimal>,
+    pub tax_amount: Option<rust_decimal::Decimal>,
+    #[serde(default)]
+    pub line_items: Vec<RefundLineItemInput>,
+}
+
+#[derive(Serialize)]
+pub struct RefundListResponse {
+    pub refunds: Vec<RefundWithLineItems>,
+    pub total: i64,
+    pub limit: i32,
+    pub offset: i32,
+}
+
+// Sync State
+#[derive(Serialize, Deserialize, Clone, sqlx::FromRow)]
+pub struct SyncState {
+    pub merchant_id: Uuid,
+    pub resource: String,
+    pub last_updated_at: Option<chrono::DateTime<chrono::Utc>>,
+    pub cursor: Option<String>,
+    pub run_started_at: Option<chrono::DateTime<chrono::Utc>>,
+    pub updated_at_min: Option<chrono::DateTime<chrono::Utc>>, // Where the next sync starts, None from the beginning
+    pub updated_at: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+#[derive(Deserialize)]
+pub struct SyncStateParams {
+    pub merchant_id: Uuid,
+    pub resource: String, // products|orders
+}
+
+#[derive(Deserialize)]
+pub struct UpdateSyncStateRequest {
+    pub merchant_id: Uuid,
+    pub resource: String, // products|orders
+    pub last_updated_at: Option<chrono::DateTime<chrono::Utc>>, // Kept when omitted
+    pub cursor: Option<String>,
+    pub run_started_at: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+// Shopify Installs
+#[derive(Serialize, Deserialize, Clone, sqlx::FromRow)]
+pub struct ShopifyInstall {
+    pub id: Uuid,
+    pub merchant_id: Uuid,
+    pub access_scopes: String,
+    pub installed_at: chrono::DateTime<chrono::Utc>,
+    pub uninstalled_at: Option<chrono::DateTime<chrono::Utc>>,
+    pub status: String, // active|uninstalled
+    pub created_at: chrono::DateTime<chrono::Utc>,
+    pub updated_at: chrono::DateTime<chrono::Utc>,
+}
+
+#[derive(Deserialize)]
+pub struct RecordInstallRequest {
+    pub merchant_id: Uuid,
+    pub access_token: String, // Stored encrypted
+    pub access_scopes: String,
+}
+
+/// An active install with what the shopify-consumer needs to sync its store
+#[derive(Serialize, Deserialize, Clone, sqlx::FromRow)]
+pub struct ActiveInstall {
+    pub merchant_id: Uuid,
+    pub shop_domain: String,
+    pub access_token_encrypted: String, // Only readable with SHOPIFY_TOKEN_KEY
+    pub access_scopes: String,
+    pub auto_refresh_cron: Option<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct ActiveInstallListResponse {
+    pub installs: Vec<ActiveInstall>,
+}
+
+// Order Transactions
+#[derive(Serialize, Deserialize, Clone, sqlx::FromRow)]
+pub struct OrderTransaction {
+    pub id: i64,
+    pub merchant_id: Uuid,
+    pub order_id: i64,
+    pub shopify_transaction_id: i64,
+    pub kind: String,
+    pub status: Option<String>,
+    pub gateway: Option<String>,
+    pub amount: rust_decimal::Decimal,
+    pub currency: Option<String>,
+    pub processed_at: Option<chrono::DateTime<chrono::Utc>>,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+    pub updated_at: chrono::DateTime<chrono::Utc>,
+}
+
+#[derive(Deserialize)]
+pub struct ListOrderTransactionsParams {
+    pub merchant_id: Uuid,
+    pub order_id: Option<i64>,
+    pub shopify_order_id: Option<i64>,
+    pub limit: Option<i32>,
+    pub offset: Option<i32>,
+}
+
+#[derive(Deserialize)]
+pub struct OrderTransactionInput {
+    pub shopify_transaction_id: i64,
+    pub kind: String, // authorization|capture|sale|void|refund
+    pub status: Option<String>,
+    pub gateway: Option<String>,
+    pub amount: rust_decimal::Decimal,
+    pub currency: Option<String>, // Defaults to the order's currency
+    pub processed_at: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+#[derive(Deserialize)]
+pub struct UpsertOrderTransactionsRequest {
+    pub merchant_id: Uuid,
+    pub shopify_order_id: i64,
+    pub transactions: Vec<OrderTransactionInput>,
+}
+
+#[derive(Serialize)]
+pub struct OrderTransactionListResponse {
+    pub transactions: Vec<OrderTransaction>,
+    pub total: i64,
+    pub limit: i32,
+    pub offset: i32,
+}
+
 // Inventory Items
 #[derive(Serialize, Deserialize, Clone, sqlx::FromRow)]
 pub struct InventoryItem {
@@ -245,6 +704,552 @@ pub struct InventoryItemListResponse {
     pub offset: i32,
 }
 
+// Inventory Cost History
+#[derive(Serialize, Deserialize, Clone, sqlx::FromRow)]
+pub struct InventoryCost {
+    pub id: Uuid,
+    pub merchant_id: Uuid,
+    pub shopify_inventory_item_id: i64,
+    pub cost: rust_decimal::Decimal,
+    pub currency: String,
+    pub effective_at: chrono::DateTime<chrono::Utc>,
+    pub source: String,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+}
+
+#[derive(Deserialize)]
+pub struct ListInventoryCostsParams {
+    pub merchant_id: Uuid,
+    pub shopify_inventory_item_id: Option<i64>,
+    pub limit: Option<i32>,
+    pub offset: Option<i32>,
+}
+
+#[derive(Deserialize)]
+pub struct CreateInventoryCostRequest {
+    pub merchant_id: Uuid,
+    pub shopify_inventory_item_id: i64,
+    pub cost: rust_decimal::Decimal,
+    pub currency: String,
+    pub effective_at: Option<chrono::DateTime<chrono::Utc>>, // Defaults to now
+    pub source: Option<String>,                               // shopify|manual, defaults to manual
+}
+
+#[derive(Serialize)]
+pub struct InventoryCostListResponse {
+    pub costs: Vec<InventoryCost>,
+    pub total: i64,
+    pub limit: i32,
+    pub offset: i32,
+}
+
+// FX Rates
+#[derive(Serialize, Deserialize, Clone, sqlx::FromRow)]
+pub struct FxRate {
+    pub id: i64,
+    pub base_currency: String,
+    pub quote_currency: String,
+    pub rate_date: chrono::NaiveDate,
+    pub rate: rust_decimal::Decimal,
+    pub source: String,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+    pub updated_at: chrono::DateTime<chrono::Utc>,
+}
+
+#[derive(Deserialize)]
+pub struct ListFxRatesParams {
+    pub base_currency: Option<String>,
+    pub quote_currency: Option<String>,
+    pub start_date: Option<chrono::NaiveDate>,
+    pub end_date: Option<chrono::NaiveDate>,
+    pub limit: Option<i32>,
+    pub offset: Option<i32>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct FxRateInput {
+    pub base_currency: String,
+    pub quote_currency: String,
+    pub rate_date: chrono::NaiveDate,
+    pub rate: rust_decimal::Decimal,
+}
+
+#[derive(Deserialize)]
+pub struct UpsertFxRatesRequest {
+    pub source: Option<String>, // manual|file|api, defaults to manual
+    pub rates: Vec<FxRateInput>,
+}
+
+#[derive(Serialize)]
+pub struct UpsertFxRatesResponse {
+    pub rates_upserted: usize,
+}
+
+#[derive(Deserialize)]
+pub struct SyncFxRatesRequest {
+    pub base_currency: String,
+    pub quote_currencies: Vec<String>,
+    pub start_date: chrono::NaiveDate,
+    pub end_date: chrono::NaiveDate,
+}
+
+#[derive(Serialize)]
+pub struct FxRateListResponse {
+    pub rates: Vec<FxRate>,
+    pub total: i64,
+    pub limit: i32,
+    pub offset: i32,
+}
+
+// Ad Spend
+#[derive(Serialize, Deserialize, Clone, sqlx::FromRow)]
+pub struct AdAccount {
+    pub id: Uuid,
+    pub merchant_id: Uuid,
+    pub platform: String,
+    pub external_account_id: String,
+    pub name: Option<String>,
+    pub currency: Option<String>,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+    pub updated_at: chrono::DateTime<chrono::Utc>,
+}
+
+#[derive(Deserialize)]
+pub struct ListAdAccountsParams {
+    pub merchant_id: Uuid,
+    pub platform: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct CreateAdAccountRequest {
+    pub merchant_id: Uuid,
+    pub platform: String, // meta|google|tiktok|other
+    pub external_account_id: String,
+    pub name: Option<String>,
+    pub currency: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct UpdateAdAccountRequest {
+    pub name: Option<String>,
+    pub currency: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, sqlx::FromRow)]
+pub struct AdCampaign {
+    pub id: Uuid,
+    pub merchant_id: Uuid,
+    pub ad_account_id: Uuid,
+    pub external_campaign_id: String,
+    pub name: Option<String>,
+    pub status: Option<String>,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+    pub updated_at: chrono::DateTime<chrono::Utc>,
+}
+
+#[derive(Deserialize)]
+pub struct ListAdCampaignsParams {
+    pub merchant_id: Uuid,
+    pub ad_account_id: Option<Uuid>,
+}
+
+#[derive(Deserialize)]
+pub struct CreateAdCampaignRequest {
+    pub ad_account_id: Uuid,
+    pub external_campaign_id: String,
+    pub name: Option<String>,
+    pub status: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct UpdateAdCampaignRequest {
+    pub name: Option<String>,
+    pub status: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, sqlx::FromRow)]
+pub struct AdSpend {
+    pub id: i64,
+    pub merchant_id: Uuid,
+    pub ad_campaign_id: Uuid,
+    pub spend_date: chrono::NaiveDate,
+    pub spend: rust_decimal::Decimal,
+    pub currency: String,
+    pub impressions: Option<i64>,
+    pub clicks: Option<i64>,
+    pub conversions: Option<rust_decimal::Decimal>,
+    pub conversion_value: Option<rust_decimal::Decimal>,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+    pub updated_at: chrono::DateTime<chrono::Utc>,
+}
+
+#[derive(Deserialize)]
+pub struct ListAdSpendParams {
+    pub merchant_id: Uuid,
+    pub ad_campaign_id: Option<Uuid>,
+    pub start_date: Option<chrono::NaiveDate>,
+    pub end_date: Option<chrono::NaiveDate>,
+    pub limit: Option<i32>,
+    pub offset: Option<i32>,
+}
+
+#[derive(Deserialize)]
+pub struct UpsertAdSpendRequest {
+    pub ad_campaign_id: Uuid,
+    pub spend_date: chrono::NaiveDate,
+    pub spend: rust_decimal::Decimal,
+    pub currency: String,
+    pub impressions: Option<i64>,
+    pub clicks: Option<i64>,
+    pub conversions: Option<rust_decimal::Decimal>,
+    pub conversion_value: Option<rust_decimal::Decimal>,
+}
+
+#[derive(Serialize)]
+pub struct AdSpendListResponse {
+    pub spend: Vec<AdSpend>,
+    pub total: i64,
+    pub limit: i32,
+    pub offset: i32,
+}
+
+/// One row of a bulk spend import; accounts and campaigns are created on first sight
+#[derive(Serialize, Deserialize, Clone)]
+pub struct AdSpendImportRow {
+    pub platform: String,
+    pub external_account_id: String,
+    pub account_name: Option<String>,
+    pub external_campaign_id: String,
+    pub campaign_name: Option<String>,
+    pub spend_date: chrono::NaiveDate,
+    pub spend: rust_decimal::Decimal,
+    pub currency: String,
+    pub impressions: Option<i64>,
+    pub clicks: Option<i64>,
+    pub conversions: Option<rust_decimal::Decimal>,
+    pub conversion_value: Option<rust_decimal::Decimal>,
+}
+
+#[derive(Deserialize)]
+pub struct ImportAdSpendRequest {
+    pub merchant_id: Uuid,
+    pub rows: Vec<AdSpendImportRow>,
+}
+
+#[derive(Serialize)]
+pub struct ImportAdSpendResponse {
+    pub rows_imported: usize,
+    pub campaigns: usize,
+}
+
+// Ad Attribution
+#[derive(Serialize, Deserialize, Clone, sqlx::FromRow)]
+pub struct OrderAdAllocation {
+    pub id: i64,
+    pub merchant_id: Uuid,
+    pub order_id: i64,
+    pub ad_campaign_id: Uuid,
+    pub spend_date: chrono::NaiveDate,
+    pub model: String,
+    pub share: rust_decimal::Decimal,
+    pub allocated_cost: rust_decimal::Decimal,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+}
+
+#[derive(Deserialize)]
+pub struct ListOrderAdAllocationsParams {
+    pub merchant_id: Uuid,
+    pub order_id: Option<i64>,
+    pub ad_campaign_id: Option<Uuid>,
+    pub start_date: Option<chrono::NaiveDate>,
+    pub end_date: Option<chrono::NaiveDate>,
+    pub limit: Option<i32>,
+    pub offset: Option<i32>,
+}
+
+#[derive(Deserialize)]
+pub struct RunAttributionRequest {
+    pub merchant_id: Uuid,
+    pub start_date: chrono::NaiveDate,
+    pub end_date: chrono::NaiveDate,
+    pub model: Option<String>, // even|revenue|utm, defaults to the merchant's attribution_model
+}
+
+#[derive(Serialize)]
+pub struct AttributionRunResponse {
+    pub merchant_id: Uuid,
+    pub model: String,
+    pub start_date: chrono::NaiveDate,
+    pub end_date: chrono::NaiveDate,
+    pub allocations_created: i64,
+    pub orders_attributed: i64,
+    pub spend: rust_decimal::Decimal,
+    pub allocated_spend: rust_decimal::Decimal,
+    pub unallocated_spend: rust_decimal::Decimal, // spend on days without orders
+}
+
+#[derive(Serialize)]
+pub struct OrderAdAllocationListResponse {
+    pub allocations: Vec<OrderAdAllocation>,
+    pub total: i64,
+    pub limit: i32,
+    pub offset: i32,
+}
+
+// Campaign Report
+#[derive(Deserialize)]
+pub struct CampaignReportParams {
+    pub merchant_id: Uuid,
+    pub start_date: chrono::NaiveDate,
+    pub end_date: chrono::NaiveDate,
+    pub ad_campaign_id: Option<Uuid>,
+}
+
+#[derive(Serialize)]
+pub struct CampaignDayPerformance {
+    pub spend_date: chrono::NaiveDate,
+    pub spend: rust_decimal::Decimal,
+    pub attributed_revenue: rust_decimal::Decimal,
+    pub attributed_gross_profit: rust_decimal::Decimal,
+    pub roas: Option<rust_decimal::Decimal>, // attributed revenue / spend
+    pub poas: Option<rust_decimal::Decimal>, // attributed gross profit / spend
+}
+
+#[derive(Serialize)]
+pub struct CampaignPerformance {
+    pub ad_campaign_id: Uuid,
+    pub external_campaign_id: String,
+    pub name: Option<String>,
+    pub platform: String,
+    pub spend: rust_decimal::Decimal,
+    pub attributed_revenue: rust_decimal::Decimal,
+    pub attributed_gross_profit: rust_decimal::Decimal,
+    pub roas: Option<rust_decimal::Decimal>,
+    pub poas: Option<rust_decimal::Decimal>,
+    pub days: Vec<CampaignDayPerformance>,
+}
+
+#[derive(Serialize)]
+pub struct CampaignReportResponse {
+    pub merchant_id: Uuid,
+    pub start_date: chrono::NaiveDate,
+    pub end_date: chrono::NaiveDate,
+    pub campaigns: Vec<CampaignPerformance>,
+    /// Amounts that could not be normalized into the reporting currency
+    pub warnings: Vec<String>,
+}
+
+// Courier Costs
+#[derive(Serialize, Deserialize, Clone, sqlx::FromRow)]
+pub struct CourierZone {
+    pub id: Uuid,
+    pub merchant_id: Uuid,
+    pub zone: String,
+    pub country_code: String,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+}
+
+#[derive(Deserialize)]
+pub struct CreateCourierZoneRequest {
+    pub merchant_id: Uuid,
+    pub zone: String,
+    pub country_code: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, sqlx::FromRow)]
+pub struct CourierRateCard {
+    pub id: Uuid,
+    pub merchant_id: Uuid,
+    pub carrier: String,
+    pub zone: String,
+    pub min_weight_grams: rust_decimal::Decimal,
+    pub max_weight_grams: Option<rust_decimal::Decimal>,
+    pub price: rust_decimal::Decimal,
+    pub currency: String,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+    pub updated_at: chrono::DateTime<chrono::Utc>,
+}
+
+#[derive(Deserialize)]
+pub struct ListCourierParams {
+    pub merchant_id: Uuid,
+    pub carrier: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct CreateCourierRateCardRequest {
+    pub merchant_id: Uuid,
+    pub carrier: String,
+    pub zone: Option<String>, // Defaults to 'default'
+    pub min_weight_grams: Option<rust_decimal::Decimal>,
+    pub max_weight_grams: Option<rust_decimal::Decimal>,
+    pub price: rust_decimal::Decimal,
+    pub currency: String,
+}
+
+#[derive(Deserialize)]
+pub struct UpdateCourierRateCardRequest {
+    pub min_weight_grams: Option<rust_decimal::Decimal>,
+    pub max_weight_grams: Option<rust_decimal::Decimal>,
+    pub price: Option<rust_decimal::Decimal>,
+}
+
+#[derive(Serialize, Deserialize, Clone, sqlx::FromRow)]
+pub struct CourierInvoice {
+    pub id: Uuid,
+    pub merchant_id: Uuid,
+    pub carrier: String,
+    pub invoice_number: String,
+    pub invoice_date: Option<chrono::NaiveDate>,
+    pub currency: String,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+    pub updated_at: chrono::DateTime<chrono::Utc>,
+}
+
+#[derive(Deserialize)]
+pub struct CourierInvoiceLineInput {
+    pub order_reference: String, // Order name (e.g. #1001) or Shopify order ID
+    pub tracking_number: Option<String>,
+    pub amount: rust_decimal::Decimal,
+}
+
+#[derive(Deserialize)]
+pub struct ImportCourierInvoiceRequest {
+    pub merchant_id: Uuid,
+    pub carrier: String,
+    pub invoice_number: String,
+    pub invoice_date: Option<chrono::NaiveDate>,
+    pub currency: String,
+    pub lines: Vec<CourierInvoiceLineInput>,
+}
+
+#[derive(Serialize)]
+pub struct ImportCourierInvoiceResponse {
+    pub invoice: CourierInvoice,
+    pub lines_imported: usize,
+    pub unmatched_references: Vec<String>,
+}
+
+// Payment Fees
+#[derive(Serialize, Deserialize, Clone, sqlx::FromRow)]
+pub struct PaymentFeeSchedule {
+    pub id: Uuid,
+    pub merchant_id: Uuid,
+    pub gateway: String,
+    pub percentage: rust_decimal::Decimal,
+    pub fixed_fee: rust_decimal::Decimal,
+    pub currency: String,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+    pub updated_at: chrono::DateTime<chrono::Utc>,
+}
+
+#[derive(Deserialize)]
+pub struct ListPaymentFeeSchedulesParams {
+    pub merchant_id: Uuid,
+}
+
+#[derive(Deserialize)]
+pub struct UpsertPaymentFeeScheduleRequest {
+    pub merchant_id: Uuid,
+    pub gateway: String, // e.g. shopify_payments, paypal, stripe
+    pub percentage: Option<rust_decimal::Decimal>, // Percent of each transaction, defaults to 0
+    pub fixed_fee: Option<rust_decimal::Decimal>, // Per transaction, defaults to 0
+    pub currency: String, // Currency of fixed_fee
+}
+
+#[derive(Deserialize)]
+pub struct UpdatePaymentFeeScheduleRequest {
+    pub percentage: Option<rust_decimal::Decimal>,
+    pub fixed_fee: Option<rust_decimal::Decimal>,
+    pub currency: Option<String>,
+}
+
+// Manual Costs
+#[derive(Serialize, Deserialize, Clone, sqlx::FromRow)]
+pub struct ManualCost {
+    pub id: Uuid,
+    pub merchant_id: Uuid,
+    pub name: String,
+    pub category: Option<String>,
+    pub cost_type: String,
+    pub amount: rust_decimal::Decimal,
+    pub currency: String,
+    pub starts_on: chrono::NaiveDate,
+    pub ends_on: Option<chrono::NaiveDate>,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+    pub updated_at: chrono::DateTime<chrono::Utc>,
+}
+
+#[derive(Deserialize)]
+pub struct ListManualCostsParams {
+    pub merchant_id: Uuid,
+    pub cost_type: Option<String>,
+    pub limit: Option<i32>,
+    pub offset: Option<i32>,
+}
+
+#[derive(Deserialize)]
+pub struct CreateManualCostRequest {
+    pub merchant_id: Uuid,
+    pub name: String,
+    pub category: Option<String>,
+    pub cost_type: String, // one_off|monthly|per_order|percent_of_revenue
+    pub amount: rust_decimal::Decimal,
+    pub currency: String,
+    pub starts_on: chrono::NaiveDate,
+    pub ends_on: Option<chrono::NaiveDate>,
+}
+
+#[derive(Deserialize)]
+pub struct UpdateManualCostRequest {
+    pub name: Option<String>,
+    pub category: Option<String>,
+    pub amount: Option<rust_decimal::Decimal>,
+    pub starts_on: Option<chrono::NaiveDate>,
+    pub ends_on: Option<chrono::NaiveDate>,
+}
+
+#[derive(Serialize)]
+pub struct ManualCostListResponse {
+    pub manual_costs: Vec<ManualCost>,
+    pub total: i64,
+    pub limit: i32,
+    pub offset: i32,
+}
+
+// Profit Timeseries
+#[derive(Deserialize)]
+pub struct ProfitTimeseriesParams {
+    pub merchant_id: Uuid,
+    pub start: chrono::NaiveDate, // inclusive, in the merchant's timezone
+    pub end: chrono::NaiveDate, // inclusive, in the merchant's timezone
+    pub granularity: Option<String>, // day|week|month, defaults to day
+}
+
+#[derive(Serialize, sqlx::FromRow)]
+pub struct ProfitBucket {
+    pub period_start: chrono::NaiveDate,
+    pub period_end: chrono::NaiveDate,
+    pub shopify_revenue: rust_decimal::Decimal,
+    pub refunds: rust_decimal::Decimal,
+    pub shopify_product_cost: rust_decimal::Decimal,
+    pub returned_product_cost: rust_decimal::Decimal,
+    pub ad_cost: rust_decimal::Decimal,
+    pub courier_cost: rust_decimal::Decimal,
+    pub payment_fees: rust_decimal::Decimal,
+    pub manual_cost: rust_decimal::Decimal,
+    pub profit: rust_decimal::Decimal,
+}
+
+#[derive(Serialize)]
+pub struct ProfitTimeseriesResponse {
+    pub merchant_id: Uuid,
+    pub granularity: String,
+    pub timezone: String,
+    pub buckets: Vec<ProfitBucket>,
+    pub warnings: Vec<String>,
+}
+
 // Authentication Types
 #[derive(Deserialize)]
 pub struct LoginRequest {
@@ -0,0 +1,199 @@
+use crate::http::{types::*, ApiContext, AppError, AppResult};
+use axum::{
+    extract::{Path, Query},
+    routing::get,
+    Extension, Json, Router,
+};
+
+/// Insert or update a variant by its Shopify ID (can be used by HTTP handlers and tests)
+///
+/// When the variant carries an inventory item, the matching `inventory_items`
+/// row is upserted in the same transaction so COGS lookups can resolve it.
+pub async fn upsert_variant(
+    db: &sqlx::PgPool,
+    payload: UpsertVariantRequest,
+) -> Result<Variant, AppError> {
+    let mut tx = db.begin().await?;
+
+    let variant = sqlx::query_as::<_, Variant>(
+        r#"
+        INSERT INTO variants (
+            merchant_id, shopify_variant_id, shopify_product_id, sku, title, barcode,
+            weight, weight_unit, price, shopify_inventory_item_id
+        )
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
+        ON CONFLICT (merchant_id, shopify_variant_id) DO UPDATE SET
+            shopify_product_id = EXCLUDED.shopify_product_id,
+            sku = EXCLUDED.sku,
+            title = EXCLUDED.title,
+            barcode = EXCLUDED.barcode,
+            weight = EXCLUDED.weight,
+            weight_unit = EXCLUDED.weight_unit,
+            price = EXCLUDED.price,
+            shopify_inventory_item_id = EXCLUDED.shopify_inventory_item_id,
+            updated_at = NOW()
+        RETURNING id, merchant_id, shopify_variant_id, shopify_product_id, sku, title, barcode,
+                  weight, weight_unit, price, shopify_inventory_item_id, created_at, updated_at
+        "#,
+    )
+    .bind(payload.merchant_id)
+    .bind(payload.shopify_variant_id)
+    .bind(payload.shopify_product_id)
+    .bind(payload.sku)
+    .bind(payload.title)
+    .bind(payload.barcode)
+    .bind(payload.weight)
+    .bind(payload.weight_unit)
+    .bind(payload.price)
+    .bind(payload.shopify_inventory_item_id)
+    .fetch_one(&mut *tx)
+    .await?;
+
+    if let Some(inventory_item_id) = variant.shopify_inventory_item_id {
+        sqlx::query(
+            r#"
+            INSERT INTO inventory_items (merchant_id, shopify_inventory_item_id, shopify_variant_id)
+            VALUES ($1, $2, $3)
+            ON CONFLICT (merchant_id, shopify_inventory_item_id) DO UPDATE SET
+                shopify_variant_id = EXCLUDED.shopify_variant_id,
+                updated_at = NOW()
+            "#,
+        )
+        .bind(variant.merchant_id)
+        .bind(inventory_item_id)
+        .bind(variant.shopify_variant_id)
+        .execute(&mut *tx)
+        .await?;
+    }
+
+    tx.commit().await?;
+
+    Ok(variant)
+}
+
+pub fn variants_router() -> Router {
+    Router::new()
+        .route("/variants", get(list_variants).post(upsert_variant_handler))
+        .route("/variants/:id", get(get_variant))
+}
+
+async fn list_variants(
+    Extension(ctx): Extension<ApiContext>,
+    Query(params): Query<ListVariantsParams>,
+) -> AppResult<VariantListResponse> {
+    eprintln!(
+        "Listing variants: merchant_id={}, shopify_product_id={:?}, limit={:?}, offset={:?}",
+        params.merchant_id, params.shopify_product_id, params.limit, params.offset
+    );
+
+    let limit = params.limit.unwrap_or(50).min(100);
+    let offset = params.offset.unwrap_or(0);
+
+    // Get total count
+    let total: i64 = sqlx::query_scalar::<_, Option<i64>>(
+        r#"
+        SELECT COUNT(*) as count
+        FROM variants
+        WHERE merchant_id = $1
+            AND ($2::bigint IS NULL OR shopify_product_id = $2)
+            AND ($3::text IS NULL OR sku = $3)
+        "#,
+    )
+    .bind(params.merchant_id)
+    .bind(params.shopify_product_id)
+    .bind(&params.sku)
+    .fetch_one(&ctx.db)
+    .await?
+    .unwrap_or(0);
+
+    // Get variants
+    let variants = sqlx::query_as::<_, Variant>(
+        r#"
+        SELECT
+            id,
+            merchant_id,
+            shopify_variant_id,
+            shopify_product_id,
+            sku,
+            title,
+            barcode,
+            weight,
+            weight_unit,
+            price,
+            shopify_inventory_item_id,
+            created_at,
+            updated_at
+        FROM variants
+        WHERE merchant_id = $1
+            AND ($2::bigint IS NULL OR shopify_product_id = $2)
+            AND ($3::text IS NULL OR sku = $3)
+        ORDER BY shopify_product_id, created_at
+        LIMIT $4 OFFSET $5
+        "#,
+    )
+    .bind(params.merchant_id)
+    .bind(params.shopify_product_id)
+    .bind(params.sku)
+    .bind(limit)
+    .bind(offset)
+    .fetch_all(&ctx.db)
+    .await?;
+
+    eprintln!("Found {} variants (total: {})", variants.len(), total);
+
+    Ok(Json(VariantListResponse {
+        variants,
+        total,
+        limit,
+        offset,
+    }))
+}
+
+async fn get_variant(
+    Extension(ctx): Extension<ApiContext>,
+    Path(id): Path<uuid::Uuid>,
+) -> AppResult<Variant> {
+    eprintln!("Getting variant: id={}", id);
+
+    let variant = sqlx::query_as::<_, Variant>(
+        r#"
+        SELECT
+            id,
+            merchant_id,
+            shopify_variant_id,
+            shopify_product_id,
+            sku,
+            title,
+            barcode,
+            weight,
+            weight_unit,
+            price,
+            shopify_inventory_item_id,
+            created_at,
+            updated_at
+        FROM variants
+        WHERE id = $1
+        "#,
+    )
+    .bind(id)
+    .fetch_optional(&ctx.db)
+    .await?
+    .ok_or(AppError::NotFound)?;
+
+    Ok(Json(variant))
+}
+
+async fn upsert_variant_handler(
+    Extension(ctx): Extension<ApiContext>,
+    Json(payload): Json<UpsertVariantRequest>,
+) -> AppResult<Variant> {
+    eprintln!(
+        "Upserting variant: merchant_id={}, shopify_variant_id={}, sku={:?}",
+        payload.merchant_id, payload.shopify_variant_id, payload.sku
+    );
+
+    let variant = upsert_variant(&ctx.db, payload).await?;
+
+    eprintln!("Variant upserted successfully: id={}", variant.id);
+    Ok(Json(variant))
+}
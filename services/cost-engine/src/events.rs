@@ -0,0 +1,390 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::Arc;
+use std::time::Duration;
+
+use chrono::{Days, NaiveDate, Utc};
+use lib_shopify::kafka::{
+    AdSpendImported, DecodeEvent, Encoding, EventConsumer, EventEnvelope, ShopifyChangeEvent,
+};
+use rdkafka::producer::FutureProducer;
+use sqlx::PgPool;
+use tokio::time::Instant;
+use uuid::Uuid;
+
+use crate::snapshots::{self, RecomputeSummary};
+
+/// How long to keep collecting events after the first one of a batch
+const BATCH_WINDOW: Duration = Duration::from_millis(500);
+
+/// Most events recomputed together
+const MAX_BATCH_SIZE: usize = 100;
+
+/// An event that changes the inputs of profit snapshots
+#[derive(Debug, Clone, PartialEq)]
+pub enum ChangeEvent {
+    Shopify(ShopifyChangeEvent),
+    AdSpendImported(EventEnvelope<AdSpendImported>),
+}
+
+impl ChangeEvent {
+    pub fn merchant_id(&self) -> Uuid {
+        match self {
+            Self::Shopify(event) => event.merchant_id(),
+            Self::AdSpendImported(envelope) => envelope.merchant_id,
+        }
+    }
+}
+
+impl DecodeEvent for ChangeEvent {
+    fn decode_event(bytes: &[u8], encoding: Encoding) -> anyhow::Result<Self> {
+        // Ad spend imports are the only events besides the Shopify changes
+        match EventEnvelope::<AdSpendImported>::decode(bytes, encoding) {
+            Ok(envelope) => Ok(Self::AdSpendImported(envelope)),
+            Err(_) => ShopifyChangeEvent::decode_event(bytes, encoding).map(Self::Shopify),
+        }
+    }
+}
+
+/// Recompute the snapshots touched by Shopify changes and ad spend imports as
+/// they arrive.
+///
+/// Events are collected in short batches so that a sync of many orders on the
+/// same day recomputes that day once. The periodic refresh still runs, which
+/// covers events lost to a crash or a missing Kafka.
+pub async fn consume_changes(
+    db: PgPool,
+    producer: Option<Arc<FutureProducer>>,
+    consumer: EventConsumer<ChangeEvent>,
+    lookback_days: u64,
+) {
+    loop {
+        let first = match consumer.recv().await {
+            Ok(event) => event,
+            Err(e) => {
+                eprintln!("❌ Failed to receive change event: {}", e);
+                tokio::time::sleep(Duration::from_secs(1)).await;
+                continue;
+            }
+        };
+
+        let mut batch = vec![first];
+        let deadline = Instant::now() + BATCH_WINDOW;
+        while batch.len() < MAX_BATCH_SIZE {
+            match tokio::time::timeout_at(deadline, consumer.recv()).await {
+                Ok(Ok(event)) => batch.push(event),
+                Ok(Err(e)) => {
+                    eprintln!("❌ Failed to receive change event: {}", e);
+                    break;
+                }
+                Err(_) => break,
+            }
+        }
+
+        let summary = recompute_affected(&db, producer.as_deref(), &batch, lookback_days).await;
+        println!(
+            "⚡ Recomputed {} snapshots for {} merchants after {} change events",
+            summary.snapshots,
+            summary.merchants,
+            batch.len()
+        );
+    }
+}
+
+/// Recompute the merchant-days a batch of events touches.
+///
+/// Orders and refunds touch the local day they were processed on, and ad spend
+/// imports the days they cover. Product changes, and orders without a
+/// processing time, may touch any recent day, so they refresh the merchant's
+/// last `lookback_days` days instead; older days of that merchant are still
+/// recomputed.
+///
+/// The consumer has already committed the batch, so failures are logged per
+/// merchant-day (or imported range) and the remaining days are still recomputed.
+async fn recompute_affected(
+    db: &PgPool,
+    producer: Option<&FutureProducer>,
+    batch: &[ChangeEvent],
+    lookback_days: u64,
+) -> RecomputeSummary {
+    let mut days = BTreeSet::new();
+    let mut ranges = BTreeSet::new();
+    let mut recent = BTreeSet::new();
+
+    for event in batch {
+        let merchant_id = event.merchant_id();
+        let processed_at = match event {
+            ChangeEvent::Shopify(ShopifyChangeEvent::OrderUpserted(envelope)) => {
+                envelope.payload.processed_at
+            }
+            ChangeEvent::Shopify(ShopifyChangeEvent::RefundCreated(envelope)) => {
+                envelope.payload.processed_at
+            }
+            ChangeEvent::Shopify(ShopifyChangeEvent::ProductUpserted(_)) => None,
+            // Spend days are already local calendar days
+            ChangeEvent::AdSpendImported(envelope) => {
+                let AdSpendImported {
+                    start_date,
+                    end_date,
+                    ..
+                } = envelope.payload;
+                if start_date <= end_date {
+                    ranges.insert((merchant_id, start_date, end_date));
+                }
+                continue;
+            }
+        };
+
+        match processed_at {
+            Some(processed_at) => {
+                match snapshots::local_date(db, merchant_id, processed_at).await {
+                    Ok(Some(day)) => {
+                        days.insert((merchant_id, day));
+                    }
+                    // Unknown merchants (e.g. deleted since) have nothing to recompute
+                    Ok(None) => {}
+                    Err(e) => eprintln!(
+                        "✗ Error resolving the local day of {} for merchant {}: {}",
+                        processed_at, merchant_id, e
+                    ),
+                }
+            }
+            None => {
+                recent.insert(merchant_id);
+            }
+        }
+    }
+
+    let mut summary = RecomputeSummary::default();
+    let mut merchants = BTreeSet::new();
+    // Day before the first day each refreshed merchant's lookback covered; the
+    // boundary day itself is recomputed again in case midnight passed meanwhile
+    let mut covered_after = BTreeMap::new();
+
+    for merchant_id in &recent {
+        let today = match snapshots::local_date(db, *merchant_id, Utc::now()).await {
+            Ok(Some(today)) => today,
+            Ok(None) => continue,
+            Err(e) => {
+                eprintln!(
+                    "✗ Error resolving today for merchant {}: {}",
+                    merchant_id, e
+                );
+                continue;
+            }
+        };
+
+        match snapshots::recompute_recent(db, producer, Some(*merchant_id), lookback_days).await {
+            // recompute_recent logs the merchant's own failure and counts it out
+            Ok(refreshed) if refreshed.merchants > 0 => {
+                summary.snapshots += refreshed.snapshots;
+                merchants.insert(*merchant_id);
+                covered_after.insert(*merchant_id, today - Days::new(lookback_days));
+            }
+            Ok(_) => {}
+            Err(e) => eprintln!(
+                "✗ Error recomputing recent snapshots for merchant {}: {}",
+                merchant_id, e
+            ),
+        }
+    }
+
+    for &(merchant_id, start, end) in &ranges {
+        // Days after the lookback refresh's first are already covered
+        let end = match covered_after.get(&merchant_id) {
+            Some(after) if start > *after => continue,
+            Some(after) => end.min(*after),
+            None => end,
+        };
+        match snapshots::recompute_snapshots(db, producer, merchant_id, start, end).await {
+            Ok(snapshots) => {
+                summary.snapshots += snapshots;
+                merchants.insert(merchant_id);
+            }
+            Err(e) => eprintln!(
+                "✗ Error recomputing the {} to {} snapshots for merchant {}: {}",
+                start, end, merchant_id, e
+            ),
+        }
+    }
+
+    for (merchant_id, day) in days {
+        // Already covered by the lookback refresh or an imported range
+        if covered_after
+            .get(&merchant_id)
+            .is_some_and(|after| day > *after)
+            || in_ranges(&ranges, merchant_id, day)
+        {
+            continue;
+        }
+        match snapshots::recompute_snapshots(db, producer, merchant_id, day, day).await {
+            Ok(snapshots) => {
+                summary.snapshots += snapshots;
+                merchants.insert(merchant_id);
+            }
+            Err(e) => eprintln!(
+                "✗ Error recomputing the {} snapshot for merchant {}: {}",
+                day, merchant_id, e
+            ),
+        }
+    }
+
+    summary.merchants = merchants.len();
+    summary
+}
+
+/// Whether an imported ad spend range of the merchant covers `day`
+fn in_ranges(
+    ranges: &BTreeSet<(Uuid, NaiveDate, NaiveDate)>,
+    merchant_id: Uuid,
+    day: NaiveDate,
+) -> bool {
+    ranges
+        .iter()
+        .any(|&(id, start, end)| id == merchant_id && start <= day && day <= end)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{create_test_merchant, setup_test_db};
+    use chrono::DateTime;
+    use lib_shopify::kafka::{OrderUpserted, ProductUpserted, ProfitRecomputed, RefundCreated};
+
+    fn utc(at: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(at)
+            .unwrap()
+            .with_timezone(&Utc)
+    }
+
+    fn order(merchant_id: Uuid, shopify_order_id: i64, processed_at: &str) -> ChangeEvent {
+        ChangeEvent::Shopify(ShopifyChangeEvent::OrderUpserted(EventEnvelope::new(
+            merchant_id,
+            OrderUpserted {
+                shopify_order_id,
+                processed_at: Some(utc(processed_at)),
+            },
+        )))
+    }
+
+    fn ad_spend(merchant_id: Uuid, start_date: NaiveDate, end_date: NaiveDate) -> ChangeEvent {
+        ChangeEvent::AdSpendImported(EventEnvelope::new(
+            merchant_id,
+            AdSpendImported {
+                start_date,
+                end_date,
+                rows_imported: 4,
+                campaigns: 2,
+            },
+        ))
+    }
+
+    async fn snapshot_days(db: &PgPool, merchant_id: Uuid) -> Vec<NaiveDate> {
+        sqlx::query_scalar(
+            "SELECT snapshot_date FROM profit_snapshots WHERE merchant_id = $1 ORDER BY 1",
+        )
+        .bind(merchant_id)
+        .fetch_all(db)
+        .await
+        .unwrap()
+    }
+
+    #[test]
+    fn test_change_events_decode_from_both_topics() {
+        let merchant_id = Uuid::new_v4();
+        let jan_1 = NaiveDate::from_ymd_opt(2024, 1, 1).unwrap();
+
+        let envelope = EventEnvelope::new(
+            merchant_id,
+            ProductUpserted {
+                shopify_product_id: 5,
+            },
+        );
+        let bytes = envelope.encode(Encoding::MessagePack).unwrap();
+        assert_eq!(
+            ChangeEvent::decode_event(&bytes, Encoding::MessagePack).unwrap(),
+            ChangeEvent::Shopify(ShopifyChangeEvent::ProductUpserted(envelope))
+        );
+
+        let event = ad_spend(merchant_id, jan_1, jan_1);
+        let ChangeEvent::AdSpendImported(envelope) = &event else {
+            unreachable!()
+        };
+        let bytes = envelope.encode(Encoding::Json).unwrap();
+        assert_eq!(
+            ChangeEvent::decode_event(&bytes, Encoding::Json).unwrap(),
+            event
+        );
+
+        // Events of other topics are refused
+        let envelope = EventEnvelope::new(
+            merchant_id,
+            ProfitRecomputed {
+                start_date: jan_1,
+                end_date: jan_1,
+                snapshots: 1,
+            },
+        );
+        let bytes = envelope.encode(Encoding::Json).unwrap();
+        assert!(ChangeEvent::decode_event(&bytes, Encoding::Json).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_recompute_affected_batches_days() {
+        let db = setup_test_db().await.expect(
+            "Failed to setup test database. Make sure PostgreSQL is running and accessible.",
+        );
+        let merchant_id = create_test_merchant(&db, "America/New_York").await;
+        let jan_1 = NaiveDate::from_ymd_opt(2024, 1, 1).unwrap();
+        let jan_2 = NaiveDate::from_ymd_opt(2024, 1, 2).unwrap();
+        let jan_3 = NaiveDate::from_ymd_opt(2024, 1, 3).unwrap();
+
+        // Both orders fall on Jan 1 in New York, and the refund's day is within the
+        // imported spend; events of unknown merchants are ignored
+        let batch = vec![
+            order(merchant_id, 1, "2024-01-01T15:00:00Z"),
+            order(merchant_id, 2, "2024-01-02T03:00:00Z"),
+            ChangeEvent::Shopify(ShopifyChangeEvent::RefundCreated(EventEnvelope::new(
+                merchant_id,
+                RefundCreated {
+                    shopify_order_id: 1,
+                    shopify_refund_id: 10,
+                    processed_at: Some(utc("2024-01-03T15:00:00Z")),
+                },
+            ))),
+            ad_spend(merchant_id, jan_2, jan_3),
+            order(Uuid::new_v4(), 3, "2024-01-01T15:00:00Z"),
+        ];
+
+        let summary = recompute_affected(&db, None, &batch, 7).await;
+        assert_eq!(summary.merchants, 1);
+        assert_eq!(summary.snapshots, 3);
+        assert_eq!(
+            snapshot_days(&db, merchant_id).await,
+            vec![jan_1, jan_2, jan_3]
+        );
+
+        // Product changes refresh the lookback; older days are still recomputed
+        let batch = vec![
+            ChangeEvent::Shopify(ShopifyChangeEvent::ProductUpserted(EventEnvelope::new(
+                merchant_id,
+                ProductUpserted {
+                    shopify_product_id: 5,
+                },
+            ))),
+            order(merchant_id, 1, "2024-01-01T15:00:00Z"),
+        ];
+
+        let summary = recompute_affected(&db, None, &batch, 1).await;
+        assert_eq!(summary.merchants, 1);
+        assert_eq!(summary.snapshots, 3);
+
+        let today = snapshots::local_date(&db, merchant_id, Utc::now())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            snapshot_days(&db, merchant_id).await,
+            vec![jan_1, jan_2, jan_3, today - Days::new(1), today]
+        );
+    }
+}
@@ -1,151 +1,287 @@
 use anyhow::Context;
-use chrono::{DateTime, Utc};
-use rust_decimal::Decimal;
+use axum::{
+    Router,
+    extract::{Query, State},
+    http::StatusCode,
+    response::Json,
+    routing::{get, post},
+};
+use chrono::NaiveDate;
+use clap::{Parser, Subcommand};
+use serde::{Deserialize, Serialize};
 use sqlx::PgPool;
+use sqlx::postgres::PgPoolOptions;
+use std::sync::Arc;
+use std::time::Duration;
 use uuid::Uuid;
 
-pub struct CostEngineConfig {
-    pub database_url: String,
-    pub api_keys: Vec<String>,
-    pub mapping_table_path: String,
-    pub manual_data_path: String,
+mod events;
+mod snapshots;
+#[cfg(test)]
+mod test_utils;
+
+use lib_shopify::kafka::{AD_SPEND_TOPIC, EventConsumer, SHOPIFY_CHANGES_TOPIC, create_producer};
+use rdkafka::producer::FutureProducer;
+use snapshots::{ProfitSnapshot, RecomputeSummary};
+
+#[derive(Parser, Debug)]
+#[command(author, version, about, long_about = None)]
+struct Args {
+    /// PostgreSQL database URL (the auth API's database, which owns the schema)
+    #[arg(long, env = "DATABASE_URL")]
+    database_url: String,
+
+    /// HTTP server port
+    #[arg(long, default_value = "8082", env = "HTTP_PORT")]
+    http_port: u16,
+
+    /// Seconds between snapshot refreshes
+    #[arg(long, default_value = "300", env = "SNAPSHOT_INTERVAL_SECS")]
+    interval_secs: u64,
+
+    /// Days before today that each refresh recomputes
+    #[arg(long, default_value = "7", env = "SNAPSHOT_LOOKBACK_DAYS")]
+    lookback_days: u64,
+
+    /// Kafka brokers (comma-separated); enables recomputing on Shopify change and ad
+    /// spend events and publishing profit recompute events
+    #[arg(long, env = "KAFKA_BROKERS")]
+    kafka_brokers: Option<String>,
+
+    /// Kafka consumer group for change events
+    #[arg(long, default_value = "cost-engine", env = "KAFKA_GROUP_ID")]
+    kafka_group_id: String,
+
+    /// Run a one-off command instead of the service
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Recompute snapshots for a date range once and exit
+    Backfill {
+        /// First day to recompute (YYYY-MM-DD, local to each merchant)
+        #[arg(long)]
+        start: NaiveDate,
+
+        /// Last day to recompute (YYYY-MM-DD, local to each merchant)
+        #[arg(long)]
+        end: NaiveDate,
+
+        /// Only recompute this merchant
+        #[arg(long)]
+        merchant_id: Option<Uuid>,
+    },
+}
+
+#[derive(Clone)]
+struct AppContext {
+    db: PgPool,
+    producer: Option<Arc<FutureProducer>>,
 }
 
-pub struct CostEngineProducer {
-    config: CostEngineConfig,
+#[derive(Serialize)]
+struct ErrorResponse {
+    error: String,
 }
 
-#[derive(Debug)]
-pub struct ProfitCalculation {
-    pub shopify_revenue: Decimal,
-    pub shopify_product_cost: Decimal,
-    pub ad_cost: Decimal,
-    pub courier_cost: Decimal,
-    pub manual_cost: Decimal,
-    pub profit: Decimal,
+type ApiResult<T> = Result<Json<T>, (StatusCode, Json<ErrorResponse>)>;
+
+fn api_error(status: StatusCode, error: impl ToString) -> (StatusCode, Json<ErrorResponse>) {
+    (
+        status,
+        Json(ErrorResponse {
+            error: error.to_string(),
+        }),
+    )
+}
+
+#[derive(Serialize)]
+struct HealthResponse {
+    success: bool,
+    message: String,
 }
 
-/// Calculate profit by retrieving data from SQL
-/// 
-/// # Arguments
-/// * `db` - PostgreSQL connection pool
-/// * `merchant_id` - UUID of the merchant to calculate profit for
-/// * `start_date` - Optional start date for the calculation period
-/// * `end_date` - Optional end date for the calculation period
-pub async fn post_calculate(
-    db: &PgPool,
+#[derive(Deserialize)]
+struct ListSnapshotsParams {
     merchant_id: Uuid,
-    start_date: Option<DateTime<Utc>>,
-    end_date: Option<DateTime<Utc>>,
-) -> anyhow::Result<ProfitCalculation> {
-    // 1. Get Shopify revenue (sum of total_price from orders)
-    let shopify_revenue: Decimal = match (start_date, end_date) {
-        (Some(start), Some(end)) => {
-            sqlx::query_scalar(
-                "SELECT COALESCE(SUM(total_price), 0) FROM orders 
-                 WHERE merchant_id = $1 AND processed_at >= $2 AND processed_at <= $3"
-            )
-            .bind(merchant_id)
-            .bind(start)
-            .bind(end)
-            .fetch_one(db)
-            .await?
-        }
-        (Some(start), None) => {
-            sqlx::query_scalar(
-                "SELECT COALESCE(SUM(total_price), 0) FROM orders 
-                 WHERE merchant_id = $1 AND processed_at >= $2"
-            )
-            .bind(merchant_id)
-            .bind(start)
-            .fetch_one(db)
-            .await?
-        }
-        (None, Some(end)) => {
-            sqlx::query_scalar(
-                "SELECT COALESCE(SUM(total_price), 0) FROM orders 
-                 WHERE merchant_id = $1 AND processed_at <= $2"
-            )
-            .bind(merchant_id)
-            .bind(end)
-            .fetch_one(db)
-            .await?
-        }
-        (None, None) => {
-            sqlx::query_scalar(
-                "SELECT COALESCE(SUM(total_price), 0) FROM orders WHERE merchant_id = $1"
-            )
-            .bind(merchant_id)
-            .fetch_one(db)
-            .await?
-        }
-    };
+    start: NaiveDate,
+    end: NaiveDate,
+}
+
+#[derive(Serialize)]
+struct SnapshotListResponse {
+    merchant_id: Uuid,
+    snapshots: Vec<ProfitSnapshot>,
+}
 
-    // 2. Get Shopify product cost (placeholder - would need product cost data)
-    // TODO: Replace with actual product cost query when cost data is available
-    let shopify_product_cost: Decimal = Decimal::ZERO;
+#[derive(Deserialize)]
+struct RecomputeRequest {
+    merchant_id: Option<Uuid>,
+    start: NaiveDate,
+    end: NaiveDate,
+}
 
-    // 3. Get ad cost (placeholder query - would need ad_cost table)
-    // TODO: Replace with actual ad_cost query when ad_cost table is available
-    let ad_cost: Decimal = Decimal::ZERO;
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    let args = Args::parse();
 
-    // 4. Get courier cost (sum of total_shipping_price_set_amount from orders)
-    let courier_cost: Decimal = match (start_date, end_date) {
-        (Some(start), Some(end)) => {
-            sqlx::query_scalar(
-                "SELECT COALESCE(SUM(total_shipping_price_set_amount), 0) FROM orders 
-                 WHERE merchant_id = $1 AND processed_at >= $2 AND processed_at <= $3"
-            )
-            .bind(merchant_id)
-            .bind(start)
-            .bind(end)
-            .fetch_one(db)
-            .await?
-        }
-        (Some(start), None) => {
-            sqlx::query_scalar(
-                "SELECT COALESCE(SUM(total_shipping_price_set_amount), 0) FROM orders 
-                 WHERE merchant_id = $1 AND processed_at >= $2"
-            )
-            .bind(merchant_id)
-            .bind(start)
-            .fetch_one(db)
-            .await?
-        }
-        (None, Some(end)) => {
-            sqlx::query_scalar(
-                "SELECT COALESCE(SUM(total_shipping_price_set_amount), 0) FROM orders 
-                 WHERE merchant_id = $1 AND processed_at <= $2"
-            )
-            .bind(merchant_id)
-            .bind(end)
-            .fetch_one(db)
-            .await?
+    let db = PgPoolOptions::new()
+        .max_connections(10)
+        .connect(&args.database_url)
+        .await
+        .context("could not connect to database_url")?;
+
+    let producer = match &args.kafka_brokers {
+        Some(brokers) => Some(create_producer(brokers).context("Failed to create Kafka producer")?),
+        None => None,
+    };
+
+    match args.command {
+        Some(Command::Backfill {
+            start,
+            end,
+            merchant_id,
+        }) => {
+            let summary =
+                snapshots::recompute_range(&db, producer.as_deref(), merchant_id, start, end)
+                    .await?;
+            println!(
+                "✓ Recomputed {} snapshots for {} merchants ({} to {})",
+                summary.snapshots, summary.merchants, start, end
+            );
+            Ok(())
         }
-        (None, None) => {
-            sqlx::query_scalar(
-                "SELECT COALESCE(SUM(total_shipping_price_set_amount), 0) FROM orders 
-                 WHERE merchant_id = $1"
+        None => serve(db, producer, &args).await,
+    }
+}
+
+async fn serve(
+    db: PgPool,
+    producer: Option<Arc<FutureProducer>>,
+    args: &Args,
+) -> anyhow::Result<()> {
+    let Args {
+        http_port,
+        interval_secs,
+        lookback_days,
+        ..
+    } = *args;
+
+    // Main loop: refresh recent snapshots of every merchant
+    let loop_db = db.clone();
+    let loop_producer = producer.clone();
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(Duration::from_secs(interval_secs.max(1)));
+        loop {
+            interval.tick().await;
+            match snapshots::recompute_recent(
+                &loop_db,
+                loop_producer.as_deref(),
+                None,
+                lookback_days,
             )
-            .bind(merchant_id)
-            .fetch_one(db)
-            .await?
+            .await
+            {
+                Ok(summary) => println!(
+                    "🔄 Refreshed {} snapshots for {} merchants",
+                    summary.snapshots, summary.merchants
+                ),
+                Err(e) => eprintln!("❌ Snapshot refresh failed: {}", e),
+            }
         }
-    };
+    });
+
+    // Recompute the days touched by synced Shopify changes and ad spend imports as they arrive
+    if let Some(brokers) = &args.kafka_brokers {
+        let topics = [SHOPIFY_CHANGES_TOPIC, AD_SPEND_TOPIC];
+        let consumer = EventConsumer::new(brokers, &args.kafka_group_id, &topics)
+            .context("Failed to create Kafka consumer")?;
+        tokio::spawn(events::consume_changes(
+            db.clone(),
+            producer.clone(),
+            consumer,
+            lookback_days,
+        ));
+        println!("📥 Consuming change events from {}", topics.join(", "));
+    }
+
+    let app = Router::new()
+        .route("/health", get(health_check))
+        .route("/api/v1/profit/snapshots", get(list_snapshots))
+        .route("/api/v1/profit/snapshots/recompute", post(recompute))
+        .with_state(AppContext { db, producer });
+
+    let listener = tokio::net::TcpListener::bind(format!("0.0.0.0:{}", http_port))
+        .await
+        .context("Failed to bind HTTP server")?;
 
-    // 5. Get manual cost (placeholder query - would need manual_cost table)
-    // TODO: Replace with actual manual_cost query when manual_cost table is available
-    let manual_cost: Decimal = Decimal::ZERO;
+    println!("🚀 Cost engine running on port {}", http_port);
+    println!(
+        "🔄 Refreshing the last {} days of snapshots every {}s",
+        lookback_days, interval_secs
+    );
+    println!("📡 Health check: http://localhost:{}/health", http_port);
 
-    // Calculate profit
-    let profit = shopify_revenue - shopify_product_cost - ad_cost - courier_cost - manual_cost;
+    axum::serve(listener, app).await?;
 
-    Ok(ProfitCalculation {
-        shopify_revenue,
-        shopify_product_cost,
-        ad_cost,
-        courier_cost,
-        manual_cost,
-        profit,
+    Ok(())
+}
+
+async fn health_check() -> Json<HealthResponse> {
+    Json(HealthResponse {
+        success: true,
+        message: "Service is healthy".to_string(),
     })
 }
+
+async fn list_snapshots(
+    State(ctx): State<AppContext>,
+    Query(params): Query<ListSnapshotsParams>,
+) -> ApiResult<SnapshotListResponse> {
+    if params.start > params.end {
+        return Err(api_error(
+            StatusCode::BAD_REQUEST,
+            "start must not be after end",
+        ));
+    }
+
+    let snapshots =
+        snapshots::list_snapshots(&ctx.db, params.merchant_id, params.start, params.end)
+            .await
+            .map_err(|e| api_error(StatusCode::INTERNAL_SERVER_ERROR, e))?;
+
+    Ok(Json(SnapshotListResponse {
+        merchant_id: params.merchant_id,
+        snapshots,
+    }))
+}
+
+async fn recompute(
+    State(ctx): State<AppContext>,
+    Json(req): Json<RecomputeRequest>,
+) -> ApiResult<RecomputeSummary> {
+    if req.start > req.end {
+        return Err(api_error(
+            StatusCode::BAD_REQUEST,
+            "start must not be after end",
+        ));
+    }
+
+    let summary = snapshots::recompute_range(
+        &ctx.db,
+        ctx.producer.as_deref(),
+        req.merchant_id,
+        req.start,
+        req.end,
+    )
+    .await
+    .map_err(|e| api_error(StatusCode::INTERNAL_SERVER_ERROR, e))?;
+
+    println!(
+        "✓ Recomputed {} snapshots for {} merchants ({} to {})",
+        summary.snapshots, summary.merchants, req.start, req.end
+    );
+    Ok(Json(summary))
+}
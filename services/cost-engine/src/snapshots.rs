@@ -0,0 +1,360 @@
+use chrono::{DateTime, Days, NaiveDate, Utc};
+use lib_shopify::kafka::{Encoding, EventEnvelope, ProfitRecomputed, publish};
+use rdkafka::producer::FutureProducer;
+use rust_decimal::Decimal;
+use serde::Serialize;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+/// Profit of one merchant on one local day, as stored in `profit_snapshots`
+#[derive(Debug, Serialize, sqlx::FromRow)]
+pub struct ProfitSnapshot {
+    pub merchant_id: Uuid,
+    pub snapshot_date: NaiveDate,
+    pub reporting_currency: Option<String>,
+    pub shopify_revenue: Decimal,
+    pub refunds: Decimal,
+    pub shopify_product_cost: Decimal,
+    pub returned_product_cost: Decimal,
+    pub ad_cost: Decimal,
+    pub courier_cost: Decimal,
+    pub payment_fees: Decimal,
+    pub manual_cost: Decimal,
+    pub profit: Decimal,
+    pub computed_at: DateTime<Utc>,
+}
+
+/// Outcome of a recompute over one or more merchants
+#[derive(Debug, Default, Serialize)]
+pub struct RecomputeSummary {
+    pub merchants: usize,
+    pub snapshots: u64,
+}
+
+#[derive(sqlx::FromRow)]
+struct MerchantDay {
+    merchant_id: Uuid,
+    today: NaiveDate,
+}
+
+/// Compute and store the daily snapshots of a merchant between two dates local
+/// to its timezone, replacing any previous snapshot of those days.
+///
+/// Components come from the `merchant_daily_profit` function, which also backs
+/// the auth API's `/profit/timeseries`, so snapshots match the live numbers.
+/// With a `producer`, a `ProfitRecomputed` event announces the new snapshots.
+pub async fn recompute_snapshots(
+    db: &PgPool,
+    producer: Option<&FutureProducer>,
+    merchant_id: Uuid,
+    start: NaiveDate,
+    end: NaiveDate,
+) -> anyhow::Result<u64> {
+    anyhow::ensure!(start <= end, "start must not be after end");
+
+    let result = sqlx::query(
+        r#"
+        INSERT INTO profit_snapshots (
+            merchant_id, snapshot_date, reporting_currency, shopify_revenue, refunds,
+            shopify_product_cost, returned_product_cost, ad_cost, courier_cost, payment_fees,
+            manual_cost, profit
+        )
+        SELECT
+            $1, d.day, cur.reporting_currency, d.shopify_revenue, d.refunds,
+            d.shopify_product_cost, d.returned_product_cost, d.ad_cost, d.courier_cost,
+            d.payment_fees, d.manual_cost, d.profit
+        FROM merchant_daily_profit($1, $2, $3) d
+        LEFT JOIN merchant_currency cur ON cur.merchant_id = $1
+        ON CONFLICT (merchant_id, snapshot_date) DO UPDATE SET
+            reporting_currency = EXCLUDED.reporting_currency,
+            shopify_revenue = EXCLUDED.shopify_revenue,
+            refunds = EXCLUDED.refunds,
+            shopify_product_cost = EXCLUDED.shopify_product_cost,
+            returned_product_cost = EXCLUDED.returned_product_cost,
+            ad_cost = EXCLUDED.ad_cost,
+            courier_cost = EXCLUDED.courier_cost,
+            payment_fees = EXCLUDED.payment_fees,
+            manual_cost = EXCLUDED.manual_cost,
+            profit = EXCLUDED.profit,
+            computed_at = NOW()
+        "#,
+    )
+    .bind(merchant_id)
+    .bind(start)
+    .bind(end)
+    .execute(db)
+    .await?;
+    let snapshots = result.rows_affected();
+
+    if let Some(producer) = producer {
+        let envelope = EventEnvelope::new(
+            merchant_id,
+            ProfitRecomputed {
+                start_date: start,
+                end_date: end,
+                snapshots,
+            },
+        );
+        // The snapshots are stored either way; consumers catch up on the next recompute
+        if let Err(e) = publish(producer, &envelope, Encoding::Json).await {
+            eprintln!("✗ Error publishing {:?}: {}", envelope.payload, e);
+        }
+    }
+
+    Ok(snapshots)
+}
+
+/// Recompute the snapshots of every live merchant (or only `merchant_id`)
+/// between two local dates. Soft-deleted merchants are skipped.
+pub async fn recompute_range(
+    db: &PgPool,
+    producer: Option<&FutureProducer>,
+    merchant_id: Option<Uuid>,
+    start: NaiveDate,
+    end: NaiveDate,
+) -> anyhow::Result<RecomputeSummary> {
+    let merchant_ids = sqlx::query_scalar::<_, Uuid>(
+        r#"
+        SELECT id FROM merchants
+        WHERE deleted_at IS NULL AND ($1::uuid IS NULL OR id = $1)
+        ORDER BY created_at
+        "#,
+    )
+    .bind(merchant_id)
+    .fetch_all(db)
+    .await?;
+
+    let mut summary = RecomputeSummary::default();
+    for merchant_id in merchant_ids {
+        summary.snapshots += recompute_snapshots(db, producer, merchant_id, start, end).await?;
+        summary.merchants += 1;
+    }
+
+    Ok(summary)
+}
+
+/// Recompute the last `lookback_days` days, up to today, of every live
+/// merchant (or only `merchant_id`).
+///
+/// "Today" is local to each merchant. Looking back lets late data (refunds,
+/// courier invoices, attribution runs, FX rates) reach recent snapshots.
+pub async fn recompute_recent(
+    db: &PgPool,
+    producer: Option<&FutureProducer>,
+    merchant_id: Option<Uuid>,
+    lookback_days: u64,
+) -> anyhow::Result<RecomputeSummary> {
+    let merchants = sqlx::query_as::<_, MerchantDay>(
+        r#"
+        SELECT tz.merchant_id, (NOW() AT TIME ZONE tz.timezone)::date AS today
+        FROM merchant_timezone tz
+        JOIN merchants m ON m.id = tz.merchant_id
+        WHERE m.deleted_at IS NULL AND ($1::uuid IS NULL OR tz.merchant_id = $1)
+        "#,
+    )
+    .bind(merchant_id)
+    .fetch_all(db)
+    .await?;
+
+    let mut summary = RecomputeSummary::default();
+    for merchant in merchants {
+        let start = merchant.today - Days::new(lookback_days);
+        match recompute_snapshots(db, producer, merchant.merchant_id, start, merchant.today).await {
+            Ok(snapshots) => {
+                summary.snapshots += snapshots;
+                summary.merchants += 1;
+            }
+            // One failing merchant should not hold back the others
+            Err(e) => eprintln!(
+                "✗ Error recomputing snapshots for merchant {}: {}",
+                merchant.merchant_id, e
+            ),
+        }
+    }
+
+    Ok(summary)
+}
+
+/// The day `at` falls on in the merchant's timezone, `None` for unknown merchants
+pub async fn local_date(
+    db: &PgPool,
+    merchant_id: Uuid,
+    at: DateTime<Utc>,
+) -> anyhow::Result<Option<NaiveDate>> {
+    let day = sqlx::query_scalar::<_, NaiveDate>(
+        r#"
+        SELECT ($2 AT TIME ZONE timezone)::date
+        FROM merchant_timezone
+        WHERE merchant_id = $1
+        "#,
+    )
+    .bind(merchant_id)
+    .bind(at)
+    .fetch_optional(db)
+    .await?;
+
+    Ok(day)
+}
+
+/// Stored snapshots of a merchant between two local dates, oldest first
+pub async fn list_snapshots(
+    db: &PgPool,
+    merchant_id: Uuid,
+    start: NaiveDate,
+    end: NaiveDate,
+) -> anyhow::Result<Vec<ProfitSnapshot>> {
+    let snapshots = sqlx::query_as::<_, ProfitSnapshot>(
+        r#"
+        SELECT merchant_id, snapshot_date, reporting_currency, shopify_revenue, refunds,
+               shopify_product_cost, returned_product_cost, ad_cost, courier_cost, payment_fees,
+               manual_cost, profit, computed_at
+        FROM profit_snapshots
+        WHERE merchant_id = $1 AND snapshot_date >= $2 AND snapshot_date <= $3
+        ORDER BY snapshot_date
+        "#,
+    )
+    .bind(merchant_id)
+    .bind(start)
+    .bind(end)
+    .fetch_all(db)
+    .await?;
+
+    Ok(snapshots)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{create_test_merchant, setup_test_db, upsert_test_order};
+
+    fn utc(at: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(at)
+            .unwrap()
+            .with_timezone(&Utc)
+    }
+
+    #[tokio::test]
+    async fn test_recompute_snapshots_replaces_local_days() {
+        let db = setup_test_db().await.expect(
+            "Failed to setup test database. Make sure PostgreSQL is running and accessible.",
+        );
+        let merchant_id = create_test_merchant(&db, "America/New_York").await;
+
+        // 03:00 UTC on Jan 2 is still Jan 1 in New York
+        upsert_test_order(
+            &db,
+            merchant_id,
+            1,
+            utc("2024-01-01T15:00:00Z"),
+            Decimal::from(100),
+        )
+        .await;
+        upsert_test_order(
+            &db,
+            merchant_id,
+            2,
+            utc("2024-01-02T03:00:00Z"),
+            Decimal::from(40),
+        )
+        .await;
+
+        let jan_1 = NaiveDate::from_ymd_opt(2024, 1, 1).unwrap();
+        let jan_2 = NaiveDate::from_ymd_opt(2024, 1, 2).unwrap();
+        assert_eq!(
+            local_date(&db, merchant_id, utc("2024-01-02T03:00:00Z"))
+                .await
+                .unwrap(),
+            Some(jan_1)
+        );
+        assert_eq!(
+            local_date(&db, Uuid::new_v4(), Utc::now()).await.unwrap(),
+            None
+        );
+
+        let snapshots = recompute_snapshots(&db, None, merchant_id, jan_1, jan_2)
+            .await
+            .unwrap();
+        assert_eq!(snapshots, 2);
+
+        // Recomputing replaces the stored days instead of adding rows
+        upsert_test_order(
+            &db,
+            merchant_id,
+            2,
+            utc("2024-01-02T03:00:00Z"),
+            Decimal::from(60),
+        )
+        .await;
+        let snapshots = recompute_snapshots(&db, None, merchant_id, jan_1, jan_2)
+            .await
+            .unwrap();
+        assert_eq!(snapshots, 2);
+
+        let stored = list_snapshots(&db, merchant_id, jan_1, jan_2)
+            .await
+            .unwrap();
+        let revenue = stored
+            .iter()
+            .map(|s| {
+                (
+                    s.snapshot_date,
+                    s.shopify_revenue,
+                    s.reporting_currency.as_deref(),
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            revenue,
+            vec![
+                (jan_1, Decimal::from(160), Some("USD")),
+                (jan_2, Decimal::ZERO, Some("USD")),
+            ]
+        );
+
+        assert!(
+            recompute_snapshots(&db, None, merchant_id, jan_2, jan_1)
+                .await
+                .is_err()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_recompute_recent_covers_lookback_of_live_merchants() {
+        let db = setup_test_db().await.expect(
+            "Failed to setup test database. Make sure PostgreSQL is running and accessible.",
+        );
+        let merchant_id = create_test_merchant(&db, "Asia/Tokyo").await;
+
+        let summary = recompute_recent(&db, None, Some(merchant_id), 2)
+            .await
+            .unwrap();
+        assert_eq!(summary.merchants, 1);
+        assert_eq!(summary.snapshots, 3);
+
+        // Today is local to the merchant
+        let today = local_date(&db, merchant_id, Utc::now())
+            .await
+            .unwrap()
+            .unwrap();
+        let stored = list_snapshots(&db, merchant_id, today - Days::new(7), today + Days::new(1))
+            .await
+            .unwrap();
+        let days = stored.iter().map(|s| s.snapshot_date).collect::<Vec<_>>();
+        assert_eq!(
+            days,
+            vec![today - Days::new(2), today - Days::new(1), today]
+        );
+
+        // Soft-deleted merchants are skipped
+        sqlx::query("UPDATE merchants SET deleted_at = NOW() WHERE id = $1")
+            .bind(merchant_id)
+            .execute(&db)
+            .await
+            .unwrap();
+        let summary = recompute_recent(&db, None, Some(merchant_id), 2)
+            .await
+            .unwrap();
+        assert_eq!(summary.merchants, 0);
+        assert_eq!(summary.snapshots, 0);
+    }
+}
@@ -6,32 +6,36 @@ use axum::{
     routing::{get, post},
     Router,
 };
+use chrono::{DateTime, Utc};
 use clap::Parser;
+use futures::TryStreamExt;
+use rdkafka::producer::FutureProducer;
 use reqwest::Client;
-use std::sync::Arc;
-use uuid::Uuid;
 use rust_decimal::Decimal;
-use chrono::{DateTime, Utc};
-use rdkafka::producer::FutureProducer;
 use serde::{Deserialize, Serialize};
-use tokio_cron_scheduler::{Job, JobScheduler};
+use std::pin::pin;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio_cron_scheduler::JobScheduler;
+use uuid::Uuid;
 
+mod merchants;
 mod shopify;
+mod tokens;
+mod webhooks;
 
-use shopify::{ShopifyClient, ShopifyProduct, ShopifyOrder};
-use lib_shopify::kafka::create_producer;
+use lib_shopify::kafka::{
+    create_producer, publish, Encoding, Event, EventEnvelope, OrderUpserted, ProductUpserted,
+    RefundCreated,
+};
+use merchants::{refresh_merchants, Merchant, Merchants};
+use shopify::{ShopifyClient, ShopifyOrder, ShopifyPriceSet, ShopifyProduct};
+use tokens::TokenCipher;
+use webhooks::{receive_webhook, WebhookDedup};
 
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
 struct Args {
-    /// Shopify store name
-    #[arg(short, long, env = "SHOPIFY_STORE_NAME")]
-    store_name: String,
-
-    /// Shopify access token
-    #[arg(short, long, env = "SHOPIFY_ACCESS_TOKEN")]
-    access_token: String,
-
     /// Shopify API version
     #[arg(short, long, default_value = "2025-10", env = "SHOPIFY_API_VERSION")]
     api_version: String,
@@ -40,10 +44,6 @@ struct Args {
     #[arg(long, default_value = "http://localhost:8080", env = "AUTH_API_URL")]
     auth_api_url: String,
 
-    /// Merchant ID (UUID)
-    #[arg(short, long, env = "MERCHANT_ID")]
-    merchant_id: Uuid,
-
     /// Kafka brokers (comma-separated)
     #[arg(long, env = "KAFKA_BROKERS")]
     kafka_brokers: Option<String>,
@@ -52,22 +52,31 @@ struct Args {
     #[arg(long, default_value = "8081", env = "HTTP_PORT")]
     http_port: u16,
 
-    /// Enable periodic product sync (cron expression, e.g., "0 */6 * * *" for every 6 hours)
-    #[arg(long, env = "PRODUCTS_SYNC_CRON")]
-    products_sync_cron: Option<String>,
+    /// How often to reload the merchants with an active install and their sync schedules
+    /// (app_settings.auto_refresh_cron) from the auth API, in seconds
+    #[arg(long, default_value = "300", env = "MERCHANTS_REFRESH_SECS")]
+    merchants_refresh_secs: u64,
 
-    /// Enable periodic order sync (cron expression, e.g., "0 */1 * * *" for every hour)
-    #[arg(long, env = "ORDERS_SYNC_CRON")]
-    orders_sync_cron: Option<String>,
+    /// Shopify app API secret; enables the webhook endpoint, whose HMAC signatures it verifies
+    #[arg(long, env = "SHOPIFY_WEBHOOK_SECRET")]
+    webhook_secret: Option<String>,
+
+    /// Base64 of the 32-byte key the auth API encrypts Shopify access tokens with
+    #[arg(long, env = "SHOPIFY_TOKEN_KEY")]
+    shopify_token_key: String,
 }
 
 #[derive(Clone)]
 struct AppContext {
-    shopify_client: Arc<ShopifyClient>,
     http_client: Arc<Client>,
     auth_api_url: String,
-    merchant_id: Uuid,
+    api_version: String,
+    merchants: Arc<Merchants>,
+    token_cipher: Arc<TokenCipher>,
+    scheduler: JobScheduler,
     kafka_producer: Option<Arc<FutureProducer>>,
+    webhook_secret: Option<String>,
+    webhook_dedup: Arc<WebhookDedup>,
 }
 
 #[derive(Serialize)]
@@ -78,6 +87,8 @@ struct SyncResponse {
 
 #[derive(Deserialize)]
 struct SyncRequest {
+    /// Merchant to sync; every merchant when omitted
+    merchant_id: Option<Uuid>,
     limit: Option<u32>,
 }
 
@@ -85,12 +96,6 @@ struct SyncRequest {
 async fn main() -> anyhow::Result<()> {
     let args = Args::parse();
 
-    let shopify_client = Arc::new(ShopifyClient::new(
-        args.store_name,
-        args.access_token,
-        args.api_version,
-    ));
-
     let http_client = Arc::new(Client::new());
     let auth_api_url = args.auth_api_url.trim_end_matches('/').to_string();
 
@@ -101,71 +106,75 @@ async fn main() -> anyhow::Result<()> {
         None
     };
 
+    // The auth API lists access tokens encrypted; only this service reads them
+    let token_cipher = TokenCipher::from_base64_key(&args.shopify_token_key)
+        .context("Invalid SHOPIFY_TOKEN_KEY")?;
+
+    // Setup scheduler for periodic syncs; each merchant gets its own job
+    let scheduler = JobScheduler::new().await?;
+    scheduler.start().await?;
+
     let app_context = AppContext {
-        shopify_client: shopify_client.clone(),
         http_client: http_client.clone(),
         auth_api_url: auth_api_url.clone(),
-        merchant_id: args.merchant_id,
+        api_version: args.api_version.clone(),
+        merchants: Arc::new(Merchants::default()),
+        token_cipher: Arc::new(token_cipher),
+        scheduler,
         kafka_producer,
+        webhook_secret: args.webhook_secret.clone(),
+        webhook_dedup: Arc::new(WebhookDedup::default()),
     };
 
-    // Setup scheduler for periodic syncs
-    let scheduler = JobScheduler::new().await?;
-    
-    if let Some(cron) = &args.products_sync_cron {
-        let ctx = app_context.clone();
-        scheduler
-            .add(
-                Job::new_async(cron.as_str(), move |_uuid, _l| {
-                    let ctx = ctx.clone();
-                    Box::pin(async move {
-                        println!("🔄 Scheduled product sync triggered");
-                        if let Err(e) = sync_products_internal(&ctx, None).await {
-                            eprintln!("❌ Scheduled product sync failed: {}", e);
-                        }
-                    })
-                })?
-            )
-            .await?;
-        println!("✅ Product sync scheduled: {}", cron);
-    }
-
-    if let Some(cron) = &args.orders_sync_cron {
-        let ctx = app_context.clone();
-        scheduler
-            .add(
-                Job::new_async(cron.as_str(), move |_uuid, _l| {
-                    let ctx = ctx.clone();
-                    Box::pin(async move {
-                        println!("🔄 Scheduled order sync triggered");
-                        if let Err(e) = sync_orders_internal(&ctx, None).await {
-                            eprintln!("❌ Scheduled order sync failed: {}", e);
-                        }
-                    })
-                })?
-            )
-            .await?;
-        println!("✅ Order sync scheduled: {}", cron);
-    }
-
-    scheduler.start().await?;
+    // Load the merchants with an active install, then follow installs,
+    // uninstalls and schedule changes
+    let ctx = app_context.clone();
+    let refresh_every = Duration::from_secs(args.merchants_refresh_secs.max(1));
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(refresh_every);
+        loop {
+            interval.tick().await;
+            if let Err(e) = refresh_merchants(&ctx).await {
+                eprintln!("❌ Failed to refresh merchants: {:#}", e);
+            }
+        }
+    });
 
     // Setup HTTP API
     let app = Router::new()
         .route("/health", get(health_check))
         .route("/api/v1/sync/products", post(trigger_sync_products))
         .route("/api/v1/sync/orders", post(trigger_sync_orders))
+        .route("/api/v1/webhooks", post(receive_webhook))
         .with_state(app_context);
 
     let listener = tokio::net::TcpListener::bind(format!("0.0.0.0:{}", args.http_port))
         .await
         .context("Failed to bind HTTP server")?;
 
-    println!("🚀 Shopify Consumer service running on port {}", args.http_port);
-    println!("📡 Health check: http://localhost:{}/health", args.http_port);
+    println!(
+        "🚀 Shopify Consumer service running on port {}",
+        args.http_port
+    );
+    println!(
+        "📡 Health check: http://localhost:{}/health",
+        args.http_port
+    );
     println!("🔄 Sync endpoints:");
-    println!("   POST http://localhost:{}/api/v1/sync/products", args.http_port);
-    println!("   POST http://localhost:{}/api/v1/sync/orders", args.http_port);
+    println!(
+        "   POST http://localhost:{}/api/v1/sync/products",
+        args.http_port
+    );
+    println!(
+        "   POST http://localhost:{}/api/v1/sync/orders",
+        args.http_port
+    );
+    if args.webhook_secret.is_some() {
+        println!(
+            "📨 Webhooks: POST http://localhost:{}/api/v1/webhooks",
+            args.http_port
+        );
+    }
 
     axum::serve(listener, app).await?;
 
@@ -183,18 +192,28 @@ async fn trigger_sync_products(
     State(ctx): State<AppContext>,
     Json(req): Json<SyncRequest>,
 ) -> Result<Json<SyncResponse>, (StatusCode, Json<SyncResponse>)> {
-    match sync_products_internal(&ctx, req.limit).await {
-        Ok(_) => Ok(Json(SyncResponse {
+    let merchants = requested_merchants(&ctx, req.merchant_id)?;
+
+    let mut failures = Vec::new();
+    for merchant in &merchants {
+        if let Err(e) = sync_products_internal(&ctx, merchant, req.limit).await {
+            failures.push(format!("merchant {}: {}", merchant.merchant_id, e));
+        }
+    }
+
+    if failures.is_empty() {
+        Ok(Json(SyncResponse {
             success: true,
-            message: "Product sync completed".to_string(),
-        })),
-        Err(e) => Err((
+            message: format!("Product sync completed for {} merchant(s)", merchants.len()),
+        }))
+    } else {
+        Err((
             StatusCode::INTERNAL_SERVER_ERROR,
             Json(SyncResponse {
                 success: false,
-                message: format!("Product sync failed: {}", e),
+                message: format!("Product sync failed: {}", failures.join("; ")),
             }),
-        )),
+        ))
     }
 }
 
@@ -202,27 +221,123 @@ async fn trigger_sync_orders(
     State(ctx): State<AppContext>,
     Json(req): Json<SyncRequest>,
 ) -> Result<Json<SyncResponse>, (StatusCode, Json<SyncResponse>)> {
-    match sync_orders_internal(&ctx, req.limit).await {
-        Ok(_) => Ok(Json(SyncResponse {
+    let merchants = requested_merchants(&ctx, req.merchant_id)?;
+
+    let mut failures = Vec::new();
+    for merchant in &merchants {
+        if let Err(e) = sync_orders_internal(&ctx, merchant, req.limit).await {
+            failures.push(format!("merchant {}: {}", merchant.merchant_id, e));
+        }
+    }
+
+    if failures.is_empty() {
+        Ok(Json(SyncResponse {
             success: true,
-            message: "Order sync completed".to_string(),
-        })),
-        Err(e) => Err((
+            message: format!("Order sync completed for {} merchant(s)", merchants.len()),
+        }))
+    } else {
+        Err((
             StatusCode::INTERNAL_SERVER_ERROR,
             Json(SyncResponse {
                 success: false,
-                message: format!("Order sync failed: {}", e),
+                message: format!("Order sync failed: {}", failures.join("; ")),
+            }),
+        ))
+    }
+}
+
+/// The merchant a sync request names, or every merchant when it names none
+fn requested_merchants(
+    ctx: &AppContext,
+    merchant_id: Option<Uuid>,
+) -> Result<Vec<Arc<Merchant>>, (StatusCode, Json<SyncResponse>)> {
+    let Some(merchant_id) = merchant_id else {
+        return Ok(ctx.merchants.all());
+    };
+
+    match ctx.merchants.get(merchant_id) {
+        Some(merchant) => Ok(vec![merchant]),
+        None => Err((
+            StatusCode::NOT_FOUND,
+            Json(SyncResponse {
+                success: false,
+                message: format!("No active install for merchant {}", merchant_id),
             }),
         )),
     }
 }
 
-async fn sync_products_internal(ctx: &AppContext, limit: Option<u32>) -> anyhow::Result<()> {
-    sync_products(&ctx.http_client, &ctx.shopify_client, &ctx.auth_api_url, ctx.merchant_id, limit, ctx).await
+async fn sync_products_internal(
+    ctx: &AppContext,
+    merchant: &Merchant,
+    limit: Option<u32>,
+) -> anyhow::Result<()> {
+    let _sync = ctx
+        .merchants
+        .try_lock_sync(merchant.merchant_id)
+        .with_context(|| {
+            format!(
+                "A sync of merchant {} is already running",
+                merchant.merchant_id
+            )
+        })?;
+
+    sync_products(
+        &ctx.http_client,
+        &merchant.shopify_client,
+        &ctx.auth_api_url,
+        merchant.merchant_id,
+        limit,
+        ctx,
+    )
+    .await
 }
 
-async fn sync_orders_internal(ctx: &AppContext, limit: Option<u32>) -> anyhow::Result<()> {
-    sync_orders(&ctx.http_client, &ctx.shopify_client, &ctx.auth_api_url, ctx.merchant_id, limit, ctx).await
+async fn sync_orders_internal(
+    ctx: &AppContext,
+    merchant: &Merchant,
+    limit: Option<u32>,
+) -> anyhow::Result<()> {
+    let _sync = ctx
+        .merchants
+        .try_lock_sync(merchant.merchant_id)
+        .with_context(|| {
+            format!(
+                "A sync of merchant {} is already running",
+                merchant.merchant_id
+            )
+        })?;
+
+    sync_orders(
+        &ctx.http_client,
+        &merchant.shopify_client,
+        &ctx.auth_api_url,
+        merchant.merchant_id,
+        limit,
+        ctx,
+    )
+    .await
+}
+
+/// Scheduled sync of a merchant's products, then its orders
+async fn sync_merchant(ctx: &AppContext, merchant_id: Uuid) {
+    // Uninstalled since the job was scheduled
+    let Some(merchant) = ctx.merchants.get(merchant_id) else {
+        return;
+    };
+
+    if let Err(e) = sync_products_internal(ctx, &merchant, None).await {
+        eprintln!(
+            "❌ Scheduled product sync of merchant {} failed: {}",
+            merchant_id, e
+        );
+    }
+    if let Err(e) = sync_orders_internal(ctx, &merchant, None).await {
+        eprintln!(
+            "❌ Scheduled order sync of merchant {} failed: {}",
+            merchant_id, e
+        );
+    }
 }
 
 async fn sync_products(
@@ -233,52 +348,42 @@ async fn sync_products(
     limit: Option<u32>,
     app_context: &AppContext,
 ) -> anyhow::Result<()> {
-    let products = shopify_client
-        .get_products(limit, None)
+    let checkpoint = start_sync(http_client, auth_api_url, merchant_id, "products", limit).await?;
+    let mut pages = pin!(shopify_client.products_pages(
+        limit,
+        checkpoint.updated_at_min,
+        checkpoint.cursor.clone(),
+    ));
+    let mut remaining = limit.map(|limit| limit as usize);
+
+    while let Some(page) = pages
+        .try_next()
         .await
-        .context("Failed to fetch products from Shopify")?;
+        .context("Failed to fetch products from Shopify")?
+    {
+        let mut products = page.items;
+        if let Some(remaining) = remaining.as_mut() {
+            products.truncate(*remaining);
+            *remaining -= products.len();
+        }
 
-    println!("Fetched {} products from Shopify", products.len());
+        println!("Fetched {} products from Shopify", products.len());
 
-    for product in products {
-        // Create product via auth API
-        let product_payload = serde_json::json!({
-            "merchant_id": merchant_id,
-            "shopify_product_id": product.id,
-            "title": product.title,
-            "product_type": product.product_type,
-            "status": product.status,
-        });
+        for product in products {
+            sync_product(http_client, auth_api_url, merchant_id, product, app_context).await?;
+        }
 
-        let url = format!("{}/api/v1/products", auth_api_url);
-        let response = http_client
-            .post(&url)
-            .json(&product_payload)
-            .send()
+        checkpoint
+            .save_progress(http_client, auth_api_url, page.next_page_info)
             .await?;
 
-        if response.status().is_success() {
-            println!("✓ Synced product: {} (Shopify ID: {})", product.title, product.id);
-        } else if response.status() == 400 {
-            let error_text = response.text().await?;
-            if error_text.contains("already exists") {
-                println!("⊘ Product {} already exists, skipping", product.id);
-            } else {
-                eprintln!("✗ Error syncing product {}: {}", product.id, error_text);
-            }
-        } else {
-            let error_text = response.text().await?;
-            eprintln!("✗ Error syncing product {}: {}", product.id, error_text);
-        }
-
-        // Sync variants
-        for variant in product.variants {
-            // Note: Variants would need their own endpoint or be handled differently
-            // For now, we'll just log them
-            println!("  - Variant: {} (SKU: {:?})", variant.title, variant.sku);
+        if remaining == Some(0) {
+            break;
         }
     }
 
+    checkpoint.complete(http_client, auth_api_url).await?;
+
     Ok(())
 }
 
@@ -290,67 +395,609 @@ async fn sync_orders(
     limit: Option<u32>,
     app_context: &AppContext,
 ) -> anyhow::Result<()> {
-    let orders = shopify_client
-        .get_orders(limit, None, Some("any"), None)
+    let checkpoint = start_sync(http_client, auth_api_url, merchant_id, "orders", limit).await?;
+    let mut pages = pin!(shopify_client.orders_pages(
+        limit,
+        Some("any"),
+        None,
+        checkpoint.updated_at_min,
+        checkpoint.cursor.clone(),
+    ));
+    let mut remaining = limit.map(|limit| limit as usize);
+
+    while let Some(page) = pages
+        .try_next()
         .await
-        .context("Failed to fetch orders from Shopify")?;
+        .context("Failed to fetch orders from Shopify")?
+    {
+        let mut orders = page.items;
+        if let Some(remaining) = remaining.as_mut() {
+            orders.truncate(*remaining);
+            *remaining -= orders.len();
+        }
+
+        println!("Fetched {} orders from Shopify", orders.len());
 
-    println!("Fetched {} orders from Shopify", orders.len());
+        for order in orders {
+            sync_order(
+                http_client,
+                shopify_client,
+                auth_api_url,
+                merchant_id,
+                &order,
+                app_context,
+            )
+            .await?;
+        }
 
-    for order in orders {
-        // Parse processed_at
-        let processed_at = order.processed_at
+        checkpoint
+            .save_progress(http_client, auth_api_url, page.next_page_info)
+            .await?;
+
+        if remaining == Some(0) {
+            break;
+        }
+    }
+
+    checkpoint.complete(http_client, auth_api_url).await?;
+
+    Ok(())
+}
+
+/// Upsert one product and its variants through the auth API (shared by polled
+/// syncs and webhooks)
+async fn sync_product(
+    http_client: &Client,
+    auth_api_url: &str,
+    merchant_id: Uuid,
+    product: ShopifyProduct,
+    app_context: &AppContext,
+) -> anyhow::Result<()> {
+    // Upsert product via auth API, so changed products are refreshed
+    let product_payload = serde_json::json!({
+        "merchant_id": merchant_id,
+        "title": product.title,
+        "product_type": product.product_type,
+        "status": product.status,
+    });
+
+    let url = format!(
+        "{}/api/v1/products/by-shopify-id/{}",
+        auth_api_url, product.id
+    );
+    let response = http_client.put(&url).json(&product_payload).send().await?;
+
+    // Fail the run so its checkpoint does not move past this product
+    if !response.status().is_success() {
+        let error_text = response.text().await?;
+        anyhow::bail!("Failed to sync product {}: {}", product.id, error_text);
+    }
+    println!(
+        "✓ Synced product: {} (Shopify ID: {})",
+        product.title, product.id
+    );
+
+    // Sync variants (upserted, which also links their inventory items)
+    for variant in product.variants {
+        let variant_payload = serde_json::json!({
+            "merchant_id": merchant_id,
+            "shopify_variant_id": variant.id,
+            "shopify_product_id": variant.product_id,
+            "sku": variant.sku,
+            "title": variant.title,
+            "barcode": variant.barcode,
+            "weight": variant.weight.and_then(|w| Decimal::try_from(w).ok()).map(|d| d.to_string()),
+            "weight_unit": variant.weight_unit,
+            "price": variant.price.parse::<Decimal>().ok().map(|d| d.to_string()),
+            "shopify_inventory_item_id": variant.inventory_item_id,
+        });
+
+        let url = format!("{}/api/v1/variants", auth_api_url);
+        let response = http_client.post(&url).json(&variant_payload).send().await?;
+
+        if !response.status().is_success() {
+            let error_text = response.text().await?;
+            anyhow::bail!("Failed to sync variant {}: {}", variant.id, error_text);
+        }
+        println!(
+            "  ✓ Synced variant: {} (SKU: {:?})",
+            variant.title, variant.sku
+        );
+    }
+
+    publish_change(
+        app_context,
+        merchant_id,
+        ProductUpserted {
+            shopify_product_id: product.id,
+        },
+    )
+    .await;
+
+    Ok(())
+}
+
+/// Upsert one order with its line items, transactions and refunds through the
+/// auth API (shared by polled syncs and webhooks)
+async fn sync_order(
+    http_client: &Client,
+    shopify_client: &ShopifyClient,
+    auth_api_url: &str,
+    merchant_id: Uuid,
+    order: &ShopifyOrder,
+    app_context: &AppContext,
+) -> anyhow::Result<()> {
+    // Parse processed_at
+    let processed_at = order
+        .processed_at
+        .as_ref()
+        .and_then(|s| DateTime::parse_from_rfc3339(s).ok())
+        .map(|dt| dt.with_timezone(&Utc));
+
+    let cancelled_at = order
+        .cancelled_at
+        .as_ref()
+        .and_then(|s| DateTime::parse_from_rfc3339(s).ok())
+        .map(|dt| dt.with_timezone(&Utc));
+
+    // Parse decimal values
+    let subtotal_price = order.subtotal_price.parse::<Decimal>().ok();
+    let total_price = order.total_price.parse::<Decimal>().ok();
+    let total_discounts = order.total_discounts.parse::<Decimal>().ok();
+    let total_shipping_price_set_amount = order
+        .total_shipping_price_set
+        .shop_money
+        .amount
+        .parse::<Decimal>()
+        .ok();
+    let total_tax = order.total_tax.parse::<Decimal>().ok();
+
+    let order_payload = serde_json::json!({
+        "merchant_id": merchant_id,
+        "name": order.name,
+        "processed_at": processed_at.map(|dt| dt.to_rfc3339()),
+        "currency": order.currency,
+        "subtotal_price": subtotal_price.map(|d| d.to_string()),
+        "total_price": total_price.map(|d| d.to_string()),
+        "total_discounts": total_discounts.map(|d| d.to_string()),
+        "total_shipping_price_set_amount": total_shipping_price_set_amount.map(|d| d.to_string()),
+        "total_tax": total_tax.map(|d| d.to_string()),
+        "financial_status": order.financial_status,
+        "shipping_country_code": order
+            .shipping_address
             .as_ref()
-            .and_then(|s| DateTime::parse_from_rfc3339(s).ok())
-            .map(|dt| dt.with_timezone(&Utc));
-
-        // Parse decimal values
-        let subtotal_price = order.subtotal_price.parse::<Decimal>().ok();
-        let total_price = order.total_price.parse::<Decimal>().ok();
-        let total_discounts = order.total_discounts.parse::<Decimal>().ok();
-        let total_shipping_price_set_amount = order.total_shipping_price_set
-            .shop_money
-            .amount
-            .parse::<Decimal>()
-            .ok();
-        let total_tax = order.total_tax.parse::<Decimal>().ok();
-
-        let order_payload = serde_json::json!({
+            .and_then(|address| address.country_code.clone()),
+        "cancelled_at": cancelled_at.map(|dt| dt.to_rfc3339()),
+        "test": order.test,
+        "landing_site": order.landing_site,
+        "referring_site": order.referring_site,
+    });
+
+    // Upsert, so re-synced orders pick up new statuses, cancellations and totals
+    let url = format!("{}/api/v1/orders/by-shopify-id/{}", auth_api_url, order.id);
+    let response = http_client.put(&url).json(&order_payload).send().await?;
+
+    // Fail the run so its checkpoint does not move past this order
+    if !response.status().is_success() {
+        let error_text = response.text().await?;
+        anyhow::bail!("Failed to sync order {}: {}", order.id, error_text);
+    }
+    let upserted: UpsertedOrder = response.json().await?;
+    println!("✓ Synced order: {} (Shopify ID: {})", order.name, order.id);
+
+    // Sync line items (upserted, so existing orders still get theirs refreshed)
+    sync_order_line_items(http_client, auth_api_url, merchant_id, order).await?;
+
+    // Payment transactions carry the gateway that payment fees are charged by.
+    // They are not part of the order, so they are only fetched while the
+    // stored ones may be out of date, which keeps full syncs from making a
+    // request per order.
+    if upserted.transactions_outdated {
+        sync_order_transactions(
+            http_client,
+            shopify_client,
+            auth_api_url,
+            merchant_id,
+            order,
+        )
+        .await?;
+    }
+
+    sync_order_refunds(http_client, auth_api_url, merchant_id, order, app_context).await?;
+
+    publish_change(
+        app_context,
+        merchant_id,
+        OrderUpserted {
+            shopify_order_id: order.id,
+            processed_at,
+        },
+    )
+    .await;
+
+    Ok(())
+}
+
+async fn sync_order_line_items(
+    http_client: &Client,
+    auth_api_url: &str,
+    merchant_id: Uuid,
+    order: &ShopifyOrder,
+) -> anyhow::Result<()> {
+    // The auth API deletes line items missing from the payload, so a line item
+    // that cannot be read fails the whole order instead of being left out
+    let line_items = order
+        .line_items
+        .iter()
+        .map(|item| {
+            let price = item.price.parse::<Decimal>().with_context(|| {
+                format!(
+                    "Line item {} of order {} has an invalid price {:?}",
+                    item.id, order.id, item.price
+                )
+            })?;
+
+            // Prefer the per-allocation amounts; older orders only carry total_discount
+            let total_discount = if item.discount_allocations.is_empty() {
+                item.total_discount
+                    .as_deref()
+                    .and_then(|d| d.parse::<Decimal>().ok())
+                    .unwrap_or_default()
+            } else {
+                item.discount_allocations
+                    .iter()
+                    .filter_map(|a| a.amount.parse::<Decimal>().ok())
+                    .sum()
+            };
+
+            Ok(serde_json::json!({
+                "shopify_line_item_id": item.id,
+                "shopify_product_id": item.product_id,
+                "shopify_variant_id": item.variant_id,
+                "sku": item.sku,
+                "title": item.title,
+                "quantity": item.quantity,
+                "price": price.to_string(),
+                "total_discount": total_discount.to_string(),
+            }))
+        })
+        .collect::<anyhow::Result<Vec<serde_json::Value>>>()?;
+
+    let line_items_payload = serde_json::json!({
+        "merchant_id": merchant_id,
+        "shopify_order_id": order.id,
+        "line_items": line_items,
+    });
+
+    let url = format!("{}/api/v1/order-line-items", auth_api_url);
+    let response = http_client
+        .post(&url)
+        .json(&line_items_payload)
+        .send()
+        .await?;
+
+    if !response.status().is_success() {
+        let error_text = response.text().await?;
+        anyhow::bail!(
+            "Failed to sync line items for order {}: {}",
+            order.id,
+            error_text
+        );
+    }
+    println!(
+        "  ✓ Synced {} line items for order {}",
+        order.line_items.len(),
+        order.id
+    );
+
+    Ok(())
+}
+
+async fn sync_order_transactions(
+    http_client: &Client,
+    shopify_client: &ShopifyClient,
+    auth_api_url: &str,
+    merchant_id: Uuid,
+    order: &ShopifyOrder,
+) -> anyhow::Result<()> {
+    let transactions = shopify_client
+        .get_transactions(order.id)
+        .await
+        .with_context(|| format!("Failed to fetch transactions for order {}", order.id))?;
+
+    let transactions: Vec<serde_json::Value> = transactions
+        .iter()
+        .map(|transaction| {
+            serde_json::json!({
+                "shopify_transaction_id": transaction.id,
+                "kind": transaction.kind,
+                "status": transaction.status,
+                "gateway": transaction.gateway,
+                "amount": transaction.amount.parse::<Decimal>().unwrap_or_default().to_string(),
+                "currency": transaction.currency,
+                "processed_at": transaction.processed_at,
+            })
+        })
+        .collect();
+
+    let transactions_payload = serde_json::json!({
+        "merchant_id": merchant_id,
+        "shopify_order_id": order.id,
+        "transactions": transactions,
+    });
+
+    let url = format!("{}/api/v1/order-transactions", auth_api_url);
+    let response = http_client
+        .post(&url)
+        .json(&transactions_payload)
+        .send()
+        .await?;
+
+    if !response.status().is_success() {
+        let error_text = response.text().await?;
+        anyhow::bail!(
+            "Failed to sync transactions for order {}: {}",
+            order.id,
+            error_text
+        );
+    }
+    println!(
+        "  ✓ Synced {} transactions for order {}",
+        transactions.len(),
+        order.id
+    );
+
+    Ok(())
+}
+
+async fn sync_order_refunds(
+    http_client: &Client,
+    auth_api_url: &str,
+    merchant_id: Uuid,
+    order: &ShopifyOrder,
+    app_context: &AppContext,
+) -> anyhow::Result<()> {
+    let url = format!("{}/api/v1/refunds", auth_api_url);
+
+    for refund in &order.refunds {
+        let parse = |amount: &str| amount.parse::<Decimal>().unwrap_or_default();
+        let set_amount = |set: &Option<ShopifyPriceSet>| {
+            set.as_ref()
+                .map(|set| parse(&set.shop_money.amount))
+                .unwrap_or_default()
+        };
+
+        // Money actually returned to the customer
+        let amount: Decimal = refund
+            .transactions
+            .iter()
+            .filter(|t| t.kind == "refund" && t.status.as_deref().unwrap_or("success") == "success")
+            .map(|t| parse(&t.amount))
+            .sum();
+
+        // Shipping refunds are order adjustments with negative amounts
+        let shipping_adjustments = refund
+            .order_adjustments
+            .iter()
+            .filter(|adjustment| adjustment.kind == "shipping_refund");
+        let shipping_amount: Decimal = -shipping_adjustments
+            .clone()
+            .map(|adjustment| parse(&adjustment.amount))
+            .sum::<Decimal>();
+        let shipping_tax: Decimal = -shipping_adjustments
+            .map(|adjustment| parse(&adjustment.tax_amount))
+            .sum::<Decimal>();
+
+        let line_items: Vec<serde_json::Value> = refund
+            .refund_line_items
+            .iter()
+            .map(|item| {
+                serde_json::json!({
+                    "shopify_refund_line_item_id": item.id,
+                    "shopify_line_item_id": item.line_item_id,
+                    "quantity": item.quantity,
+                    "subtotal": set_amount(&item.subtotal_set).to_string(),
+                    "total_tax": set_amount(&item.total_tax_set).to_string(),
+                    "restock_type": item.restock_type,
+                })
+            })
+            .collect();
+        let tax_amount: Decimal = refund
+            .refund_line_items
+            .iter()
+            .map(|item| set_amount(&item.total_tax_set))
+            .sum::<Decimal>()
+            + shipping_tax;
+
+        let processed_at = refund.processed_at.as_deref().unwrap_or(&refund.created_at);
+
+        let refund_payload = serde_json::json!({
             "merchant_id": merchant_id,
             "shopify_order_id": order.id,
-            "name": order.name,
-            "processed_at": processed_at.map(|dt| dt.to_rfc3339()),
+            "shopify_refund_id": refund.id,
+            "processed_at": processed_at,
+            "note": refund.note,
             "currency": order.currency,
-            "subtotal_price": subtotal_price.map(|d| d.to_string()),
-            "total_price": total_price.map(|d| d.to_string()),
-            "total_discounts": total_discounts.map(|d| d.to_string()),
-            "total_shipping_price_set_amount": total_shipping_price_set_amount.map(|d| d.to_string()),
-            "total_tax": total_tax.map(|d| d.to_string()),
-            "financial_status": order.financial_status,
+            "amount": amount.to_string(),
+            "shipping_amount": shipping_amount.to_string(),
+            "tax_amount": tax_amount.to_string(),
+            "line_items": line_items,
         });
 
-        let url = format!("{}/api/v1/orders", auth_api_url);
-        let response = http_client
-            .post(&url)
-            .json(&order_payload)
-            .send()
-            .await?;
+        let response = http_client.post(&url).json(&refund_payload).send().await?;
 
-        if response.status().is_success() {
-            println!("✓ Synced order: {} (Shopify ID: {})", order.name, order.id);
-        } else if response.status() == 400 {
+        if !response.status().is_success() {
             let error_text = response.text().await?;
-            if error_text.contains("already exists") {
-                println!("⊘ Order {} already exists, skipping", order.id);
-            } else {
-                eprintln!("✗ Error syncing order {}: {}", order.id, error_text);
-            }
-        } else {
-            let error_text = response.text().await?;
-            eprintln!("✗ Error syncing order {}: {}", order.id, error_text);
+            anyhow::bail!(
+                "Failed to sync refund {} for order {}: {}",
+                refund.id,
+                order.id,
+                error_text
+            );
         }
+        println!("  ✓ Synced refund {} for order {}", refund.id, order.id);
+
+        publish_change(
+            app_context,
+            merchant_id,
+            RefundCreated {
+                shopify_order_id: order.id,
+                shopify_refund_id: refund.id,
+                processed_at: DateTime::parse_from_rfc3339(processed_at)
+                    .ok()
+                    .map(|dt| dt.with_timezone(&Utc)),
+            },
+        )
+        .await;
     }
 
     Ok(())
 }
 
+/// Tell downstream services (the cost engine) that synced data changed.
+/// Failures are logged only: the data is already stored and consumers catch up
+/// periodically.
+async fn publish_change<E: Event + std::fmt::Debug>(
+    app_context: &AppContext,
+    merchant_id: Uuid,
+    event: E,
+) {
+    let Some(producer) = &app_context.kafka_producer else {
+        return;
+    };
+
+    let envelope = EventEnvelope::new(merchant_id, event);
+    if let Err(e) = publish(producer, &envelope, Encoding::Json).await {
+        eprintln!("  ✗ Error publishing {:?}: {}", envelope.payload, e);
+    }
+}
+
+/// What the auth API reports back after upserting an order
+#[derive(Deserialize)]
+struct UpsertedOrder {
+    /// The order is new, its financial status changed or none of its
+    /// transactions are stored yet
+    transactions_outdated: bool,
+}
+
+/// Where a sync of a resource stands, as stored by the auth API
+#[derive(Deserialize)]
+struct SyncState {
+    cursor: Option<String>,
+    run_started_at: Option<DateTime<Utc>>,
+    updated_at_min: Option<DateTime<Utc>>,
+}
+
+/// Checkpoint handling of one sync run
+///
+/// Runs ask Shopify only for records updated since the last checkpoint, save
+/// the cursor after each page so an interrupted run resumes where it stopped,
+/// and move the checkpoint to their start time once done. Runs with a `limit`
+/// only sample records: they start from scratch and save nothing.
+struct SyncCheckpoint {
+    merchant_id: Uuid,
+    resource: &'static str,
+    updated_at_min: Option<DateTime<Utc>>,
+    cursor: Option<String>,
+    run_started_at: DateTime<Utc>,
+    enabled: bool,
+}
+
+async fn start_sync(
+    http_client: &Client,
+    auth_api_url: &str,
+    merchant_id: Uuid,
+    resource: &'static str,
+    limit: Option<u32>,
+) -> anyhow::Result<SyncCheckpoint> {
+    let mut checkpoint = SyncCheckpoint {
+        merchant_id,
+        resource,
+        updated_at_min: None,
+        cursor: None,
+        run_started_at: Utc::now(),
+        enabled: limit.is_none(),
+    };
+    if !checkpoint.enabled {
+        return Ok(checkpoint);
+    }
+
+    let url = format!("{}/api/v1/sync-state", auth_api_url);
+    let state: SyncState = http_client
+        .get(&url)
+        .query(&[
+            ("merchant_id", merchant_id.to_string()),
+            ("resource", resource.to_string()),
+        ])
+        .send()
+        .await?
+        .error_for_status()
+        .with_context(|| format!("Failed to get {} sync state", resource))?
+        .json()
+        .await?;
+
+    checkpoint.updated_at_min = state.updated_at_min;
+    // Resume an interrupted run; its start time stays the next checkpoint
+    if let (Some(cursor), Some(run_started_at)) = (state.cursor, state.run_started_at) {
+        println!("↻ Resuming {} sync started at {}", resource, run_started_at);
+        checkpoint.cursor = Some(cursor);
+        checkpoint.run_started_at = run_started_at;
+    } else if let Some(updated_at_min) = state.updated_at_min {
+        println!("↻ Syncing {} updated since {}", resource, updated_at_min);
+    } else {
+        println!("↻ Syncing all {}", resource);
+    }
+
+    Ok(checkpoint)
+}
+
+impl SyncCheckpoint {
+    /// Remember the cursor of the next page once a page is synced
+    async fn save_progress(
+        &self,
+        http_client: &Client,
+        auth_api_url: &str,
+        next_page_info: Option<String>,
+    ) -> anyhow::Result<()> {
+        let cursor = next_page_info.map(|cursor| (cursor, self.run_started_at));
+        self.save(http_client, auth_api_url, None, cursor).await
+    }
+
+    /// Move the checkpoint to the start of this run once every page is synced
+    async fn complete(&self, http_client: &Client, auth_api_url: &str) -> anyhow::Result<()> {
+        self.save(http_client, auth_api_url, Some(self.run_started_at), None)
+            .await
+    }
+
+    async fn save(
+        &self,
+        http_client: &Client,
+        auth_api_url: &str,
+        last_updated_at: Option<DateTime<Utc>>,
+        cursor: Option<(String, DateTime<Utc>)>,
+    ) -> anyhow::Result<()> {
+        if !self.enabled {
+            return Ok(());
+        }
+
+        let (cursor, run_started_at) = cursor.unzip();
+        let state_payload = serde_json::json!({
+            "merchant_id": self.merchant_id,
+            "resource": self.resource,
+            "last_updated_at": last_updated_at,
+            "cursor": cursor,
+            "run_started_at": run_started_at,
+        });
+
+        let url = format!("{}/api/v1/sync-state", auth_api_url);
+        http_client
+            .put(&url)
+            .json(&state_payload)
+            .send()
+            .await?
+            .error_for_status()
+            .with_context(|| format!("Failed to save {} sync state", self.resource))?;
+
+        Ok(())
+    }
+}
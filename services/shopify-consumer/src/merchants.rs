@@ -0,0 +1,269 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex, RwLock};
+
+use anyhow::Context;
+use serde::Deserialize;
+use tokio::sync::{Mutex as AsyncMutex, OwnedMutexGuard};
+use tokio_cron_scheduler::Job;
+use uuid::Uuid;
+
+use crate::shopify::ShopifyClient;
+use crate::{sync_merchant, AppContext};
+
+/// An active install, as listed by the auth API
+#[derive(Deserialize)]
+struct ActiveInstall {
+    merchant_id: Uuid,
+    shop_domain: String,
+    access_token_encrypted: String,
+    auto_refresh_cron: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct ActiveInstallList {
+    installs: Vec<ActiveInstall>,
+}
+
+/// A merchant whose store is synced
+pub struct Merchant {
+    pub merchant_id: Uuid,
+    pub shopify_client: Arc<ShopifyClient>,
+    shop_domain: String,
+    access_token: String,
+    auto_refresh_cron: Option<String>,
+    /// Scheduler job syncing the store on `auto_refresh_cron`
+    job_id: Option<Uuid>,
+}
+
+/// Merchants with an active install, kept in line with the auth API by
+/// `refresh_merchants`
+#[derive(Default)]
+pub struct Merchants {
+    merchants: RwLock<HashMap<Uuid, Arc<Merchant>>>,
+    /// Held while a merchant's store is synced; kept apart from `Merchant` so
+    /// that refreshes replacing a merchant do not release it
+    sync_locks: Mutex<HashMap<Uuid, Arc<AsyncMutex<()>>>>,
+}
+
+impl Merchants {
+    pub fn get(&self, merchant_id: Uuid) -> Option<Arc<Merchant>> {
+        self.merchants.read().unwrap().get(&merchant_id).cloned()
+    }
+
+    pub fn all(&self) -> Vec<Arc<Merchant>> {
+        self.merchants.read().unwrap().values().cloned().collect()
+    }
+
+    /// The merchant of a store by its myshopify.com domain, as sent in webhook headers
+    pub fn by_shop_domain(&self, shop_domain: &str) -> Option<Arc<Merchant>> {
+        let shop_domain = shop_domain.to_lowercase();
+        self.merchants
+            .read()
+            .unwrap()
+            .values()
+            .find(|merchant| merchant.shopify_client.shop_domain() == shop_domain)
+            .cloned()
+    }
+
+    /// Claim the sync of a merchant's store until the guard is dropped, or
+    /// `None` while another sync of it runs. Syncs move the merchant's
+    /// checkpoints, so two at once would overwrite each other's cursors.
+    pub fn try_lock_sync(&self, merchant_id: Uuid) -> Option<OwnedMutexGuard<()>> {
+        let lock = self
+            .sync_locks
+            .lock()
+            .unwrap()
+            .entry(merchant_id)
+            .or_default()
+            .clone();
+        lock.try_lock_owned().ok()
+    }
+}
+
+/// Load the active installs from the auth API and bring the merchants and
+/// their scheduled syncs in line: new installs start syncing, uninstalled ones
+/// stop, and changed tokens or `auto_refresh_cron` schedules take effect.
+pub async fn refresh_merchants(ctx: &AppContext) -> anyhow::Result<()> {
+    let url = format!("{}/api/v1/shopify-installs", ctx.auth_api_url);
+    let installs = ctx
+        .http_client
+        .get(&url)
+        .send()
+        .await?
+        .error_for_status()
+        .context("Failed to list active Shopify installs")?
+        .json::<ActiveInstallList>()
+        .await?
+        .installs;
+
+    // Installs whose token cannot be decrypted with our key are left out, so
+    // one bad install does not stop the others from syncing
+    let installs: Vec<(ActiveInstall, String)> = installs
+        .into_iter()
+        .filter_map(
+            |install| match ctx.token_cipher.decrypt(&install.access_token_encrypted) {
+                Ok(access_token) => Some((install, access_token)),
+                Err(e) => {
+                    eprintln!(
+                        "❌ Skipping install of merchant {}: {:#}",
+                        install.merchant_id, e
+                    );
+                    None
+                }
+            },
+        )
+        .collect();
+
+    let active: HashSet<Uuid> = installs
+        .iter()
+        .map(|(install, _)| install.merchant_id)
+        .collect();
+    let stale: Vec<Uuid> = ctx
+        .merchants
+        .all()
+        .iter()
+        .map(|merchant| merchant.merchant_id)
+        .filter(|merchant_id| !active.contains(merchant_id))
+        .collect();
+    for merchant_id in stale {
+        remove_merchant(ctx, merchant_id).await;
+    }
+
+    for (install, access_token) in installs {
+        let existing = ctx.merchants.get(install.merchant_id);
+        if existing.is_none() {
+            println!(
+                "➕ Syncing merchant {} ({})",
+                install.merchant_id, install.shop_domain
+            );
+        }
+
+        // Keep the client unless the store or its token changed
+        let shopify_client = match &existing {
+            Some(merchant)
+                if merchant.shop_domain == install.shop_domain
+                    && merchant.access_token == access_token =>
+            {
+                merchant.shopify_client.clone()
+            }
+            _ => Arc::new(ShopifyClient::new(
+                install.shop_domain.clone(),
+                access_token.clone(),
+                ctx.api_version.clone(),
+            )),
+        };
+
+        // Keep the scheduled job unless the schedule changed
+        let job_id = match &existing {
+            Some(merchant) if merchant.auto_refresh_cron == install.auto_refresh_cron => {
+                merchant.job_id
+            }
+            _ => {
+                if let Some(merchant) = &existing {
+                    unschedule(ctx, merchant).await;
+                }
+                schedule(
+                    ctx,
+                    install.merchant_id,
+                    install.auto_refresh_cron.as_deref(),
+                )
+                .await
+            }
+        };
+
+        ctx.merchants.merchants.write().unwrap().insert(
+            install.merchant_id,
+            Arc::new(Merchant {
+                merchant_id: install.merchant_id,
+                shopify_client,
+                shop_domain: install.shop_domain,
+                access_token,
+                auto_refresh_cron: install.auto_refresh_cron,
+                job_id,
+            }),
+        );
+    }
+
+    Ok(())
+}
+
+/// Stop syncing a merchant, e.g. once its app is uninstalled
+pub async fn remove_merchant(ctx: &AppContext, merchant_id: Uuid) {
+    let removed = ctx
+        .merchants
+        .merchants
+        .write()
+        .unwrap()
+        .remove(&merchant_id);
+    if let Some(merchant) = removed {
+        unschedule(ctx, &merchant).await;
+        println!(
+            "➖ Stopped syncing merchant {} ({})",
+            merchant_id, merchant.shop_domain
+        );
+    }
+}
+
+/// Schedule syncs of a merchant's store, returning the job's ID; merchants
+/// without a (valid) schedule are only synced on demand and by webhooks
+async fn schedule(ctx: &AppContext, merchant_id: Uuid, cron: Option<&str>) -> Option<Uuid> {
+    let cron = cron?;
+    let job_ctx = ctx.clone();
+    let job = Job::new_async(cron, move |_uuid, _l| {
+        let ctx = job_ctx.clone();
+        Box::pin(async move {
+            println!("🔄 Scheduled sync triggered for merchant {}", merchant_id);
+            sync_merchant(&ctx, merchant_id).await;
+        })
+    });
+
+    let job_id = match job {
+        Ok(job) => ctx.scheduler.add(job).await,
+        Err(e) => Err(e),
+    };
+    match job_id {
+        Ok(job_id) => {
+            println!("✅ Merchant {} sync scheduled: {}", merchant_id, cron);
+            Some(job_id)
+        }
+        Err(e) => {
+            eprintln!(
+                "❌ Failed to schedule syncs of merchant {} ({}): {}",
+                merchant_id, cron, e
+            );
+            None
+        }
+    }
+}
+
+async fn unschedule(ctx: &AppContext, merchant: &Merchant) {
+    if let Some(job_id) = merchant.job_id {
+        if let Err(e) = ctx.scheduler.remove(&job_id).await {
+            eprintln!(
+                "❌ Failed to unschedule syncs of merchant {}: {}",
+                merchant.merchant_id, e
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_try_lock_sync() {
+        let merchants = Merchants::default();
+        let (first, second) = (Uuid::new_v4(), Uuid::new_v4());
+
+        // One sync per merchant at a time, released once its guard is dropped
+        let sync = merchants
+            .try_lock_sync(first)
+            .expect("First sync should start");
+        assert!(merchants.try_lock_sync(first).is_none());
+        assert!(merchants.try_lock_sync(second).is_some());
+
+        drop(sync);
+        assert!(merchants.try_lock_sync(first).is_some());
+    }
+}
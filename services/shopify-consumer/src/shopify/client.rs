@@ -1,7 +1,12 @@
 use crate::shopify::types::*;
+use chrono::{DateTime, Utc};
+use futures::stream::{self, Stream};
 use reqwest::Client;
 use std::time::Duration;
 
+/// Times a rate-limited request is retried before giving up
+const MAX_RATE_LIMIT_RETRIES: u32 = 5;
+
 /// Shopify Admin API Client
 ///
 /// This client handles authentication and API calls to Shopify Admin API
@@ -17,7 +22,8 @@ impl ShopifyClient {
     /// Create a new Shopify API client
     ///
     /// # Arguments
-    /// * `store_name` - Your Shopify store name (e.g., "store-analytic-app")
+    /// * `store_name` - Your Shopify store name (e.g., "store-analytic-app") or its
+    ///   myshopify.com domain (e.g., "store-analytic-app.myshopify.com")
     /// * `access_token` - Your Shopify Admin API access token
     /// * `api_version` - API version (e.g., "2024-10" or "2025-01")
     pub fn new(store_name: String, access_token: String, api_version: String) -> Self {
@@ -34,11 +40,21 @@ impl ShopifyClient {
         }
     }
 
+    /// The store's myshopify.com domain, as sent in webhook headers
+    pub fn shop_domain(&self) -> String {
+        if self.store_name.contains('.') {
+            self.store_name.to_lowercase()
+        } else {
+            format!("{}.myshopify.com", self.store_name.to_lowercase())
+        }
+    }
+
     /// Build the base URL for API requests
     fn base_url(&self) -> String {
         format!(
-            "https://{}.myshopify.com/admin/api/{}",
-            self.store_name, self.api_version
+            "https://{}/admin/api/{}",
+            self.shop_domain(),
+            self.api_version
         )
     }
 
@@ -53,43 +69,34 @@ impl ShopifyClient {
         headers
     }
 
-    /// Fetch products from Shopify
+    /// Stream all products from Shopify, one page at a time
     ///
     /// # Arguments
-    /// * `limit` - Maximum number of products to fetch per page (default: 250, max: 250)
-    /// * `since_id` - Fetch products with ID greater than this value (for pagination)
+    /// * `page_size` - Number of products per page (default: 250, max: 250)
+    /// * `updated_at_min` - Only products updated at or after this time
+    /// * `page_info` - Resume from this cursor of an earlier stream instead of the first page
     ///
     /// # Returns
-    /// Vector of ShopifyProduct objects
-    pub async fn get_products(
+    /// Stream of pages of ShopifyProduct objects, following `rel="next"` links
+    pub fn products_pages(
         &self,
-        limit: Option<u32>,
-        since_id: Option<i64>,
-    ) -> Result<Vec<ShopifyProduct>, ShopifyErrorType> {
-        let limit = limit.unwrap_or(250).min(250);
-        let url = format!("{}/products.json", self.base_url());
-
-        let mut query_params = vec![("limit", limit.to_string())];
-        if let Some(id) = since_id {
-            query_params.push(("since_id", id.to_string()));
+        page_size: Option<u32>,
+        updated_at_min: Option<DateTime<Utc>>,
+        page_info: Option<String>,
+    ) -> impl Stream<Item = Result<ShopifyPage<ShopifyProduct>, ShopifyErrorType>> + '_ {
+        let mut query_params = Vec::new();
+        if let Some(min) = updated_at_min {
+            query_params.push(("updated_at_min", min.to_rfc3339()));
         }
 
-        let response = self
-            .client
-            .get(&url)
-            .headers(self.headers())
-            .query(&query_params)
-            .send()
-            .await?;
-
-        self.handle_response(response).await
+        self.paginate("products.json", query_params, page_size, page_info)
     }
 
     /// Fetch a single product by ID
     pub async fn get_product(&self, product_id: i64) -> Result<ShopifyProduct, ShopifyErrorType> {
         let url = format!("{}/products/{}.json", self.base_url(), product_id);
 
-        let response = self.client.get(&url).headers(self.headers()).send().await?;
+        let response = self.get(&url, &[]).await?;
 
         let mut wrapper: serde_json::Value = response.json().await?;
         let product = serde_json::from_value(wrapper["product"].take())
@@ -98,53 +105,44 @@ impl ShopifyClient {
         Ok(product)
     }
 
-    /// Fetch orders from Shopify
+    /// Stream all orders from Shopify, one page at a time
     ///
     /// # Arguments
-    /// * `limit` - Maximum number of orders to fetch per page (default: 250, max: 250)
-    /// * `since_id` - Fetch orders with ID greater than this value (for pagination)
+    /// * `page_size` - Number of orders per page (default: 250, max: 250)
     /// * `status` - Filter by order status: "any", "open", "closed", "cancelled"
     /// * `financial_status` - Filter by financial status: "any", "authorized", "pending", "paid", "refunded", etc.
+    /// * `updated_at_min` - Only orders updated at or after this time
+    /// * `page_info` - Resume from this cursor of an earlier stream instead of the first page
     ///
     /// # Returns
-    /// Vector of ShopifyOrder objects
-    pub async fn get_orders(
+    /// Stream of pages of ShopifyOrder objects, following `rel="next"` links
+    pub fn orders_pages(
         &self,
-        limit: Option<u32>,
-        since_id: Option<i64>,
+        page_size: Option<u32>,
         status: Option<&str>,
         financial_status: Option<&str>,
-    ) -> Result<Vec<ShopifyOrder>, ShopifyErrorType> {
-        let limit = limit.unwrap_or(250).min(250);
-        let url = format!("{}/orders.json", self.base_url());
-
-        let mut query_params = vec![("limit", limit.to_string())];
-        if let Some(id) = since_id {
-            query_params.push(("since_id", id.to_string()));
-        }
+        updated_at_min: Option<DateTime<Utc>>,
+        page_info: Option<String>,
+    ) -> impl Stream<Item = Result<ShopifyPage<ShopifyOrder>, ShopifyErrorType>> + '_ {
+        let mut query_params = Vec::new();
         if let Some(s) = status {
             query_params.push(("status", s.to_string()));
         }
         if let Some(fs) = financial_status {
             query_params.push(("financial_status", fs.to_string()));
         }
+        if let Some(min) = updated_at_min {
+            query_params.push(("updated_at_min", min.to_rfc3339()));
+        }
 
-        let response = self
-            .client
-            .get(&url)
-            .headers(self.headers())
-            .query(&query_params)
-            .send()
-            .await?;
-
-        self.handle_response(response).await
+        self.paginate("orders.json", query_params, page_size, page_info)
     }
 
     /// Fetch a single order by ID
     pub async fn get_order(&self, order_id: i64) -> Result<ShopifyOrder, ShopifyErrorType> {
         let url = format!("{}/orders/{}.json", self.base_url(), order_id);
 
-        let response = self.client.get(&url).headers(self.headers()).send().await?;
+        let response = self.get(&url, &[]).await?;
 
         let mut wrapper: serde_json::Value = response.json().await?;
         let order = serde_json::from_value(wrapper["order"].take())
@@ -153,6 +151,110 @@ impl ShopifyClient {
         Ok(order)
     }
 
+    /// Fetch the payment transactions of an order
+    ///
+    /// # Returns
+    /// Vector of ShopifyTransaction objects, oldest first
+    pub async fn get_transactions(
+        &self,
+        order_id: i64,
+    ) -> Result<Vec<ShopifyTransaction>, ShopifyErrorType> {
+        let url = format!("{}/orders/{}/transactions.json", self.base_url(), order_id);
+
+        let response = self.get(&url, &[]).await?;
+
+        self.handle_response(response).await
+    }
+
+    /// Stream the pages of a list endpoint
+    ///
+    /// Shopify paginates with a `page_info` cursor taken from the `Link` header's
+    /// `rel="next"` URL. Requests after the first may only carry `limit` and
+    /// `page_info`, since the cursor already encodes the filters, so a stream
+    /// resumed from `page_info` ignores `query_params`.
+    fn paginate<T>(
+        &self,
+        path: &'static str,
+        query_params: Vec<(&'static str, String)>,
+        page_size: Option<u32>,
+        page_info: Option<String>,
+    ) -> impl Stream<Item = Result<ShopifyPage<T>, ShopifyErrorType>> + '_
+    where
+        T: serde::de::DeserializeOwned,
+    {
+        let limit = page_size.unwrap_or(250).min(250).to_string();
+        let url = format!("{}/{}", self.base_url(), path);
+
+        // State: Some(cursor of the next page, None for the first page), or None once done
+        stream::try_unfold(Some(page_info), move |cursor| {
+            let url = url.clone();
+            let limit = limit.clone();
+            let query_params = query_params.clone();
+            async move {
+                let Some(page_info) = cursor else {
+                    return Ok(None);
+                };
+
+                let query = match page_info {
+                    Some(page_info) => vec![("limit", limit), ("page_info", page_info)],
+                    None => {
+                        let mut query = vec![("limit", limit)];
+                        query.extend(query_params);
+                        query
+                    }
+                };
+
+                let response = self.get(&url, &query).await?;
+                let next_page_info = response
+                    .headers()
+                    .get(reqwest::header::LINK)
+                    .and_then(|link| link.to_str().ok())
+                    .and_then(next_page_info);
+                let items = self.handle_response(response).await?;
+                let page = ShopifyPage {
+                    items,
+                    next_page_info: next_page_info.clone(),
+                };
+
+                Ok(Some((page, next_page_info.map(Some))))
+            }
+        })
+    }
+
+    /// Send an authenticated GET request, waiting out rate limits
+    ///
+    /// A 429 is retried after the `Retry-After` delay (2s when missing), up to
+    /// `MAX_RATE_LIMIT_RETRIES` times; after that it surfaces as `RateLimit`.
+    async fn get(
+        &self,
+        url: &str,
+        query_params: &[(&str, String)],
+    ) -> Result<reqwest::Response, ShopifyErrorType> {
+        let mut retries = 0;
+        loop {
+            let response = self
+                .client
+                .get(url)
+                .headers(self.headers())
+                .query(query_params)
+                .send()
+                .await?;
+
+            if response.status() != 429 || retries == MAX_RATE_LIMIT_RETRIES {
+                return Ok(response);
+            }
+
+            let retry_after = response
+                .headers()
+                .get(reqwest::header::RETRY_AFTER)
+                .and_then(|value| value.to_str().ok())
+                .and_then(|value| value.parse::<f64>().ok())
+                .unwrap_or(2.0);
+            tokio::time::sleep(Duration::from_secs_f64(retry_after)).await;
+            retries += 1;
+        }
+    }
+
     /// Handle API response and check for errors
     async fn handle_response<T>(&self, response: reqwest::Response) -> Result<T, ShopifyErrorType>
     where
@@ -188,11 +290,13 @@ impl ShopifyClient {
         let json: serde_json::Value = serde_json::from_str(&text)
             .map_err(|e| ShopifyErrorType::Api(format!("Invalid JSON: {}", e)))?;
 
-        // Handle both {products: [...]} and {orders: [...]} formats
+        // Handle {products: [...]}, {orders: [...]} and {transactions: [...]} formats
         let data = if json.get("products").is_some() {
             json["products"].clone()
         } else if json.get("orders").is_some() {
             json["orders"].clone()
+        } else if json.get("transactions").is_some() {
+            json["transactions"].clone()
         } else {
             json
         };
@@ -202,6 +306,22 @@ impl ShopifyClient {
     }
 }
 
+/// Extract the `page_info` cursor of the `rel="next"` URL from a `Link` header
+///
+/// e.g. `<https://shop.myshopify.com/admin/api/2025-10/orders.json?limit=250&page_info=abc>; rel="next"`
+fn next_page_info(link: &str) -> Option<String> {
+    link.split(',')
+        .find(|part| part.contains(r#"rel="next""#))
+        .and_then(|part| {
+            let url = part.split(';').next()?.trim();
+            let url = url.strip_prefix('<')?.strip_suffix('>')?;
+            let url = reqwest::Url::parse(url).ok()?;
+            url.query_pairs()
+                .find(|(key, _)| key == "page_info")
+                .map(|(_, value)| value.into_owned())
+        })
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -217,6 +337,27 @@ mod tests {
             client.base_url(),
             "https://test-store.myshopify.com/admin/api/2024-10"
         );
+
+        let client = ShopifyClient::new(
+            "Test-Store.myshopify.com".to_string(),
+            "token".to_string(),
+            "2024-10".to_string(),
+        );
+        assert_eq!(client.shop_domain(), "test-store.myshopify.com");
     }
-}
 
+    #[test]
+    fn test_next_page_info() {
+        let base = "https://test-store.myshopify.com/admin/api/2024-10/orders.json";
+        let first = format!(r#"<{}?limit=250&page_info=abc123>; rel="next""#, base);
+        assert_eq!(next_page_info(&first), Some("abc123".to_string()));
+
+        let middle = format!(
+            r#"<{base}?limit=250&page_info=prev1>; rel="previous", <{base}?limit=250&page_info=next2>; rel="next""#
+        );
+        assert_eq!(next_page_info(&middle), Some("next2".to_string()));
+
+        let last = format!(r#"<{}?limit=250&page_info=prev3>; rel="previous""#, base);
+        assert_eq!(next_page_info(&last), None);
+    }
+}
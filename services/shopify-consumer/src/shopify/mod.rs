@@ -3,5 +3,3 @@ pub mod types;
 
 pub use client::ShopifyClient;
 pub use types::*;
-
-
@@ -63,7 +63,14 @@ pub struct ShopifyOrder {
     pub financial_status: Option<String>,
     pub fulfillment_status: Option<String>,
     pub cancelled_at: Option<String>,
+    #[serde(default)]
+    pub test: bool,
+    pub landing_site: Option<String>,
+    pub referring_site: Option<String>,
     pub line_items: Vec<ShopifyLineItem>,
+    /// Refunds come with the order, so they need no request of their own
+    #[serde(default)]
+    pub refunds: Vec<ShopifyRefund>,
     pub customer: Option<ShopifyCustomer>,
     pub shipping_address: Option<ShopifyAddress>,
     pub billing_address: Option<ShopifyAddress>,
@@ -89,6 +96,60 @@ pub struct ShopifyLineItem {
     pub quantity: i32,
     pub price: String,
     pub sku: Option<String>,
+    pub total_discount: Option<String>,
+    #[serde(default)]
+    pub discount_allocations: Vec<ShopifyDiscountAllocation>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ShopifyDiscountAllocation {
+    pub amount: String,
+    pub discount_application_index: Option<i32>,
+}
+
+// Refund Types
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ShopifyRefund {
+    pub id: i64,
+    pub order_id: i64,
+    pub created_at: String,
+    pub processed_at: Option<String>,
+    pub note: Option<String>,
+    #[serde(default)]
+    pub refund_line_items: Vec<ShopifyRefundLineItem>,
+    #[serde(default)]
+    pub transactions: Vec<ShopifyTransaction>,
+    #[serde(default)]
+    pub order_adjustments: Vec<ShopifyOrderAdjustment>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ShopifyRefundLineItem {
+    pub id: i64,
+    pub line_item_id: i64,
+    pub quantity: i32,
+    pub restock_type: Option<String>,
+    pub subtotal_set: Option<ShopifyPriceSet>,
+    pub total_tax_set: Option<ShopifyPriceSet>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ShopifyTransaction {
+    pub id: i64,
+    pub kind: String,
+    pub status: Option<String>,
+    pub gateway: Option<String>,
+    pub amount: String,
+    pub currency: Option<String>,
+    pub processed_at: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ShopifyOrderAdjustment {
+    pub id: i64,
+    pub kind: String,
+    pub amount: String,
+    pub tax_amount: String,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -107,9 +168,18 @@ pub struct ShopifyAddress {
     pub city: Option<String>,
     pub province: Option<String>,
     pub country: Option<String>,
+    pub country_code: Option<String>,
     pub zip: Option<String>,
 }
 
+/// One page of a list endpoint
+#[derive(Debug)]
+pub struct ShopifyPage<T> {
+    pub items: Vec<T>,
+    /// Cursor of the next page, `None` on the last one
+    pub next_page_info: Option<String>,
+}
+
 // API Error Types
 #[derive(Debug, Deserialize)]
 pub struct ShopifyError {
@@ -127,5 +197,3 @@ pub enum ShopifyErrorType {
     #[error("Rate limit exceeded")]
     RateLimit,
 }
-
-
@@ -0,0 +1,87 @@
+use aes_gcm::aead::{Aead, KeyInit};
+use aes_gcm::{Aes256Gcm, Key, Nonce};
+use anyhow::Context;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+
+/// Bytes of the AES-GCM nonce stored in front of each ciphertext
+const NONCE_LEN: usize = 12;
+
+/// Decrypts the Shopify access tokens that the auth API stores, and lists,
+/// encrypted with AES-256-GCM
+pub struct TokenCipher {
+    cipher: Aes256Gcm,
+}
+
+impl TokenCipher {
+    /// Cipher from the base64 of the 32-byte key the auth API encrypts with
+    pub fn from_base64_key(key: &str) -> anyhow::Result<Self> {
+        let key = STANDARD
+            .decode(key.trim())
+            .ok()
+            .filter(|key| key.len() == 32)
+            .context("SHOPIFY_TOKEN_KEY must be the base64 of 32 bytes")?;
+
+        Ok(Self {
+            cipher: Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&key)),
+        })
+    }
+
+    /// Token from the base64 of its nonce followed by its ciphertext; fails on
+    /// tampered values or another key
+    pub fn decrypt(&self, encrypted: &str) -> anyhow::Result<String> {
+        let encrypted = STANDARD
+            .decode(encrypted)
+            .context("Access token is not valid base64")?;
+        anyhow::ensure!(encrypted.len() >= NONCE_LEN, "Access token is too short");
+
+        let (nonce, ciphertext) = encrypted.split_at(NONCE_LEN);
+        let token = self
+            .cipher
+            .decrypt(Nonce::from_slice(nonce), ciphertext)
+            .map_err(|_| anyhow::anyhow!("Failed to decrypt access token"))?;
+
+        String::from_utf8(token).context("Access token is not valid UTF-8")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KEY: &str = "MDEyMzQ1Njc4OWFiY2RlZjAxMjM0NTY3ODlhYmNkZWY=";
+
+    /// Encrypt the way the auth API stores tokens
+    fn encrypt(key: &str, token: &str) -> String {
+        let key = STANDARD.decode(key).unwrap();
+        let cipher = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&key));
+        let nonce = [7u8; NONCE_LEN];
+        let ciphertext = cipher
+            .encrypt(Nonce::from_slice(&nonce), token.as_bytes())
+            .unwrap();
+
+        let mut encrypted = nonce.to_vec();
+        encrypted.extend(ciphertext);
+        STANDARD.encode(encrypted)
+    }
+
+    #[test]
+    fn test_decrypt_tokens() {
+        let cipher = TokenCipher::from_base64_key(KEY).unwrap();
+        let encrypted = encrypt(KEY, "shpat_first");
+        assert_eq!(cipher.decrypt(&encrypted).unwrap(), "shpat_first");
+
+        // Another key, tampering or garbage cannot be read
+        let other =
+            TokenCipher::from_base64_key("ZmVkY2JhOTg3NjU0MzIxMGZlZGNiYTk4NzY1NDMyMTA=").unwrap();
+        assert!(other.decrypt(&encrypted).is_err());
+
+        let mut tampered = STANDARD.decode(&encrypted).unwrap();
+        *tampered.last_mut().unwrap() ^= 1;
+        assert!(cipher.decrypt(&STANDARD.encode(tampered)).is_err());
+        assert!(cipher.decrypt("c2hvcnQ=").is_err());
+        assert!(cipher.decrypt("not base64!").is_err());
+
+        assert!(TokenCipher::from_base64_key("c2hvcnQ=").is_err());
+    }
+}
@@ -0,0 +1,257 @@
+use std::collections::{HashSet, VecDeque};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use axum::{
+    body::Bytes,
+    extract::State,
+    http::{HeaderMap, StatusCode},
+};
+use base64::Engine;
+use hmac::{Hmac, Mac};
+use serde::Deserialize;
+use sha2::Sha256;
+
+use crate::merchants::{remove_merchant, Merchant};
+use crate::shopify::{ShopifyOrder, ShopifyProduct};
+use crate::{sync_order, sync_product, AppContext};
+
+/// How long webhook IDs are remembered; Shopify retries failed deliveries for 48 hours
+const DEDUP_TTL: Duration = Duration::from_secs(48 * 60 * 60);
+
+/// Whether `hmac_header` is the base64 HMAC-SHA256 of the raw `body` keyed with
+/// the app's API secret, as Shopify signs webhooks
+pub fn verify_hmac(secret: &str, body: &[u8], hmac_header: &str) -> bool {
+    let Ok(signature) = base64::engine::general_purpose::STANDARD.decode(hmac_header.trim()) else {
+        return false;
+    };
+
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC takes keys of any size");
+    mac.update(body);
+    // Constant-time comparison
+    mac.verify_slice(&signature).is_ok()
+}
+
+/// Webhook IDs received recently, so retried deliveries are handled once
+///
+/// Kept in memory: after a restart a retried delivery is handled again, which
+/// the upserts it leads to make harmless.
+#[derive(Default)]
+pub struct WebhookDedup {
+    seen: Mutex<SeenWebhooks>,
+}
+
+#[derive(Default)]
+struct SeenWebhooks {
+    ids: HashSet<String>,
+    /// Oldest first, for expiring IDs
+    received: VecDeque<(Instant, String)>,
+}
+
+impl WebhookDedup {
+    /// Remember `webhook_id`; false if it was already received
+    pub fn first_delivery(&self, webhook_id: &str) -> bool {
+        let now = Instant::now();
+        let mut seen = self.seen.lock().unwrap();
+
+        while let Some((at, _)) = seen.received.front() {
+            if now.duration_since(*at) < DEDUP_TTL {
+                break;
+            }
+            if let Some((_, id)) = seen.received.pop_front() {
+                seen.ids.remove(&id);
+            }
+        }
+
+        if !seen.ids.insert(webhook_id.to_string()) {
+            return false;
+        }
+        seen.received.push_back((now, webhook_id.to_string()));
+        true
+    }
+}
+
+/// Payload of refunds/create, as far as needed here
+#[derive(Deserialize)]
+struct RefundWebhook {
+    order_id: i64,
+}
+
+/// Payload of products/delete
+#[derive(Deserialize)]
+struct DeletedWebhook {
+    id: i64,
+}
+
+/// Receive a Shopify webhook
+///
+/// Verified, first deliveries are acknowledged right away and handled in the
+/// background for the merchant whose store sent them (by X-Shopify-Shop-Domain),
+/// since Shopify expects an answer within 5 seconds. A failed handling, or a
+/// webhook from a store not loaded yet, is only logged: the next polled sync
+/// picks the change up.
+pub async fn receive_webhook(
+    State(ctx): State<AppContext>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> StatusCode {
+    let header = |name: &str| headers.get(name).and_then(|value| value.to_str().ok());
+
+    let Some(secret) = ctx.webhook_secret.as_deref() else {
+        return StatusCode::NOT_FOUND;
+    };
+    if !header("X-Shopify-Hmac-Sha256").is_some_and(|hmac| verify_hmac(secret, &body, hmac)) {
+        eprintln!("✗ Rejected webhook with an invalid HMAC");
+        return StatusCode::UNAUTHORIZED;
+    }
+
+    let (Some(topic), Some(webhook_id)) =
+        (header("X-Shopify-Topic"), header("X-Shopify-Webhook-Id"))
+    else {
+        return StatusCode::BAD_REQUEST;
+    };
+    let topic = topic.to_string();
+
+    let Some(merchant) = header("X-Shopify-Shop-Domain")
+        .and_then(|shop_domain| ctx.merchants.by_shop_domain(shop_domain))
+    else {
+        eprintln!(
+            "⊘ Ignoring {} webhook for a shop without an active install: {:?}",
+            topic,
+            header("X-Shopify-Shop-Domain")
+        );
+        return StatusCode::OK;
+    };
+
+    if !ctx.webhook_dedup.first_delivery(webhook_id) {
+        println!(
+            "⊘ Webhook {} ({}) already received, skipping",
+            webhook_id, topic
+        );
+        return StatusCode::OK;
+    }
+
+    println!("📨 Received {} webhook {}", topic, webhook_id);
+    tokio::spawn(async move {
+        if let Err(e) = handle_webhook(&ctx, &merchant, &topic, &body).await {
+            eprintln!("✗ Error handling {} webhook: {}", topic, e);
+        }
+    });
+
+    StatusCode::OK
+}
+
+/// Push a webhook through the same upserts as polled syncs
+async fn handle_webhook(
+    ctx: &AppContext,
+    merchant: &Merchant,
+    topic: &str,
+    body: &[u8],
+) -> anyhow::Result<()> {
+    match topic {
+        "orders/create" | "orders/updated" | "orders/cancelled" => {
+            let order: ShopifyOrder = serde_json::from_slice(body)?;
+            sync_order(
+                &ctx.http_client,
+                &merchant.shopify_client,
+                &ctx.auth_api_url,
+                merchant.merchant_id,
+                &order,
+                ctx,
+            )
+            .await
+        }
+        "refunds/create" => {
+            // The refund alone lacks the order's new financial status
+            let refund: RefundWebhook = serde_json::from_slice(body)?;
+            let order = merchant.shopify_client.get_order(refund.order_id).await?;
+            sync_order(
+                &ctx.http_client,
+                &merchant.shopify_client,
+                &ctx.auth_api_url,
+                merchant.merchant_id,
+                &order,
+                ctx,
+            )
+            .await
+        }
+        "products/update" => {
+            let product: ShopifyProduct = serde_json::from_slice(body)?;
+            sync_product(
+                &ctx.http_client,
+                &ctx.auth_api_url,
+                merchant.merchant_id,
+                product,
+                ctx,
+            )
+            .await
+        }
+        "products/delete" => {
+            let product: DeletedWebhook = serde_json::from_slice(body)?;
+            let url = format!(
+                "{}/api/v1/products/by-shopify-id/{}",
+                ctx.auth_api_url, product.id
+            );
+            let response = ctx
+                .http_client
+                .delete(&url)
+                .query(&[("merchant_id", merchant.merchant_id)])
+                .send()
+                .await?;
+
+            // Products never synced are not found, which is fine
+            if response.status().is_success() || response.status() == 404 {
+                println!("✓ Deleted product {}", product.id);
+            } else {
+                let error_text = response.text().await?;
+                eprintln!("✗ Error deleting product {}: {}", product.id, error_text);
+            }
+            Ok(())
+        }
+        "app/uninstalled" => {
+            let url = format!(
+                "{}/api/v1/merchants/{}/uninstall",
+                ctx.auth_api_url, merchant.merchant_id
+            );
+            ctx.http_client
+                .post(&url)
+                .send()
+                .await?
+                .error_for_status()?;
+
+            println!("✓ Marked merchant {} as uninstalled", merchant.merchant_id);
+            remove_merchant(ctx, merchant.merchant_id).await;
+            Ok(())
+        }
+        other => {
+            println!("⊘ Ignoring unhandled webhook topic {}", other);
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_verify_hmac() {
+        let body = br#"{"id":1}"#;
+        let signature = "VnKUjZsLuN5iZWjn5EntcBVCF9kMN43LglzCE1/GSeY=";
+
+        assert!(verify_hmac("hush", body, signature));
+        assert!(!verify_hmac("other-secret", body, signature));
+        assert!(!verify_hmac("hush", br#"{"id":2}"#, signature));
+        assert!(!verify_hmac("hush", body, "not base64!"));
+    }
+
+    #[test]
+    fn test_webhook_dedup() {
+        let dedup = WebhookDedup::default();
+
+        assert!(dedup.first_delivery("a"));
+        assert!(!dedup.first_delivery("a"));
+        assert!(dedup.first_delivery("b"));
+    }
+}